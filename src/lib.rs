@@ -0,0 +1,395 @@
+//! Code generation core of ro-topt: reading secret keys and computing TOTP codes, kept
+//! free of the GUI so other Rust programs can use it.
+//!
+//! ```
+//! # fn main() -> Result<(), String> {
+//! # let now = 59;
+//! let code = ro_topt::compute_totp("JBSWY3DPEHPK3PXP", 6, 30, ro_topt::Algorithm::SHA1, now)?;
+//! # assert_eq!(code.len(), 6);
+//! # Ok(())
+//! # }
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use totp_rs::TOTP;
+
+pub use totp_rs::Algorithm;
+
+/// Shortest decoded secret accepted (80 bits, the common 16-character Base32 key)
+pub const MIN_SECRET_BYTES: usize = 10;
+
+/// Keys shorter than this are zero-padded up to it, the minimum totp-rs accepts
+pub const PADDED_KEY_BYTES: usize = 16;
+
+/// Alphabet a Base32 secret is written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Base32Alphabet {
+    /// A-Z and 2-7, used by nearly every provider
+    #[default]
+    Rfc4648,
+    /// "Extended hex" 0-9 and A-V from RFC 4648 section 7
+    Rfc4648Hex,
+}
+
+impl Base32Alphabet {
+    fn chars(self) -> &'static str {
+        match self {
+            Base32Alphabet::Rfc4648 => "ABCDEFGHIJKLMNOPQRSTUVWXYZ234567",
+            Base32Alphabet::Rfc4648Hex => "0123456789ABCDEFGHIJKLMNOPQRSTUV",
+        }
+    }
+}
+
+impl fmt::Display for Base32Alphabet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Base32Alphabet::Rfc4648 => f.write_str("RFC 4648"),
+            Base32Alphabet::Rfc4648Hex => f.write_str("Base32hex"),
+        }
+    }
+}
+
+/// How a secret key is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SecretFormat {
+    #[default]
+    Base32,
+    /// Hex digits, as used by RFC 6238 test vectors and some providers
+    Hex,
+}
+
+impl fmt::Display for SecretFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecretFormat::Base32 => f.write_str("Base32"),
+            SecretFormat::Hex => f.write_str("Hex"),
+        }
+    }
+}
+
+/// Why a secret key couldn't be used
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SecretError {
+    Empty,
+    InvalidBase32,
+    InvalidBase32Hex,
+    /// Strict mode only: anything but clean Base32 in the chosen alphabet
+    NotStrictBase32,
+    InvalidHex,
+    OddHexLength,
+    TooShort { bits: usize },
+}
+
+impl fmt::Display for SecretError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecretError::Empty => f.write_str("Please enter a secret key"),
+            SecretError::InvalidBase32 => {
+                f.write_str("Secret key is not valid Base32 (use letters A-Z and digits 2-7)")
+            }
+            SecretError::InvalidBase32Hex => {
+                f.write_str("Secret key is not valid Base32hex (use digits 0-9 and letters A-V)")
+            }
+            SecretError::NotStrictBase32 => f.write_str(
+                "Secret key is not clean Base32 (strict mode allows no spaces, dashes or look-alike characters)",
+            ),
+            SecretError::InvalidHex => {
+                f.write_str("Secret key is not valid hex (use digits 0-9 and letters A-F)")
+            }
+            SecretError::OddHexLength => {
+                f.write_str("Hex secret key must have an even number of digits")
+            }
+            SecretError::TooShort { bits } => write!(
+                f,
+                "Secret key is too short ({} bits, at least {} required)",
+                bits,
+                MIN_SECRET_BYTES * 8
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SecretError {}
+
+/// Current TOTP code for a Base32 secret at Unix time `now`. The secret is read leniently
+/// (grouping spaces, lowercase, look-alike digits), like a key typed into the app.
+pub fn compute_totp(
+    secret: &str,
+    digits: u8,
+    period: u64,
+    algorithm: Algorithm,
+    now: u64,
+) -> Result<String, String> {
+    let key = decode_secret(secret, SecretFormat::Base32, Base32Alphabet::Rfc4648, false)
+        .map_err(|e| e.to_string())?;
+    generate_totp_code(&pad_key(key), now, period.max(1), digits, algorithm)
+}
+
+/// RFC 6238 code for `time` from an already decoded (and padded) key
+pub fn generate_totp_code(
+    secret: &[u8],
+    time: u64,
+    period: u64,
+    digits: u8,
+    algorithm: Algorithm,
+) -> Result<String, String> {
+    TOTP::new(algorithm, digits as usize, 1, period, secret.to_vec())
+        .map(|totp| totp.generate(time))
+        .map_err(|e| format!("Invalid secret key: {}", e))
+}
+
+/// Raw bytes of a secret key written in `format`. Strict mode takes Base32 only exactly as
+/// written in `alphabet`; otherwise grouping and look-alike digits are forgiven.
+pub fn decode_secret(
+    input: &str,
+    format: SecretFormat,
+    alphabet: Base32Alphabet,
+    strict: bool,
+) -> Result<Vec<u8>, SecretError> {
+    match format {
+        SecretFormat::Hex => decode_hex(input).and_then(check_secret_length),
+        SecretFormat::Base32 if strict => decode_strict_base32(alphabet, input),
+        SecretFormat::Base32 => decode_lenient_base32(alphabet, input),
+    }
+}
+
+/// Decode a Base32 secret written in `alphabet` the forgiving way typed keys are read:
+/// grouping, lowercase, padding and look-alike digits are all accepted
+pub fn decode_secret_with(alphabet: Base32Alphabet, input: &str) -> Result<Vec<u8>, String> {
+    decode_lenient_base32(alphabet, input).map_err(|e| e.to_string())
+}
+
+/// Helper function to pad a key to at least 16 bytes (128 bits), the minimum totp-rs accepts
+pub fn pad_key(key: Vec<u8>) -> Vec<u8> {
+    // If the key is too short, extend it with zeros
+    // HMAC zero-pads keys internally, so this doesn't change the generated codes
+    if key.len() < PADDED_KEY_BYTES {
+        let mut padded = key.clone();
+        padded.resize(PADDED_KEY_BYTES, 0); // Zero-pad to 16 bytes
+        return padded;
+    }
+    key
+}
+
+/// Zero-width and other invisible characters that ride along when copying from web pages
+/// and emails
+pub fn is_hidden_char(c: char) -> bool {
+    matches!(c, '\u{200B}' | '\u{200C}' | '\u{200D}' | '\u{2060}' | '\u{FEFF}' | '\u{00AD}')
+}
+
+/// Decode a Base32 secret without any guessing: apart from surrounding whitespace and
+/// letter case, the input must be exactly Base32 in `alphabet`, with padding (if any)
+/// only at the end of a whole 8-character block
+fn decode_strict_base32(alphabet: Base32Alphabet, input: &str) -> Result<Vec<u8>, SecretError> {
+    let input = input.trim().to_uppercase();
+    if input.is_empty() {
+        return Err(SecretError::Empty);
+    }
+
+    let unpadded = input.trim_end_matches('=');
+    if unpadded.len() < input.len() && input.len() % 8 != 0 {
+        return Err(SecretError::NotStrictBase32);
+    }
+
+    match decode_base32(unpadded, alphabet) {
+        Some(decoded) => check_secret_length(decoded),
+        None => Err(SecretError::NotStrictBase32),
+    }
+}
+
+/// Decode a Base32 secret written in `alphabet`, forgiving grouping and look-alike digits
+fn decode_lenient_base32(alphabet: Base32Alphabet, input: &str) -> Result<Vec<u8>, SecretError> {
+    // Normalize the input: drop spaces/dashes used for grouping, trailing
+    // padding, and convert to uppercase
+    let normalized: String = input
+        .to_uppercase()
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-' && !is_hidden_char(*c))
+        .collect();
+    let normalized = normalized.trim_end_matches('=');
+
+    if normalized.is_empty() {
+        return Err(SecretError::Empty);
+    }
+
+    // Valid Base32 is used exactly as entered
+    if let Some(decoded) = decode_base32(normalized, alphabet) {
+        return check_secret_length(decoded);
+    }
+
+    // Digits are part of the extended-hex alphabet, so there is nothing to substitute
+    if alphabet == Base32Alphabet::Rfc4648Hex {
+        return Err(SecretError::InvalidBase32Hex);
+    }
+
+    // Only if that failed, map digits that aren't in the Base32 alphabet to the
+    // letters they're commonly mistaken for. Valid characters are never touched.
+    let substituted: String = normalized
+        .chars()
+        .map(|c| match c {
+            '0' => 'O',
+            '1' => 'I',
+            '8' => 'B',
+            other => other,
+        })
+        .collect();
+
+    match decode_base32(&substituted, alphabet) {
+        Some(decoded) => check_secret_length(decoded),
+        None => Err(SecretError::InvalidBase32),
+    }
+}
+
+/// Decode a hex secret, ignoring whitespace and an optional 0x prefix
+fn decode_hex(input: &str) -> Result<Vec<u8>, SecretError> {
+    let normalized: String = input
+        .chars()
+        .filter(|c| !c.is_whitespace() && !is_hidden_char(*c))
+        .collect();
+    let normalized = normalized
+        .strip_prefix("0x")
+        .or_else(|| normalized.strip_prefix("0X"))
+        .unwrap_or(&normalized);
+
+    if normalized.is_empty() {
+        return Err(SecretError::Empty);
+    }
+    if !normalized.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(SecretError::InvalidHex);
+    }
+    if normalized.len() % 2 != 0 {
+        return Err(SecretError::OddHexLength);
+    }
+
+    (0..normalized.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&normalized[i..i + 2], 16).map_err(|_| SecretError::InvalidHex))
+        .collect()
+}
+
+/// Reject keys too short to be a real shared secret
+fn check_secret_length(decoded: Vec<u8>) -> Result<Vec<u8>, SecretError> {
+    if decoded.len() < MIN_SECRET_BYTES {
+        return Err(SecretError::TooShort { bits: decoded.len() * 8 });
+    }
+    Ok(decoded)
+}
+
+/// Strict RFC4648 decode of an unpadded Base32 string in either alphabet
+fn decode_base32(input: &str, alphabet: Base32Alphabet) -> Option<Vec<u8>> {
+    let chars = alphabet.chars();
+    if !input.chars().all(|c| chars.contains(c)) {
+        return None;
+    }
+
+    // Extended-hex digits map one-to-one onto the standard alphabet by position
+    let standard_chars = Base32Alphabet::Rfc4648.chars().as_bytes();
+    let translated: String = input
+        .chars()
+        .filter_map(|c| chars.find(c).map(|pos| standard_chars[pos] as char))
+        .collect();
+
+    base32::decode(base32::Alphabet::RFC4648 { padding: false }, &translated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RFC6238_SHA1_KEY: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn both_alphabets_decode_to_the_same_bytes() {
+        let standard = decode_secret_with(Base32Alphabet::Rfc4648, "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        let hex = decode_secret_with(Base32Alphabet::Rfc4648Hex, "64P36D1L6ORJGE9G64P36D1L6ORJGE9G");
+        assert_eq!(standard.as_deref(), Ok(RFC6238_SHA1_KEY));
+        assert_eq!(hex.as_deref(), Ok(RFC6238_SHA1_KEY));
+
+        let bytes = b"Hello!\xde\xad\xbe\xef".as_slice();
+        assert_eq!(decode_secret_with(Base32Alphabet::Rfc4648, "JBSWY3DPEHPK3PXP").as_deref(), Ok(bytes));
+        assert_eq!(decode_secret_with(Base32Alphabet::Rfc4648Hex, "91IMOR3F47FARFNF").as_deref(), Ok(bytes));
+    }
+
+    #[test]
+    fn lenient_decoding_forgives_grouping_padding_and_look_alikes() {
+        let expected = Ok(b"Hello!\xde\xad\xbe\xef".to_vec());
+        assert_eq!(decode_secret_with(Base32Alphabet::Rfc4648, "jbsw-y3dp ehpk 3pxp"), expected);
+        assert_eq!(decode_secret_with(Base32Alphabet::Rfc4648, "JBSWY3DPEHPK3PXP======"), expected);
+        // 0 and 1 aren't Base32 digits, so they are read as O and I
+        assert_eq!(
+            decode_secret_with(Base32Alphabet::Rfc4648, "GEZDGNBVGY3TQ0JQGEZDGNBVGY3TQOJQ").as_deref(),
+            Ok(RFC6238_SHA1_KEY)
+        );
+    }
+
+    #[test]
+    fn each_alphabet_rejects_characters_outside_it() {
+        assert_eq!(
+            decode_secret_with(Base32Alphabet::Rfc4648Hex, "WXYZWXYZWXYZWXYZ"),
+            Err(SecretError::InvalidBase32Hex.to_string())
+        );
+        assert_eq!(
+            decode_secret_with(Base32Alphabet::Rfc4648, "JBSWY3DPEHPK3PX!"),
+            Err(SecretError::InvalidBase32.to_string())
+        );
+        assert_eq!(decode_secret_with(Base32Alphabet::Rfc4648, "  "), Err(SecretError::Empty.to_string()));
+        assert_eq!(
+            decode_secret_with(Base32Alphabet::Rfc4648, "JBSWY3DP"),
+            Err(SecretError::TooShort { bits: 40 }.to_string())
+        );
+    }
+
+    #[test]
+    fn decodes_hex_secrets() {
+        let decode = |input| decode_secret(input, SecretFormat::Hex, Base32Alphabet::Rfc4648, false);
+        assert_eq!(decode("3132333435363738393031323334353637383930").as_deref(), Ok(RFC6238_SHA1_KEY));
+        assert_eq!(decode("0x3132 3334 3536 3738 3930 3132 3334 3536 3738 3930").as_deref(), Ok(RFC6238_SHA1_KEY));
+        assert_eq!(decode("31323"), Err(SecretError::OddHexLength));
+        assert_eq!(decode("zz"), Err(SecretError::InvalidHex));
+    }
+
+    // Times and 8-digit codes from the test vector table in RFC 6238 appendix B
+    const RFC6238_TIMES: [u64; 6] = [59, 1111111109, 1111111111, 1234567890, 2000000000, 20000000000];
+
+    fn assert_rfc6238_codes(key: &[u8], algorithm: Algorithm, expected: [&str; 6]) {
+        for (time, code) in RFC6238_TIMES.iter().zip(expected) {
+            assert_eq!(generate_totp_code(key, *time, 30, 8, algorithm).as_deref(), Ok(code), "T = {}", time);
+        }
+    }
+
+    #[test]
+    fn matches_rfc6238_sha1_vectors() {
+        assert_rfc6238_codes(
+            RFC6238_SHA1_KEY,
+            Algorithm::SHA1,
+            ["94287082", "07081804", "14050471", "89005924", "69279037", "65353130"],
+        );
+    }
+
+    #[test]
+    fn matches_rfc6238_sha256_vectors() {
+        assert_rfc6238_codes(
+            b"12345678901234567890123456789012",
+            Algorithm::SHA256,
+            ["46119246", "68084774", "67062674", "91819424", "90698825", "77737706"],
+        );
+    }
+
+    #[test]
+    fn matches_rfc6238_sha512_vectors() {
+        assert_rfc6238_codes(
+            b"1234567890123456789012345678901234567890123456789012345678901234",
+            Algorithm::SHA512,
+            ["90693936", "25091201", "99943326", "93441116", "38618901", "47863826"],
+        );
+    }
+
+    #[test]
+    fn compute_totp_reads_a_base32_secret() {
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        assert_eq!(compute_totp(secret, 8, 30, Algorithm::SHA1, 59).as_deref(), Ok("94287082"));
+        assert_eq!(compute_totp(secret, 6, 30, Algorithm::SHA1, 59).as_deref(), Ok("287082"));
+        assert!(compute_totp("not a secret!", 6, 30, Algorithm::SHA1, 59).is_err());
+    }
+}
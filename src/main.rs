@@ -1,53 +1,974 @@
 use iced::{
-    executor, theme, time,
-    widget::{button, column, container, row, text, text_input, vertical_space},
-    Alignment, Application, Command, Element, Length, Settings, Subscription, Theme,
+    event, executor, keyboard, subscription, theme, time, window,
+    widget::{
+        button, canvas, checkbox, column, container, horizontal_space, image, pick_list, row, scrollable, text,
+        text_input, vertical_rule, vertical_space,
+    },
+    Alignment, Application, Command, Element, Event, Length, Settings, Subscription, Theme,
 };
+use chacha20poly1305::aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::ChaCha20Poly1305;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::net::UdpSocket;
+use std::path::{Path, PathBuf};
+use std::fmt;
+use std::str::FromStr;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use ro_topt::{
+    decode_secret, generate_totp_code, is_hidden_char, pad_key, Base32Alphabet, SecretError, SecretFormat,
+    PADDED_KEY_BYTES,
+};
 use totp_rs::{Algorithm, TOTP};
-use base32;
 use clipboard::ClipboardProvider;
+use qrcode::QrCode;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use base64::engine::{general_purpose::{GeneralPurpose, GeneralPurposeConfig}, DecodePaddingMode};
+use base64::{alphabet, Engine};
+use tray_icon::menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+use tray_icon::{Icon, TrayIcon, TrayIconBuilder};
+use global_hotkey::hotkey::{Code, HotKey, Modifiers};
+use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState};
 
 fn main() -> iced::Result {
-    TotpGenerator::run(Settings::default())
+    // With command-line options, print the current code and exit instead of opening the window
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{}", CLI_USAGE);
+        return Ok(());
+    }
+    let (secret_file, launch_uri) = match parse_cli_args(&args) {
+        Ok(LaunchMode::Headless(tab)) => {
+            match effective_time(0).and_then(|now| token_for_timestamp(&tab, now, false)) {
+                Ok(token) => {
+                    println!("{}", token);
+                    return Ok(());
+                }
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Ok(LaunchMode::Gui { secret_file, launch_uri }) => (secret_file, launch_uri),
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, CLI_USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    // A secret from --secret-file or TOTP_SECRET pre-fills the first tab
+    let startup_secret = resolve_startup_secret(
+        secret_file.map(|path| {
+            fs::read_to_string(&path).map_err(|e| format!("Could not read secret file {}: {}", path, e))
+        }),
+        std::env::var("TOTP_SECRET").ok(),
+    );
+
+    let state = TotpGenerator::load_state();
+    let window = state.as_ref().and_then(|state| state.window).unwrap_or_default();
+    // Starting to the tray keeps the window invisible from the start; new() shows it after
+    // all if the tray icon can't be created
+    let start_minimized = state.as_ref().is_some_and(|state| state.start_minimized);
+
+    TotpGenerator::run(Settings {
+        window: window::Settings {
+            size: window_size(&window),
+            position: window_position(&window),
+            min_size: Some(MIN_WINDOW_SIZE),
+            visible: !start_minimized,
+            ..Default::default()
+        },
+        flags: LaunchFlags { state, startup_secret, launch_uri },
+        // Closing is handled in update() so the window can go to the tray instead
+        exit_on_close_request: false,
+        ..Default::default()
+    })
+}
+
+const CLI_USAGE: &str = "Usage: ro-topt [--secret <BASE32> [--digits N] [--period N] [--algorithm SHA1|SHA256|SHA512]]
+       ro-topt [--secret-file <PATH>] [otpauth://...]
+
+Without --secret the GUI is started. With --secret the current code is printed and the program exits.
+--secret-file (or the TOTP_SECRET environment variable) pre-fills the first tab's secret in the GUI.
+An otpauth:// link opens the GUI on that account, adding a tab for it if none has the same secret.";
+
+// Supported HMAC algorithms, in the order they appear in the dropdown
+const ALGORITHMS: [Algorithm; 3] = [Algorithm::SHA1, Algorithm::SHA256, Algorithm::SHA512];
+
+// Characters escaped in otpauth URI components (everything but RFC 3986 unreserved)
+const URI_COMPONENT: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+
+// Base64 flavour used in otpauth-migration:// payloads, which some exporters leave unpadded
+const MIGRATION_BASE64: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+// Identifies backup files written by "Export backup"; bump the version when the layout changes
+const BACKUP_FORMAT: &str = "ro-topt-backup";
+const BACKUP_VERSION: u32 = 1;
+
+// PBKDF2-HMAC-SHA256 rounds used to derive the master password key
+const KDF_ROUNDS: u32 = 600_000;
+
+// The countdown bar turns amber below WARNING and red below CRITICAL seconds remaining
+const COUNTDOWN_WARNING_SECS: u64 = 8;
+const COUNTDOWN_CRITICAL_SECS: u64 = 4;
+
+// The dots countdown lights one dot per COUNTDOWN_DOT_SECS seconds, up to COUNTDOWN_MAX_DOTS
+const COUNTDOWN_DOT_SECS: u64 = 3;
+const COUNTDOWN_MAX_DOTS: u64 = 20;
+
+// Menu ids of the tray's fixed entries; tab entries use TRAY_COPY_PREFIX plus the tab index
+const TRAY_COPY_PREFIX: &str = "copy:";
+const TRAY_SHOW_ID: &str = "show";
+const TRAY_QUIT_ID: &str = "quit";
+
+// System-wide shortcut that shows or hides the window unless configured otherwise
+const DEFAULT_HOTKEY: &str = "Ctrl+Alt+T";
+
+// Side length of the generated tray icon, in pixels
+const TRAY_ICON_SIZE: u32 = 32;
+
+// How long the active code stays highlighted after it changes, in milliseconds
+const ROLLOVER_FLASH_MS: u64 = 500;
+
+// How long the "Undo" action stays available after a tab is removed
+const UNDO_REMOVE_SECS: u64 = 5;
+
+// Pause after the last edit to a secret or period before it is used to generate codes
+const SECRET_DEBOUNCE_MS: u64 = 300;
+
+// Seconds before expiry at which tabs that opted in show a desktop notification
+const EXPIRY_NOTIFY_SECS: u64 = 5;
+
+// How long success messages such as "Code copied to clipboard!" stay on screen unless
+// configured otherwise
+const DEFAULT_MESSAGE_TIMEOUT_SECS: u64 = 3;
+
+// How long a hidden code stays visible after being clicked
+const TOKEN_REVEAL_SECS: u64 = 5;
+
+// Range and step of the text scale setting
+const MIN_UI_SCALE: f32 = 0.8;
+const MAX_UI_SCALE: f32 = 2.0;
+const UI_SCALE_STEP: f32 = 0.1;
+
+// Codes kept in each tab's generation history, when history is turned on
+const HISTORY_LEN: usize = 10;
+
+// Seconds before rollover during which the next code is previewed
+const NEXT_TOKEN_PREVIEW_SECS: u64 = 5;
+
+// How long copied values stay on the clipboard unless configured otherwise
+const DEFAULT_CLIPBOARD_CLEAR_SECS: u64 = 15;
+
+// NTP server used by "Sync time"
+const NTP_HOST: &str = "pool.ntp.org";
+
+// Favicon downloads give up after FAVICON_TIMEOUT_SECS and ignore anything past
+// FAVICON_MAX_BYTES; icons are scaled down to FAVICON_SIZE pixels
+#[cfg(feature = "favicons")]
+const FAVICON_TIMEOUT_SECS: u64 = 5;
+#[cfg(feature = "favicons")]
+const FAVICON_MAX_BYTES: u64 = 256 * 1024;
+#[cfg(feature = "favicons")]
+const FAVICON_SIZE: u32 = 32;
+
+// Earliest believable system time (2020-01-01 UTC); anything before it means the clock was
+// reset, e.g. by a dead RTC battery, and codes generated from it would be wrong
+const MIN_PLAUSIBLE_UNIX_TIME: u64 = 1_577_836_800;
+
+// Seconds between the NTP epoch (1900) and the Unix epoch (1970)
+const NTP_UNIX_EPOCH_DELTA: f64 = 2_208_988_800.0;
+
+// Default window size, and the bounds a restored size is clamped to
+const DEFAULT_WINDOW_SIZE: (u32, u32) = (1024, 768);
+const MIN_WINDOW_SIZE: (u32, u32) = (400, 300);
+const MAX_WINDOW_SIZE: (u32, u32) = (8192, 8192);
+
+// Window size while in mini mode: the smallest the full window may be, since the minimum
+// size is fixed when the window opens and iced can't lower it just for mini mode
+const MINI_WINDOW_SIZE: (u32, u32) = MIN_WINDOW_SIZE;
+
+// Saved window positions beyond this distance from the origin are treated as off-screen
+const MAX_WINDOW_COORDINATE: i32 = 16_000;
+
+// Pause after the window stops moving or resizing before its geometry is saved
+const GEOMETRY_SAVE_MS: u64 = 1000;
+
+// Room kept beside the tabs for the overflow dropdown and the "+" button, at 100% scale
+const TAB_BAR_RESERVED_WIDTH: f32 = 150.0;
+
+// Average character width relative to the text size, for estimating tab widths
+const TAB_CHAR_WIDTH_EM: f32 = 0.6;
+
+// Periods offered as quick buttons next to the period field, in seconds
+const PERIOD_PRESETS: [u64; 3] = [15, 30, 60];
+
+// Supported code lengths, in the order they appear in the dropdown
+const DIGIT_OPTIONS: [u8; 3] = [6, 7, 8];
+
+// Supported token types, in the order they appear in the dropdown
+const OTP_MODES: [OtpMode; 3] = [OtpMode::Totp, OtpMode::Hotp, OtpMode::Steam];
+
+// Supported secret encodings, in the order they appear in the dropdown
+const SECRET_FORMATS: [SecretFormat; 2] = [SecretFormat::Base32, SecretFormat::Hex];
+
+// Colors a tab can be labelled with, in the order they appear in the picker
+const TAB_COLORS: [TabColor; 6] = [
+    TabColor::Red,
+    TabColor::Orange,
+    TabColor::Green,
+    TabColor::Blue,
+    TabColor::Purple,
+    TabColor::Gray,
+];
+
+// Label color for a tab, to tell accounts apart at a glance
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum TabColor {
+    Red,
+    Orange,
+    Green,
+    Blue,
+    Purple,
+    Gray,
+}
+
+impl TabColor {
+    fn color(self) -> iced::Color {
+        match self {
+            TabColor::Red => iced::Color::from_rgb8(0xE5, 0x39, 0x35),
+            TabColor::Orange => iced::Color::from_rgb8(0xFB, 0x8C, 0x00),
+            TabColor::Green => iced::Color::from_rgb8(0x43, 0xA0, 0x47),
+            TabColor::Blue => iced::Color::from_rgb8(0x1E, 0x88, 0xE5),
+            TabColor::Purple => iced::Color::from_rgb8(0x8E, 0x24, 0xAA),
+            TabColor::Gray => iced::Color::from_rgb8(0x75, 0x75, 0x75),
+        }
+    }
+}
+
+// Tab layouts, in the order they appear in the dropdown
+const TAB_LAYOUTS: [TabLayout; 2] = [TabLayout::Row, TabLayout::Sidebar];
+
+// Where the tabs are shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+enum TabLayout {
+    // A single row of tabs above the active tab
+    #[default]
+    Row,
+    // A sidebar with the tabs grouped under collapsible issuer headers
+    Sidebar,
+}
+
+impl fmt::Display for TabLayout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TabLayout::Row => f.write_str("Tab row"),
+            TabLayout::Sidebar => f.write_str("Sidebar by issuer"),
+        }
+    }
+}
+
+// Tab bar orders, in the order they appear in the dropdown
+const TAB_ORDERS: [TabOrder; 2] = [TabOrder::Manual, TabOrder::RecentlyUsed];
+
+// How unpinned (and, separately, pinned) tabs are ordered in the tab bar
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+enum TabOrder {
+    // The order tabs were added or moved into
+    #[default]
+    Manual,
+    // Most recently selected or copied first
+    RecentlyUsed,
+}
+
+impl fmt::Display for TabOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TabOrder::Manual => f.write_str("Manual order"),
+            TabOrder::RecentlyUsed => f.write_str("Recently used"),
+        }
+    }
+}
+
+// Countdown indicators, in the order they appear in the dropdown
+const COUNTDOWN_STYLES: [CountdownStyle; 3] =
+    [CountdownStyle::Bar, CountdownStyle::Dots, CountdownStyle::Ring];
+
+// How the time left on a code is drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+enum CountdownStyle {
+    // Progress bar under the timer text
+    #[default]
+    Bar,
+    // Row of dots under the timer text that go out as time passes
+    Dots,
+    // Ring next to the code
+    Ring,
+}
+
+impl fmt::Display for CountdownStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CountdownStyle::Bar => f.write_str("Bar"),
+            CountdownStyle::Dots => f.write_str("Dots"),
+            CountdownStyle::Ring => f.write_str("Ring"),
+        }
+    }
+}
+
+// Progress bar directions, in the order they appear in the dropdown
+const PROGRESS_FILLS: [ProgressFill; 2] = [ProgressFill::Remaining, ProgressFill::Elapsed];
+
+// What the countdown bar measures
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+enum ProgressFill {
+    // Full when a code appears, draining toward the rollover
+    #[default]
+    Remaining,
+    // Empty when a code appears, filling up toward the rollover
+    Elapsed,
+}
+
+impl fmt::Display for ProgressFill {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProgressFill::Remaining => f.write_str("Draining"),
+            ProgressFill::Elapsed => f.write_str("Filling"),
+        }
+    }
+}
+
+// Supported Base32 alphabets, in the order they appear in the dropdown
+const BASE32_ALPHABETS: [Base32Alphabet; 2] = [Base32Alphabet::Rfc4648, Base32Alphabet::Rfc4648Hex];
+
+// Kind of code a tab produces
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+enum OtpMode {
+    // Standard RFC 6238 decimal codes
+    #[default]
+    Totp,
+    // RFC 4226 counter-based codes, advanced by hand
+    Hotp,
+    // Steam Guard: 5 characters from Steam's own alphabet, always HMAC-SHA1
+    Steam,
+}
+
+impl fmt::Display for OtpMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OtpMode::Totp => f.write_str("TOTP"),
+            OtpMode::Hotp => f.write_str("HOTP"),
+            OtpMode::Steam => f.write_str("Steam"),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
+#[allow(clippy::enum_variant_names)]
 enum Message {
     SecretKeyChanged(String, usize), // Added tab index parameter
-    DigitsChanged(u8),
-    PeriodChanged(u64),
-    GenerateToken, // Kept for backward compatibility
+    // Id of the secret edit to generate for; edit ids are unique, so they also find the tab
+    GenerateDebounced(u64),
+    DigitsChanged(u8, usize),
+    PeriodChanged(u64, usize),
+    PeriodInputChanged(String, usize),
+    // Id of the period edit to commit once typing has paused
+    PeriodDebounced(u64),
+    GenerateToken(usize),
+    AlgorithmChanged(Algorithm, usize),
+    ModeChanged(OtpMode, usize),
+    SecretFormatChanged(SecretFormat, usize),
+    Base32AlphabetChanged(Base32Alphabet, usize),
+    GenerateNext(usize),
     CopyToClipboard(usize), // Added tab index parameter
+    CopyAndHide(usize),
+    CopyActiveToken,
+    SelectableTokenEdited,
+    ToggleSecretVisibility(usize),
+    CopyAllTokens,
+    ExportUri(usize),
+    ImportFile,
+    BackupPassphraseChanged(String),
+    ExportBackup,
+    BackupExported(Result<Option<String>, String>),
+    ImportBackup,
+    BackupLoaded(Result<Option<Vec<Tab>>, String>),
+    ImportFileLoaded(Result<Option<String>, String>),
+    ImportCsvFile,
+    CsvFileLoaded(Result<Option<String>, String>),
+    ImportCsv(String),
+    ExportCsv,
+    CsvExported(Result<Option<String>, String>),
+    FileDropped(PathBuf),
+    QrImageDecoded(Result<String, String>),
+    ShowQr(usize),
+    ToggleTimePreview,
+    TimePreviewInputChanged(String),
+    PreviewAtTime(u64),
     Tick,
-    ClearMessage(usize), // Added tab index parameter
+    ClearClipboard(String),
+    ClipboardClearDelayChanged(u64),
+    MessageTimeoutChanged(u64),
+    ClockOffsetChanged(String),
+    SyncTime,
+    TimeSynced(Result<i64, String>),
     AddTab,
     RemoveTab(usize),
+    DuplicateTab(usize),
+    RequestRemoveTab(usize),
+    RequestRemoveActiveTab,
+    ConfirmRemoveTab,
+    CancelRemoveTab,
+    UndoRemoveTab,
+    UndoExpired(u64),
     SelectTab(usize),
+    CycleTab(bool), // true = forward
+    SelectTabNumber(u8),
+    MoveTab { from: usize, to: usize },
+    TogglePinned(usize),
+    ShowTabMenu(usize),
+    CloseTabMenu,
+    TabMenuSelected(TabMenuAction),
+    FilterChanged(String),
     RenameTabStarted(usize),
-    TabNameChanged(String, usize),
+    RenameActiveTab,
+    ConfirmRenames,
+    CancelRenames,
+    IssuerChanged(String, usize),
+    AccountChanged(String, usize),
     TabNameConfirmed(usize),
+    PasswordInputChanged(String),
+    Unlock,
+    // Key derived (and tabs decrypted) in the background after Unlock
+    Unlocked(Result<(VaultKey, Vec<Tab>), String>),
+    SetMasterPassword,
+    MasterPasswordDerived(Result<VaultKey, String>),
+    RemoveMasterPassword,
+    ToggleTheme,
+    HighContrastToggled(bool),
+    CountdownStyleChanged(CountdownStyle),
+    ProgressFillChanged(ProgressFill),
+    TabOrderChanged(TabOrder),
+    TabLayoutChanged(TabLayout),
+    ToggleIssuerGroup(String),
+    ScaleUp,
+    ScaleDown,
+    ResetScale,
+    HideTokensToggled(bool),
+    CopyGroupedToggled(bool),
+    VerifyClipboardToggled(bool),
+    DebugInfoToggled(bool),
+    GraceWindowToggled(bool),
+    StrictBase32Toggled(bool),
+    HideWhenUnfocusedToggled(bool),
+    ToggleMiniMode,
+    MiniOnTopToggled(bool),
+    ShowFaviconsToggled(bool),
+    FaviconLoaded(String, Option<image::Handle>),
+    WindowFocusChanged(bool),
+    RevealToken(usize),
+    TabColorChanged(Option<TabColor>, usize),
+    ExpiryNotifyToggled(bool, usize),
+    AutoCopyToggled(bool, usize),
+    AutoCopyBackgroundToggled(bool),
+    HistoryToggled(bool),
+    FlashOnRolloverToggled(bool),
+    RolloverFlashEnded(u64),
+    ToggleHistoryPanel,
+    CloseToTrayToggled(bool),
+    StartMinimizedToggled(bool),
+    WindowCloseRequested,
+    ShowWindow,
+    ToggleWindowVisibility,
+    HotkeyInputChanged(String),
+    HotkeyConfirmed,
+    Quit,
+    WindowResized(u32, u32),
+    WindowMoved(i32, i32),
+    // An expiry notification was handed to the desktop (or failed to be; that's ignored)
+    ExpiryNotified,
+    // Debounced save of the window geometry, with the generation it was scheduled for
+    GeometrySettled(u64),
 }
 
-#[derive(Debug, Clone)]
+// Only the account settings are saved; runtime state is rebuilt on load
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 struct Tab {
-    name: String,
+    // Tabs saved before issuer and account were split only had a `name`
+    #[serde(alias = "name")]
+    issuer: String,
+    account: String,
     secret_key: String,
+    #[serde(skip)]
     token: String,
+    #[serde(skip)]
+    next_token: String,
+    #[serde(skip)]
     error: Option<String>,
+    #[serde(skip)]
     time_remaining: u64,
+    #[serde(skip)]
     editing_name: bool,
+    // Issuer and account from before the rename started, restored by Escape
+    #[serde(skip)]
+    name_before_edit: Option<(String, String)>,
+    algorithm: Algorithm,
+    digits: u8,
+    period: u64,
+    // Period field as typed, while it differs from `period`; committed once typing pauses
+    // and it reads as at least 1 second
+    #[serde(skip)]
+    period_input: Option<String>,
+    // Id of the latest edit to the period field, like `secret_edit`
+    #[serde(skip)]
+    period_edit: u64,
+    mode: OtpMode,
+    secret_format: SecretFormat,
+    base32_alphabet: Base32Alphabet,
+    counter: u64,
+    #[serde(skip)]
+    show_qr: bool,
+    // The secret field is masked like a password unless this is set; reset on tab switch
+    #[serde(skip)]
+    secret_visible: bool,
+    // Stray whitespace and invisible characters in the secret as typed, which decoding skips
+    #[serde(skip)]
+    ignored_secret_chars: usize,
+    // Recent codes with the time they were generated, oldest first; never saved
+    #[serde(skip)]
+    history: VecDeque<(u64, String)>,
+    // Padded key from the last successful decode, so ticks don't decode the secret again
+    #[serde(skip)]
+    decoded: Option<DecodedKey>,
+    // Whether the code is showing while codes are hidden, and for how many more seconds
+    #[serde(skip)]
+    revealed: bool,
+    #[serde(skip)]
+    reveal_secs_left: u64,
+    // Seconds until a success message in `error` is cleared; 0 leaves it up
+    #[serde(skip)]
+    message_secs_left: u64,
+    // Time step the current code was generated for
+    #[serde(skip)]
+    last_counter: u64,
+    // The code was refreshed by a tick that arrived after its step began (e.g. after the
+    // machine was busy), so an older code was briefly on screen
+    #[serde(skip)]
+    late_refresh: bool,
+    // Id of the latest edit to the secret; a debounced generation only runs if it still matches
+    #[serde(skip)]
+    secret_edit: u64,
+    // Label color for the tab button; None keeps the theme's colors
+    color: Option<TabColor>,
+    // Pinned tabs are shown before the others
+    pinned: bool,
+    // Show a desktop notification shortly before the code expires
+    notify_before_expiry: bool,
+    // Unix time the tab was last selected or copied from, for the "recently used" order
+    last_used: Option<u64>,
+    // `last_used` as of the last time the "recently used" order was refreshed. The order is
+    // sorted by this, so tabs don't jump around under the mouse while they're clicked through.
+    #[serde(skip)]
+    shown_last_used: Option<u64>,
+    // Put each new code on the clipboard as soon as it is generated
+    auto_copy: bool,
+    // Opened for a secret passed at launch; never written to the saved state
+    #[serde(skip)]
+    transient: bool,
+}
+
+// Entries of the right-click menu on a tab
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TabMenuAction {
+    Rename,
+    Duplicate,
+    Remove,
+    TogglePin,
+    SetColor(Option<TabColor>),
+}
+
+// How the window gets out of the way after "Copy and hide"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HideAction {
+    ToTray,
+    Minimize,
+}
+
+// What the command line asked for
+#[derive(Debug)]
+enum LaunchMode {
+    Gui { secret_file: Option<String>, launch_uri: Option<String> },
+    Headless(Tab),
+}
+
+// Startup data handed to the application
+#[derive(Default)]
+struct LaunchFlags {
+    state: Option<SavedState>,
+    startup_secret: Option<Result<String, String>>,
+    launch_uri: Option<String>,
+}
+
+// On-disk layout of the saved state file. When a master password is set the
+// tabs are stored encrypted in `vault` and `tabs` is left empty.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct SavedState {
+    tabs: Vec<Tab>,
+    vault: Option<Vault>,
+    active_tab: usize,
+    dark_theme: bool,
+    high_contrast: bool,
+    window: Option<WindowGeometry>,
+    clipboard_clear_secs: Option<u64>,
+    message_timeout_secs: Option<u64>,
+    clock_offset_secs: i64,
+    // Written alongside countdown_style so older versions still pick the ring
+    countdown_ring: bool,
+    countdown_style: Option<CountdownStyle>,
+    progress_fill: ProgressFill,
+    tab_order: TabOrder,
+    tab_layout: TabLayout,
+    ui_scale: Option<f32>,
+    hide_tokens: bool,
+    copy_grouped: bool,
+    verify_clipboard: bool,
+    show_debug: bool,
+    show_grace_window: bool,
+    strict_base32: bool,
+    auto_copy_background: bool,
+    keep_history: bool,
+    flash_on_rollover: bool,
+    hide_when_unfocused: bool,
+    mini_on_top: bool,
+    show_favicons: bool,
+    close_to_tray: bool,
+    start_minimized: bool,
+    // Global show/hide shortcut; an empty string disables it
+    hotkey: Option<String>,
+}
+
+// Last known window size and position
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct WindowGeometry {
+    width: u32,
+    height: u32,
+    x: Option<i32>,
+    y: Option<i32>,
+}
+
+impl Default for WindowGeometry {
+    fn default() -> Self {
+        Self {
+            width: DEFAULT_WINDOW_SIZE.0,
+            height: DEFAULT_WINDOW_SIZE.1,
+            x: None,
+            y: None,
+        }
+    }
+}
+
+// Backup file holding every tab, encrypted with its own passphrase
+#[derive(Debug, Serialize, Deserialize)]
+struct BackupFile {
+    format: String,
+    version: u32,
+    vault: Vault,
+}
+
+// Encrypted tab list, with each field Base32-encoded
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Vault {
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+// Key derived from the master password, kept in memory so saves don't rerun the KDF
+#[derive(Clone)]
+struct VaultKey {
+    key: [u8; 32],
+    salt: Vec<u8>,
+}
+
+// Messages carry keys around, so keep the key itself out of debug output
+impl fmt::Debug for VaultKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VaultKey").field("key", &"<redacted>").finish()
+    }
+}
+
+// Tab listed in the tab bar's overflow dropdown
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TabChoice {
+    idx: usize,
+    label: String,
+}
+
+impl fmt::Display for TabChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.label)
+    }
+}
+
+// One tab's entry in the tray menu
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TrayMenuEntry {
+    label: String,
+    // Tabs without a code yet can't be copied
+    enabled: bool,
+}
+
+// A tab's padded key along with the secret and settings it was decoded from
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DecodedKey {
+    secret: String,
+    format: SecretFormat,
+    alphabet: Base32Alphabet,
+    strict: bool,
+    key: Vec<u8>,
+}
+
+impl DecodedKey {
+    // Whether this is still the key for `tab`'s current secret
+    fn matches(&self, tab: &Tab, strict: bool) -> bool {
+        self.secret == tab.secret_key
+            && self.format == tab.secret_format
+            && self.alphabet == tab.base32_alphabet
+            && self.strict == strict
+    }
+}
+
+// Account settings extracted from an otpauth:// URI
+#[derive(Debug, Clone, PartialEq)]
+struct ParsedOtp {
+    issuer: Option<String>,
+    account: String,
+    secret: String,
+    digits: u8,
+    period: u64,
+    algorithm: Algorithm,
+    mode: OtpMode,
+    // Starting counter of an HOTP account; always 0 for time-based ones
+    counter: u64,
+    // Parameters that were out of range and replaced, e.g. "digits=10 is not supported, using 8"
+    adjusted: Vec<String>,
 }
 
 impl Default for Tab {
     fn default() -> Self {
         Self {
-            name: String::from("New Tab"),
+            issuer: String::from("New Tab"),
+            account: String::new(),
             secret_key: String::new(),
             token: String::new(),
+            next_token: String::new(),
             error: None,
             time_remaining: 30,
             editing_name: true,
+            name_before_edit: None,
+            algorithm: Algorithm::SHA1,
+            digits: 6,
+            period: 30,
+            period_input: None,
+            period_edit: 0,
+            mode: OtpMode::Totp,
+            secret_format: SecretFormat::Base32,
+            base32_alphabet: Base32Alphabet::Rfc4648,
+            counter: 0,
+            show_qr: false,
+            secret_visible: false,
+            ignored_secret_chars: 0,
+            history: VecDeque::new(),
+            decoded: None,
+            revealed: false,
+            reveal_secs_left: 0,
+            message_secs_left: 0,
+            last_counter: 0,
+            late_refresh: false,
+            secret_edit: 0,
+            color: None,
+            pinned: false,
+            notify_before_expiry: false,
+            last_used: None,
+            shown_last_used: None,
+            auto_copy: false,
+            transient: false,
+        }
+    }
+}
+
+impl Tab {
+    // Show the name fields, remembering the current name so the rename can be cancelled
+    fn start_rename(&mut self) {
+        if !self.editing_name {
+            self.name_before_edit = Some((self.issuer.clone(), self.account.clone()));
+            self.editing_name = true;
+        }
+    }
+
+    // Keep the name as edited
+    fn confirm_rename(&mut self) {
+        self.editing_name = false;
+        self.name_before_edit = None;
+    }
+
+    // Put back the name from before the rename started
+    fn cancel_rename(&mut self) {
+        if let Some((issuer, account)) = self.name_before_edit.take() {
+            self.issuer = issuer;
+            self.account = account;
+        }
+        self.editing_name = false;
+    }
+
+    // Add a generated code to the history, dropping the oldest once it holds `cap` codes
+    fn record_history(&mut self, timestamp: u64, token: String, cap: usize) {
+        if cap == 0 {
+            return;
+        }
+        while self.history.len() >= cap {
+            self.history.pop_front();
+        }
+        self.history.push_back((timestamp, token));
+    }
+
+    // Show a hidden code for the next TOKEN_REVEAL_SECS ticks
+    fn reveal(&mut self) {
+        self.revealed = true;
+        self.reveal_secs_left = TOKEN_REVEAL_SECS;
+    }
+
+    fn hide(&mut self) {
+        self.revealed = false;
+        self.reveal_secs_left = 0;
+    }
+
+    // Count down a revealed code once per Tick, hiding it again when the time is up
+    fn tick_reveal(&mut self) {
+        if self.revealed {
+            self.reveal_secs_left = self.reveal_secs_left.saturating_sub(1);
+            if self.reveal_secs_left == 0 {
+                self.hide();
+            }
+        }
+    }
+
+    // Show a success message that clears itself after `timeout_secs` ticks; 0 leaves it up
+    fn show_timed_message(&mut self, message: String, timeout_secs: u64) {
+        self.error = Some(message);
+        self.message_secs_left = timeout_secs;
+    }
+
+    // Count down a timed message once per Tick. Only success messages are cleared, so an
+    // error that replaced the message in the meantime stays up.
+    fn tick_message(&mut self) {
+        if self.message_secs_left == 0 {
+            return;
+        }
+        self.message_secs_left -= 1;
+        if self.message_secs_left == 0 && self.error.as_deref().is_some_and(is_success_message) {
+            self.error = None;
+        }
+    }
+
+    // Whether the time step has changed since the displayed code was generated
+    fn needs_regeneration(&self, now: u64) -> bool {
+        !self.token.is_empty()
+            && self.mode != OtpMode::Hotp
+            && current_counter(now, self.period) != self.last_counter
+    }
+
+    // Copy of the tab's settings for a similar account, named "... (copy)" and left without
+    // a secret so the original key isn't reused by accident. Starts in name-edit mode.
+    fn duplicate(&self) -> Tab {
+        let (issuer, account) = if self.issuer.trim().is_empty() {
+            (self.issuer.clone(), format!("{} (copy)", self.account.trim()))
+        } else {
+            (format!("{} (copy)", self.issuer.trim()), self.account.clone())
+        };
+
+        Tab {
+            issuer,
+            account,
+            algorithm: self.algorithm,
+            digits: self.digits,
+            period: self.period,
+            mode: self.mode,
+            secret_format: self.secret_format,
+            base32_alphabet: self.base32_alphabet,
+            color: self.color,
+            pinned: self.pinned,
+            notify_before_expiry: self.notify_before_expiry,
+            auto_copy: self.auto_copy,
+            ..Default::default()
+        }
+    }
+
+    // Take over every setting from a parsed otpauth:// URI, naming the tab after its label.
+    // Returns a warning to show if the URI had values that had to be adjusted.
+    fn apply_parsed_otp(&mut self, parsed: ParsedOtp) -> Option<String> {
+        self.issuer = parsed.issuer.unwrap_or_default();
+        self.account = parsed.account;
+        self.editing_name = false;
+        self.name_before_edit = None;
+        self.secret_key = parsed.secret;
+        self.digits = parsed.digits;
+        self.period = parsed.period;
+        self.period_input = None;
+        self.algorithm = parsed.algorithm;
+        self.mode = parsed.mode;
+        self.counter = parsed.counter;
+        self.secret_format = SecretFormat::Base32;
+        self.base32_alphabet = Base32Alphabet::Rfc4648;
+        self.ignored_secret_chars = 0;
+
+        (!parsed.adjusted.is_empty())
+            .then(|| format!("Adjusted the otpauth URI: {}", parsed.adjusted.join("; ")))
+    }
+
+    // Raw bytes of the tab's secret, read with its format and alphabet
+    fn decode_key(&self, strict: bool) -> Result<Vec<u8>, SecretError> {
+        decode_secret(&self.secret_key, self.secret_format, self.base32_alphabet, strict)
+    }
+
+    // Padded key for generating codes. The cached one is reused while the secret and the
+    // way it is read are unchanged, so any edit to them invalidates it on the next call.
+    fn padded_key(&mut self, strict: bool) -> Result<Vec<u8>, SecretError> {
+        if let Some(decoded) = &self.decoded {
+            if decoded.matches(self, strict) {
+                return Ok(decoded.key.clone());
+            }
+        }
+
+        let key = pad_key(self.decode_key(strict)?);
+        self.decoded = Some(DecodedKey {
+            secret: self.secret_key.clone(),
+            format: self.secret_format,
+            alphabet: self.base32_alphabet,
+            strict,
+            key: key.clone(),
+        });
+        Ok(key)
+    }
+
+    // Label shown in the tab header, e.g. "GitHub (octocat)"
+    fn display_name(&self) -> String {
+        let issuer = self.issuer.trim();
+        let account = self.account.trim();
+
+        match (issuer.is_empty(), account.is_empty()) {
+            (false, false) => format!("{} ({})", issuer, account),
+            (false, true) => issuer.to_string(),
+            (true, _) => account.to_string(),
         }
     }
 }
@@ -55,8 +976,121 @@ impl Default for Tab {
 struct TotpGenerator {
     tabs: Vec<Tab>,
     active_tab: usize,
-    digits: u8,
-    period: u64,
+    // Tab whose "×" was clicked, waiting for the removal to be confirmed
+    pending_removal: Option<usize>,
+    // Tab whose right-click menu is open
+    tab_menu: Option<usize>,
+    // Most recently removed tab and where it was, while it can still be undone
+    last_removed: Option<(usize, Tab)>,
+    // Bumped on every removal so an older timeout doesn't clear a newer stash
+    removal_generation: u64,
+    // Source of Tab::secret_edit ids, shared by all tabs so ids stay unique when tabs move
+    secret_edits: u64,
+    // Source of Tab::period_edit ids
+    period_edits: u64,
+    locked: bool,
+    locked_vault: Option<Vault>,
+    vault_key: Option<VaultKey>,
+    password_input: String,
+    password_error: Option<String>,
+    // A master password key is being derived; further submits are ignored until it's done
+    deriving_key: bool,
+    dark_theme: bool,
+    // Black and white instead of the soft grays, for readability
+    high_contrast: bool,
+    // NO_COLOR is set, which forces high contrast for this run without touching the setting
+    no_color: bool,
+    window: WindowGeometry,
+    // Bumped on every resize or move so only the last one in a drag saves the geometry
+    geometry_generation: u64,
+    filter: String,
+    // Seconds before copied values are wiped from the clipboard; 0 disables it
+    clipboard_clear_secs: u64,
+    // Seconds before success messages disappear; 0 keeps them until replaced
+    message_timeout_secs: u64,
+    last_copied: Option<String>,
+    // Let tabs with auto-copy on copy their new codes even when they aren't the active tab
+    auto_copy_background: bool,
+    // Keep each tab's recent codes in memory for the history panel; off by default for privacy
+    keep_history: bool,
+    history_open: bool,
+    // Briefly highlight the active code when it rolls over while the window is in view
+    flash_on_rollover: bool,
+    flashing: bool,
+    // Bumped on every flash so the timer of an earlier one doesn't cut a newer one short
+    flash_generation: u64,
+    // Tabs whose new code is waiting to be auto-copied once the current message is handled
+    pending_auto_copies: Vec<usize>,
+    // Set when copying failed because there is no usable clipboard (e.g. headless, or
+    // Wayland without a portal); the code is then shown as selectable text instead
+    clipboard_unavailable: bool,
+    // Seconds added to the system clock before generating codes
+    clock_offset_secs: i64,
+    // Effective time of the previous Tick, to notice when the machine was asleep in between
+    last_tick: Option<u64>,
+    clock_offset_input: String,
+    // Whether the countdown is drawn as a bar, a row of dots or a ring next to the code
+    countdown_style: CountdownStyle,
+    // Whether the countdown bar drains (time left) or fills up (time elapsed)
+    progress_fill: ProgressFill,
+    // Manual or most-recently-used order for the tab bar; pinned tabs come first either way
+    tab_order: TabOrder,
+    tab_layout: TabLayout,
+    // Issuer groups folded away in the sidebar layout, by issuer_group_key
+    collapsed_groups: HashSet<String>,
+    // Multiplier applied to every text size, for accessibility
+    scale: f32,
+    // Mask codes until they are clicked, for screen sharing
+    hide_tokens: bool,
+    // Copy codes with the display grouping ("123 456") instead of as plain digits
+    copy_grouped: bool,
+    // Read the clipboard back after copying and warn if it doesn't hold what was written
+    verify_clipboard: bool,
+    // Show the time step, timestamp and key length behind the active tab's code
+    show_debug: bool,
+    // Show the previous, current and next codes side by side, as a server allowing ±1 step would
+    show_grace_window: bool,
+    // Reject Base32 secrets that would otherwise be cleaned up (spacing, look-alike digits)
+    strict_base32: bool,
+    // Mask codes while the window doesn't have focus, against shoulder-surfing
+    hide_when_unfocused: bool,
+    focused: bool,
+    // Compact window showing only the active code and its countdown, optionally kept on top
+    mini: bool,
+    mini_on_top: bool,
+    // Show each tab's service icon, fetched from the issuer's website
+    show_favicons: bool,
+    // Favicons by issuer domain; None while loading or when the site has no usable icon
+    favicons: HashMap<String, Option<image::Handle>>,
+    // Issuers the favicons were last looked up for; lookups only run again once they change
+    favicon_issuers: Vec<String>,
+    // Hide the window instead of quitting when it is closed, if the tray icon is available
+    close_to_tray: bool,
+    // Launch hidden in the tray, e.g. when started at login
+    start_minimized: bool,
+    tray: Option<TrayIcon>,
+    // Entries the tray menu was last built from, so it is only rebuilt when they change
+    tray_entries: Vec<TrayMenuEntry>,
+    window_hidden: bool,
+    // Global show/hide shortcut as typed, and the one currently registered with the OS
+    hotkey_input: String,
+    hotkey_manager: Option<GlobalHotKeyManager>,
+    hotkey: Option<HotKey>,
+    // Why the shortcut couldn't be registered, shown next to the setting
+    hotkey_error: Option<String>,
+    syncing_time: bool,
+    // Outcome of the last NTP sync, shown under the clock offset
+    time_sync_status: Option<Result<String, String>>,
+    // "Code at another time" panel: whether it is open, what was typed and the instant
+    // whose code is shown. Nothing here touches the live codes.
+    time_preview_open: bool,
+    time_preview_input: String,
+    time_preview_at: Option<u64>,
+    backup_passphrase: String,
+    // Secret from the command line or environment, applied once the tabs are loaded
+    startup_secret: Option<Result<String, String>>,
+    // otpauth:// link the app was opened with, applied the same way
+    launch_uri: Option<String>,
 }
 
 impl Default for TotpGenerator {
@@ -64,8 +1098,74 @@ impl Default for TotpGenerator {
         Self {
             tabs: vec![Tab::default()],
             active_tab: 0,
-            digits: 6,
-            period: 30,
+            pending_removal: None,
+            tab_menu: None,
+            last_removed: None,
+            removal_generation: 0,
+            secret_edits: 0,
+            period_edits: 0,
+            locked: false,
+            locked_vault: None,
+            vault_key: None,
+            password_input: String::new(),
+            password_error: None,
+            deriving_key: false,
+            dark_theme: false,
+            high_contrast: false,
+            no_color: false,
+            window: WindowGeometry::default(),
+            geometry_generation: 0,
+            filter: String::new(),
+            clipboard_clear_secs: DEFAULT_CLIPBOARD_CLEAR_SECS,
+            message_timeout_secs: DEFAULT_MESSAGE_TIMEOUT_SECS,
+            last_copied: None,
+            auto_copy_background: false,
+            pending_auto_copies: Vec::new(),
+            keep_history: false,
+            flash_on_rollover: false,
+            flashing: false,
+            flash_generation: 0,
+            history_open: false,
+            clipboard_unavailable: false,
+            clock_offset_secs: 0,
+            last_tick: None,
+            clock_offset_input: String::from("0"),
+            countdown_style: CountdownStyle::Bar,
+            progress_fill: ProgressFill::Remaining,
+            tab_order: TabOrder::Manual,
+            tab_layout: TabLayout::Row,
+            collapsed_groups: HashSet::new(),
+            scale: 1.0,
+            hide_tokens: false,
+            copy_grouped: false,
+            verify_clipboard: false,
+            show_debug: false,
+            show_grace_window: false,
+            strict_base32: false,
+            hide_when_unfocused: false,
+            focused: true,
+            mini: false,
+            mini_on_top: false,
+            show_favicons: false,
+            favicons: HashMap::new(),
+            favicon_issuers: Vec::new(),
+            close_to_tray: false,
+            start_minimized: false,
+            tray: None,
+            tray_entries: Vec::new(),
+            window_hidden: false,
+            hotkey_input: String::from(DEFAULT_HOTKEY),
+            hotkey_manager: None,
+            hotkey: None,
+            hotkey_error: None,
+            syncing_time: false,
+            time_sync_status: None,
+            time_preview_open: false,
+            time_preview_input: String::new(),
+            time_preview_at: None,
+            backup_passphrase: String::new(),
+            startup_secret: None,
+            launch_uri: None,
         }
     }
 }
@@ -74,194 +1174,332 @@ impl Application for TotpGenerator {
     type Executor = executor::Default;
     type Message = Message;
     type Theme = Theme;
-    type Flags = ();
+    type Flags = LaunchFlags;
+
+    fn new(flags: LaunchFlags) -> (Self, Command<Message>) {
+        let mut app = Self {
+            startup_secret: flags.startup_secret,
+            launch_uri: flags.launch_uri,
+            ..Self::default()
+        };
+
+        // NO_COLOR (https://no-color.org) asks for the high-contrast palette on every launch
+        let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+
+        if let Some(state) = flags.state {
+            app.dark_theme = state.dark_theme;
+            app.high_contrast = state.high_contrast;
+            app.active_tab = state.active_tab;
+            app.window = state.window.unwrap_or_default();
+            app.clipboard_clear_secs = state
+                .clipboard_clear_secs
+                .unwrap_or(DEFAULT_CLIPBOARD_CLEAR_SECS);
+            app.message_timeout_secs = state
+                .message_timeout_secs
+                .unwrap_or(DEFAULT_MESSAGE_TIMEOUT_SECS);
+            app.clock_offset_secs = state.clock_offset_secs;
+            app.clock_offset_input = state.clock_offset_secs.to_string();
+            app.countdown_style = state.countdown_style.unwrap_or(if state.countdown_ring {
+                CountdownStyle::Ring
+            } else {
+                CountdownStyle::Bar
+            });
+            app.progress_fill = state.progress_fill;
+            app.tab_order = state.tab_order;
+            app.tab_layout = state.tab_layout;
+            app.scale = clamp_ui_scale(state.ui_scale.unwrap_or(1.0));
+            app.hide_tokens = state.hide_tokens;
+            app.copy_grouped = state.copy_grouped;
+            app.verify_clipboard = state.verify_clipboard;
+            app.show_debug = state.show_debug;
+            app.show_grace_window = state.show_grace_window;
+            app.strict_base32 = state.strict_base32;
+            app.auto_copy_background = state.auto_copy_background;
+            app.keep_history = state.keep_history;
+            app.flash_on_rollover = state.flash_on_rollover;
+            app.hide_when_unfocused = state.hide_when_unfocused;
+            app.mini_on_top = state.mini_on_top;
+            app.show_favicons = state.show_favicons;
+            app.close_to_tray = state.close_to_tray;
+            app.start_minimized = state.start_minimized;
+            app.hotkey_input = state.hotkey.unwrap_or_else(|| DEFAULT_HOTKEY.to_string());
+
+            match state.vault {
+                // Encrypted tabs stay locked until the master password is entered
+                Some(vault) => {
+                    app.locked = true;
+                    app.locked_vault = Some(vault);
+                }
+                None => app.restore_tabs(state.tabs),
+            }
+        }
+
+        app.no_color = no_color;
+
+        if !app.locked {
+            app.apply_startup_secret();
+            app.apply_launch_uri();
+        }
+
+        // Without a system tray (e.g. some Linux desktops) the app just runs windowed
+        app.tray_entries = tray_menu_entries(&app.tabs, app.locked);
+        app.tray = build_tray_icon(&app.tray_entries);
+
+        // main() opened the window invisible if this was set; without a tray icon to bring
+        // it back from, it is shown after all
+        app.window_hidden = start_hidden(app.start_minimized, app.tray.is_some());
+        let show_window = if app.start_minimized && !app.window_hidden {
+            window::change_mode(window::Mode::Windowed)
+        } else {
+            Command::none()
+        };
+
+        match GlobalHotKeyManager::new() {
+            Ok(manager) => {
+                app.hotkey_manager = Some(manager);
+                app.register_hotkey();
+            }
+            Err(e) => app.hotkey_error = Some(format!("Global shortcuts are unavailable: {}", e)),
+        }
 
-    fn new(_flags: ()) -> (Self, Command<Message>) {
-        (Self::default(), Command::none())
+        let command = Command::batch(vec![show_window, app.fetch_missing_favicons()]);
+        (app, command)
     }
 
     fn title(&self) -> String {
         String::from("TOTP Token Generator")
     }
 
+    fn theme(&self) -> Theme {
+        match (self.high_contrast || self.no_color, self.dark_theme) {
+            (true, dark) => Theme::custom(app_palette(dark, true)),
+            (false, true) => Theme::Dark,
+            (false, false) => Theme::Light,
+        }
+    }
+
     fn update(&mut self, message: Message) -> Command<Message> {
-        match message {
-            Message::SecretKeyChanged(value, tab_index) => {
-                if tab_index < self.tabs.len() {
-                    let tab = &mut self.tabs[tab_index];
-                    tab.secret_key = value;
-                    tab.error = None;
-                    
-                    // Generate token automatically if secret key is not empty
-                    if !tab.secret_key.is_empty() {
-                        self.generate_token(tab_index);
-                    } else {
-                        tab.token = String::new();
-                    }
-                }
-            }
-            Message::DigitsChanged(_) => {
-                // We keep the default value of 6 digits
-                self.digits = 6;
-            }
-            Message::PeriodChanged(_) => {
-                // We keep the default value of 30 seconds
-                self.period = 30;
-            }
-            Message::GenerateToken => {
-                // For backward compatibility - uses active tab
-                self.generate_token(self.active_tab);
-            }
-            Message::CopyToClipboard(tab_index) => {
-                if tab_index < self.tabs.len() && !self.tabs[tab_index].token.is_empty() {
-                    let token = self.tabs[tab_index].token.clone();
-                    let mut ctx: clipboard::ClipboardContext = match ClipboardProvider::new() {
-                        Ok(ctx) => ctx,
-                        Err(e) => {
-                            self.tabs[tab_index].error = Some(format!("Failed to access clipboard: {}", e));
-                            return Command::none();
-                        }
-                    };
-                    
-                    if let Err(e) = ctx.set_contents(token.replace(" ", "")) {
-                        self.tabs[tab_index].error = Some(format!("Failed to copy to clipboard: {}", e));
-                    } else {
-                        self.tabs[tab_index].error = Some("Code copied to clipboard!".to_string());
-                        // Clear the message after 3 seconds
-                        return Command::perform(
-                            async move {
-                                std::thread::sleep(std::time::Duration::from_secs(3));
-                                tab_index
-                            },
-                            |idx| Message::ClearMessage(idx),
-                        );
-                    }
-                }
-            }
-            Message::ClearMessage(tab_index) => {
-                // Clear any success/error message for the specified tab
-                if tab_index < self.tabs.len() {
-                    self.tabs[tab_index].error = None;
-                }
-            }
-            Message::Tick => {
-                // Update time remaining for all tabs
-                let now = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_secs();
-                
-                // Collect indices that need regeneration
-                let mut indices_to_regenerate = Vec::new();
-                
-                // First pass: update time remaining
-                for (idx, tab) in self.tabs.iter_mut().enumerate() {
-                    if !tab.token.is_empty() {
-                        tab.time_remaining = self.period - (now % self.period);
-                        
-                        // Mark for token regeneration when time expires
-                        if tab.time_remaining == self.period {
-                            indices_to_regenerate.push(idx);
-                        }
-                    }
-                }
-                
-                // Second pass: regenerate tokens for expired tabs
-                for idx in indices_to_regenerate {
-                    self.generate_token(idx);
-                }
-            }
-            Message::AddTab => {
-                // Create a new tab with default values and add it to the list
-                let new_tab = Tab {
-                    name: format!("Tab {}", self.tabs.len() + 1),
-                    ..Default::default()
-                };
-                self.tabs.push(new_tab);
-                self.active_tab = self.tabs.len() - 1;
-            }
-            Message::RemoveTab(idx) => {
-                if self.tabs.len() > 1 && idx < self.tabs.len() {
-                    self.tabs.remove(idx);
-                    // Adjust active_tab if necessary
-                    if self.active_tab >= self.tabs.len() {
-                        self.active_tab = self.tabs.len() - 1;
-                    }
-                }
-            }
-            Message::SelectTab(idx) => {
-                if idx < self.tabs.len() {
-                    self.active_tab = idx;
-                }
-            }
-            Message::RenameTabStarted(idx) => {
-                if idx < self.tabs.len() {
-                    self.tabs[idx].editing_name = true;
-                }
-            }
-            Message::TabNameChanged(name, idx) => {
-                if idx < self.tabs.len() {
-                    self.tabs[idx].name = name;
-                }
-            }
-            Message::TabNameConfirmed(idx) => {
-                if idx < self.tabs.len() {
-                    self.tabs[idx].editing_name = false;
-                }
-            }
+        // Ticks and message dismissals don't change anything worth saving, and window
+        // geometry is saved once it settles
+        let persist = !matches!(
+            message,
+            Message::Tick
+                | Message::ClearClipboard(_)
+                | Message::WindowResized(..)
+                | Message::WindowMoved(..)
+                | Message::GeometrySettled(_)
+                | Message::ExpiryNotified
+                | Message::UndoExpired(_)
+                | Message::GenerateDebounced(..)
+                | Message::PeriodInputChanged(..)
+                | Message::WindowFocusChanged(_)
+                | Message::FaviconLoaded(..)
+                | Message::ToggleTimePreview
+                | Message::TimePreviewInputChanged(_)
+                | Message::PreviewAtTime(_)
+                | Message::SelectableTokenEdited
+                | Message::ToggleHistoryPanel
+                | Message::ToggleIssuerGroup(_)
+                | Message::ToggleSecretVisibility(_)
+                | Message::ToggleMiniMode
+                | Message::RolloverFlashEnded(_)
+        );
+
+        let command = self.handle_message(message);
+        if persist {
+            self.save_state();
         }
-        Command::none()
+        self.refresh_tray_menu();
+        Command::batch(vec![command, self.auto_copy_new_tokens(), self.fetch_missing_favicons()])
     }
 
     fn subscription(&self) -> Subscription<Message> {
-        time::every(Duration::from_secs(1))
-            .map(|_| Message::Tick)
+        // The once-a-second timer only runs while something on screen is counting down
+        let tick = if needs_tick(&self.tabs) {
+            time::every(Duration::from_secs(1)).map(|_| Message::Tick)
+        } else {
+            Subscription::none()
+        };
+
+        Subscription::batch(vec![
+            tick,
+            subscription::events_with(handle_event),
+            subscription::unfold("tray-menu-events", (), |_| async {
+                // recv() blocks until the tray menu is used, so it waits on the blocking pool
+                let event = run_blocking(|| MenuEvent::receiver().recv().map_err(|e| e.to_string())).await;
+                let message = event.ok().and_then(|event| tray_menu_message(&event.id.0));
+                (message, ())
+            }),
+            subscription::unfold("global-hotkey-events", (), |_| async {
+                // Waits on the blocking pool like the tray menu. Only the key press toggles
+                // the window, not its release.
+                let event = run_blocking(|| GlobalHotKeyEvent::receiver().recv().map_err(|e| e.to_string())).await;
+                let message = event
+                    .ok()
+                    .filter(|event| event.state == HotKeyState::Pressed)
+                    .map(|_| Message::ToggleWindowVisibility);
+                (message, ())
+            }),
+        ])
     }
 
-    fn view(&self) -> Element<Message> {
-        // Title with improved styling
+    fn view(&self) -> Element<'_, Message> {
+        if self.locked {
+            return self.locked_view();
+        }
+        if self.mini {
+            return self.mini_view();
+        }
+
+        // Colors come from the current theme so both light and dark modes stay readable
+        let palette = self.theme().palette();
+        let muted_text = muted_text_color(&self.theme());
+
+        // Title with improved styling, plus the light/dark toggle
+        let theme_toggle = button(
+            text(if self.dark_theme { "Light mode" } else { "Dark mode" }).size(self.scaled(14.0)),
+        )
+            .padding(6)
+            .style(theme::Button::Secondary)
+            .on_press(Message::ToggleTheme);
+
+        // Text size controls; Ctrl+= / Ctrl+- do the same
+        let mut scale_down = button(text("A-").size(self.scaled(14.0)))
+            .padding(6)
+            .style(theme::Button::Secondary);
+        if self.scale > MIN_UI_SCALE {
+            scale_down = scale_down.on_press(Message::ScaleDown);
+        }
+        let mut scale_up = button(text("A+").size(self.scaled(14.0)))
+            .padding(6)
+            .style(theme::Button::Secondary);
+        if self.scale < MAX_UI_SCALE {
+            scale_up = scale_up.on_press(Message::ScaleUp);
+        }
+        let scale_controls = row![
+            scale_down,
+            button(text(format!("{:.0}%", self.scale * 100.0)).size(self.scaled(12.0)))
+                .padding(6)
+                .style(theme::Button::Text)
+                .on_press(Message::ResetScale),
+            scale_up,
+        ]
+        .spacing(2)
+        .align_items(Alignment::Center);
+
         let title = container(
-            text("TOTP Token Generator")
-                .size(30)
-                .style(iced::theme::Text::Color(iced::Color::from_rgb(0.1, 0.1, 0.1)))
+            row![
+                container(
+                    text("TOTP Token Generator")
+                        .size(self.scaled(30.0))
+                        .style(iced::theme::Text::Color(palette.text))
+                )
+                .width(Length::Fill)
+                .center_x(),
+                scale_controls,
+                button(text("Copy all").size(self.scaled(14.0)))
+                    .padding(6)
+                    .style(theme::Button::Secondary)
+                    .on_press(Message::CopyAllTokens),
+                button(text("Mini").size(self.scaled(14.0)))
+                    .padding(6)
+                    .style(theme::Button::Secondary)
+                    .on_press(Message::ToggleMiniMode),
+                theme_toggle,
+            ]
+            .spacing(10)
+            .align_items(Alignment::Center)
         )
         .width(Length::Fill)
-        .center_x()
         .padding([0, 0, 10, 0]);
 
         // Create the tab bar with a bottom border
         let mut tab_row = row![].spacing(2).padding([5, 5, 0, 5]);
         
-        // Add tabs
-        for (idx, tab) in self.tabs.iter().enumerate() {
+        // Search box that narrows down which tabs are shown
+        let filter_input = text_input("Search tabs", &self.filter)
+            .on_input(Message::FilterChanged)
+            .padding(6)
+            .size(self.scaled(14.0));
+        let filter_row = row![
+            filter_input,
+            pick_list(&TAB_ORDERS[..], Some(self.tab_order), Message::TabOrderChanged)
+                .text_size(self.scaled(14.0))
+                .padding(6),
+            pick_list(&TAB_LAYOUTS[..], Some(self.tab_layout), Message::TabLayoutChanged)
+                .text_size(self.scaled(14.0))
+                .padding(6),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        // Add tabs in the chosen order (pinned ones first), skipping the ones hidden by the search
+        // box. In the sidebar layout the tabs are in the sidebar and only the "+" button is here.
+        let order = match self.tab_layout {
+            TabLayout::Row => self.ordered_tab_indices(),
+            TabLayout::Sidebar => Vec::new(),
+        };
+        let mut shown_tabs: Vec<(usize, f32)> = Vec::new();
+        for (position, &idx) in order.iter().enumerate() {
+            let tab = &self.tabs[idx];
+            if !tab_matches_filter(&tab.display_name(), &self.filter) {
+                continue;
+            }
+
             let is_active = idx == self.active_tab;
+
+            // Rough width for the overflow dropdown: the active tab also has its pin and
+            // move buttons, and every tab has a close button once there are two
+            let extra_buttons = usize::from(self.tabs.len() > 1) + if is_active { 3 } else { 0 };
+            shown_tabs.push((idx, estimated_tab_width(&tab.display_name(), self.scaled(14.0), extra_buttons)));
             
             // Create content for the tab
             let tab_content = if tab.editing_name {
-                // Show text input for rename with a save button
-                let tab_name_input = text_input("Tab name", &tab.name)
-                    .on_input(move |name| Message::TabNameChanged(name, idx))
+                // Show text inputs for the issuer and account; Enter in either one saves
+                // and Escape puts the old name back
+                let issuer_input = text_input("Issuer", &tab.issuer)
+                    .id(rename_input_id(idx))
+                    .on_input(move |issuer| Message::IssuerChanged(issuer, idx))
+                    .on_submit(Message::TabNameConfirmed(idx))
+                    .width(Length::Fixed(100.0));
+
+                let account_input = text_input("Account", &tab.account)
+                    .on_input(move |account| Message::AccountChanged(account, idx))
                     .on_submit(Message::TabNameConfirmed(idx))
                     .width(Length::Fixed(100.0));
                 
                 container(
                     row![
-                        tab_name_input,
+                        issuer_input,
+                        account_input,
                     ].spacing(5)
                 )
                 .padding(5)
             } else {
-                // Show tab name with styling
-                container(text(&tab.name).size(14))
+                // Show tab name with styling, after the service icon if there is one
+                let label = text(tab.display_name()).size(self.scaled(14.0));
+                match self.tab_favicon(tab) {
+                    Some(icon) => container(
+                        row![
+                            image(icon)
+                                .width(Length::Fixed(self.scaled(16.0)))
+                                .height(Length::Fixed(self.scaled(16.0))),
+                            label,
+                        ]
+                        .spacing(6)
+                        .align_items(Alignment::Center),
+                    ),
+                    None => container(label),
+                }
             };
             
             // Use button for the tab instead of container
             let tab_button = button(tab_content)
                 .padding(8)
                 .style(if is_active {
-                    theme::Button::Custom(Box::new(ActiveTabButtonStyle))
+                    theme::Button::Custom(Box::new(ActiveTabButtonStyle(tab.color)))
                 } else {
-                    theme::Button::Custom(Box::new(InactiveTabButtonStyle))
+                    theme::Button::Custom(Box::new(InactiveTabButtonStyle(tab.color)))
                 })
                 .on_press(Message::SelectTab(idx));
                 
@@ -283,11 +1521,11 @@ impl Application for TotpGenerator {
             };
             
             // Only add X button if we have more than one tab
-            let tab_with_close_button = if self.tabs.len() > 1 {
+            let mut tab_with_close_button = if self.tabs.len() > 1 {
                 row![
                     tab_with_rename,
-                    button(text("×").size(14))
-                        .on_press(Message::RemoveTab(idx))
+                    button(text("×").size(self.scaled(14.0)))
+                        .on_press(Message::RequestRemoveTab(idx))
                         .padding(5)
                         .style(theme::Button::Destructive)
                 ]
@@ -296,22 +1534,154 @@ impl Application for TotpGenerator {
             } else {
                 row![tab_with_rename]
             };
+
+            // The active tab gets a pin toggle, and arrows to move it left or right
+            // within its group (pinned or not) unless the order is by recent use
+            if is_active {
+                let pin_button = button(text(if tab.pinned { "★" } else { "☆" }).size(self.scaled(14.0)))
+                    .padding(5)
+                    .style(theme::Button::Secondary)
+                    .on_press(Message::TogglePinned(idx));
+                tab_with_close_button = row![pin_button, tab_with_close_button]
+                    .align_items(Alignment::Center)
+                    .spacing(2);
+            }
+
+            if is_active && self.tabs.len() > 1 && self.tab_order == TabOrder::Manual {
+                let same_group = |other: &&usize| self.tabs[**other].pinned == tab.pinned;
+
+                let mut move_left = button(text("<").size(self.scaled(14.0)))
+                    .padding(5)
+                    .style(theme::Button::Secondary);
+                if let Some(&to) = position.checked_sub(1).and_then(|prev| order.get(prev)).filter(same_group) {
+                    move_left = move_left.on_press(Message::MoveTab { from: idx, to });
+                }
+
+                let mut move_right = button(text(">").size(self.scaled(14.0)))
+                    .padding(5)
+                    .style(theme::Button::Secondary);
+                if let Some(&to) = order.get(position + 1).filter(same_group) {
+                    move_right = move_right.on_press(Message::MoveTab { from: idx, to });
+                }
+
+                tab_with_close_button = row![move_left, tab_with_close_button, move_right]
+                    .align_items(Alignment::Center)
+                    .spacing(2);
+            }
             
-            tab_row = tab_row.push(tab_with_close_button);
+            // Right-clicking anywhere on the tab opens its menu
+            tab_row = tab_row.push(RightClickArea::new(tab_with_close_button, Message::ShowTabMenu(idx)));
         }
         
         // Add "+" button to create new tab
         let add_tab_button = button(
             text("+")
-                .size(20)
-                .style(iced::theme::Text::Color(iced::Color::from_rgb(0.0, 0.5, 0.0)))
+                .size(self.scaled(20.0))
+                .style(iced::theme::Text::Color(palette.success))
         )
         .on_press(Message::AddTab)
         .padding(5)
         .style(theme::Button::Secondary);
+
+        // Tabs that don't fit scroll sideways, and the ones past the edge are also listed in a
+        // dropdown. The "+" button stays outside the scrolling part so it is always reachable.
+        let widths: Vec<f32> = shown_tabs.iter().map(|&(_, width)| width).collect();
+        let available = self.window.width as f32 - self.scaled(TAB_BAR_RESERVED_WIDTH);
+        let overflow: Vec<TabChoice> = shown_tabs[tabs_that_fit(&widths, available)..]
+            .iter()
+            .map(|&(idx, _)| TabChoice {
+                idx,
+                label: self.tabs[idx].display_name(),
+            })
+            .collect();
+        let mut tab_bar = row![
+            scrollable(tab_row)
+                .horizontal_scroll(scrollable::Properties::new().width(4).scroller_width(4))
+                .width(Length::Fill),
+        ]
+        .spacing(5)
+        .align_items(Alignment::Center);
+        if !overflow.is_empty() {
+            let placeholder = format!("▾ {} more", overflow.len());
+            tab_bar = tab_bar.push(
+                pick_list(overflow, None, |choice: TabChoice| Message::SelectTab(choice.idx))
+                    .placeholder(placeholder)
+                    .text_size(self.scaled(14.0))
+                    .padding(6),
+            );
+        }
+        tab_bar = tab_bar.push(add_tab_button);
         
-        tab_row = tab_row.push(add_tab_button);
-        
+        // Sidebar layout: tabs grouped by issuer under headers that fold the group away
+        let sidebar = (self.tab_layout == TabLayout::Sidebar).then(|| {
+            let mut groups = column![].spacing(4).padding(10).width(Length::Fixed(self.scaled(200.0)));
+            for (issuer, indices) in self.grouped_tabs() {
+                let visible: Vec<usize> = indices
+                    .into_iter()
+                    .filter(|&idx| tab_matches_filter(&self.tabs[idx].display_name(), &self.filter))
+                    .collect();
+                if visible.is_empty() {
+                    continue;
+                }
+
+                let collapsed = self.collapsed_groups.contains(&issuer_group_key(&issuer));
+                let header = text(format!(
+                    "{} {} ({})",
+                    if collapsed { "▸" } else { "▾" },
+                    if issuer.is_empty() { "No issuer" } else { issuer.as_str() },
+                    visible.len()
+                ))
+                .size(self.scaled(13.0))
+                .style(iced::theme::Text::Color(muted_text));
+                groups = groups.push(
+                    button(header)
+                        .padding(4)
+                        .style(theme::Button::Text)
+                        .on_press(Message::ToggleIssuerGroup(issuer.clone())),
+                );
+                if collapsed {
+                    continue;
+                }
+
+                // The issuer is already in the header, so entries show just the account
+                for idx in visible {
+                    let tab = &self.tabs[idx];
+                    if tab.editing_name {
+                        groups = groups.push(
+                            column![
+                                text_input("Issuer", &tab.issuer)
+                                    .id(rename_input_id(idx))
+                                    .on_input(move |issuer| Message::IssuerChanged(issuer, idx))
+                                    .on_submit(Message::TabNameConfirmed(idx)),
+                                text_input("Account", &tab.account)
+                                    .on_input(move |account| Message::AccountChanged(account, idx))
+                                    .on_submit(Message::TabNameConfirmed(idx)),
+                            ]
+                            .spacing(4),
+                        );
+                        continue;
+                    }
+
+                    let label = match (tab.account.trim(), tab.display_name()) {
+                        ("", name) if name.is_empty() => format!("Tab {}", idx + 1),
+                        ("", name) => name,
+                        (account, _) => account.to_string(),
+                    };
+                    let entry = button(text(label).size(self.scaled(14.0)))
+                        .width(Length::Fill)
+                        .padding(6)
+                        .style(if idx == self.active_tab {
+                            theme::Button::Custom(Box::new(ActiveTabButtonStyle(tab.color)))
+                        } else {
+                            theme::Button::Custom(Box::new(InactiveTabButtonStyle(tab.color)))
+                        })
+                        .on_press(Message::SelectTab(idx));
+                    groups = groups.push(RightClickArea::new(entry, Message::ShowTabMenu(idx)));
+                }
+            }
+            scrollable(groups).height(Length::Fill)
+        });
+
         // Add a horizontal separator line below the tabs
         let tab_separator = container(
             iced::widget::horizontal_rule(1)
@@ -321,17 +1691,128 @@ impl Application for TotpGenerator {
         
         // Get the currently active tab
         let active_tab = &self.tabs[self.active_tab];
+
+        // The active tab stays usable when filtered out, with a note explaining why it's missing
+        let filter_notice = if tab_matches_filter(&active_tab.display_name(), &self.filter) {
+            text("").size(0)
+        } else {
+            text(format!("\"{}\" is hidden by the search filter", active_tab.display_name()))
+                .size(self.scaled(12.0))
+                .style(iced::theme::Text::Color(muted_text))
+        };
+
+        // Right-click menu for a tab, shown under the tab bar
+        let tab_menu = match self.tab_menu.and_then(|idx| self.tabs.get(idx)) {
+            Some(tab) => {
+                let menu_button = |label: &str, action: TabMenuAction| {
+                    button(text(label).size(self.scaled(14.0)))
+                        .padding(6)
+                        .style(theme::Button::Secondary)
+                        .on_press(Message::TabMenuSelected(action))
+                };
+
+                let mut menu = row![
+                    text(tab.display_name()).size(self.scaled(14.0)),
+                    menu_button("Rename", TabMenuAction::Rename),
+                    menu_button("Duplicate", TabMenuAction::Duplicate),
+                    menu_button(if tab.pinned { "Unpin" } else { "Pin" }, TabMenuAction::TogglePin),
+                ]
+                .spacing(6)
+                .align_items(Alignment::Center);
+                if self.tabs.len() > 1 {
+                    menu = menu.push(menu_button("Remove", TabMenuAction::Remove));
+                }
+                for color in std::iter::once(None).chain(TAB_COLORS.into_iter().map(Some)) {
+                    menu = menu.push(
+                        button(text("").size(self.scaled(12.0)))
+                            .width(Length::Fixed(self.scaled(20.0)))
+                            .height(Length::Fixed(self.scaled(20.0)))
+                            .style(theme::Button::Custom(Box::new(TabColorSwatchStyle {
+                                color,
+                                selected: tab.color == color,
+                            })))
+                            .on_press(Message::TabMenuSelected(TabMenuAction::SetColor(color))),
+                    );
+                }
+                menu = menu.push(
+                    button(text("×").size(self.scaled(14.0)))
+                        .padding(6)
+                        .style(theme::Button::Text)
+                        .on_press(Message::CloseTabMenu),
+                );
+
+                container(menu)
+                    .padding([6, 8, 6, 8])
+                    .style(theme::Container::Box)
+            }
+            None => container(text("").size(0)),
+        };
+
+        // Inline confirmation shown after a tab's "×" is clicked
+        let removal_prompt = match self.pending_removal.and_then(|idx| self.tabs.get(idx)) {
+            Some(tab) => container(
+                row![
+                    text(format!("Remove \"{}\"?", tab.display_name()))
+                        .size(self.scaled(14.0))
+                        .style(iced::theme::Text::Color(palette.danger)),
+                    button(text("Confirm").size(self.scaled(14.0)))
+                        .padding(6)
+                        .style(theme::Button::Destructive)
+                        .on_press(Message::ConfirmRemoveTab),
+                    button(text("Cancel").size(self.scaled(14.0)))
+                        .padding(6)
+                        .style(theme::Button::Secondary)
+                        .on_press(Message::CancelRemoveTab),
+                ]
+                .spacing(10)
+                .align_items(Alignment::Center),
+            )
+            .padding([8, 12, 8, 12])
+            .style(theme::Container::Custom(Box::new(ErrorMessageStyle))),
+            None => match &self.last_removed {
+                // Short-lived undo for the tab that was just removed
+                Some((_, tab)) => container(
+                    row![
+                        text(format!("Removed \"{}\"", tab.display_name()))
+                            .size(self.scaled(14.0))
+                            .style(iced::theme::Text::Color(muted_text)),
+                        button(text("Undo").size(self.scaled(14.0)))
+                            .padding(6)
+                            .style(theme::Button::Secondary)
+                            .on_press(Message::UndoRemoveTab),
+                    ]
+                    .spacing(10)
+                    .align_items(Alignment::Center),
+                )
+                .padding([8, 12, 8, 12]),
+                None => container(text("").size(0)),
+            },
+        };
         
         // Secret Key Input with placeholder text
+        // Masked like a password unless revealed; pasting works either way
         let secret_key_input = text_input("Enter your secret key", &active_tab.secret_key)
             .padding(12)
-            .size(16)
+            .size(self.scaled(16.0))
             .style(theme::TextInput::Default)
+            .secure(!active_tab.secret_visible)
             .on_input(|value| Message::SecretKeyChanged(value, self.active_tab));
+        let secret_visibility_button = button(
+            text(if active_tab.secret_visible { "Hide" } else { "Show" }).size(self.scaled(14.0)),
+        )
+        .padding(6)
+        .style(theme::Button::Secondary)
+        .on_press(Message::ToggleSecretVisibility(self.active_tab));
+        let mut refresh_button = button(text("↻").size(self.scaled(14.0)))
+            .padding(6)
+            .style(theme::Button::Secondary);
+        if !active_tab.secret_key.is_empty() {
+            refresh_button = refresh_button.on_press(Message::GenerateToken(self.active_tab));
+        }
 
         // Progress Bar for Countdown
         let progress_percentage = if !active_tab.token.is_empty() {
-            (active_tab.time_remaining as f32) / (self.period as f32)
+            progress_value(active_tab.time_remaining, active_tab.period, self.progress_fill)
         } else {
             0.0
         };
@@ -340,73 +1821,367 @@ impl Application for TotpGenerator {
         let progress_bar = iced::widget::progress_bar(0.0..=1.0, progress_percentage)
             .height(iced::Length::Fixed(6.0))  // Slightly taller for better visibility
             .width(Length::Fill)
-            .style(theme::ProgressBar::Primary);
+            .style(theme::ProgressBar::Custom(Box::new(CountdownBarStyle(
+                countdown_urgency(active_tab.time_remaining),
+            ))));
+
+        // Dots that go out one by one as the period runs down, in the bar's urgency color
+        let (lit_dots, total_dots) = if !active_tab.token.is_empty() {
+            countdown_lit_dots(active_tab.time_remaining, active_tab.period)
+        } else {
+            (0, 0)
+        };
+        let lit_color = urgency_color(countdown_urgency(active_tab.time_remaining), &self.theme());
+        let unlit_color = iced::Color { a: 0.2, ..palette.text };
+        let countdown_dots = (0..total_dots).fold(row![].spacing(4), |dots, idx| {
+            dots.push(
+                text("●")
+                    .size(self.scaled(10.0))
+                    .style(iced::theme::Text::Color(if idx < lit_dots { lit_color } else { unlit_color })),
+            )
+        });
 
+        // The code shown is always for the current step; a late refresh is flagged quietly
         let timer_text = if !active_tab.token.is_empty() {
-            text(format!("Code expires in {} seconds", active_tab.time_remaining))
-                .size(14)
-                .style(iced::theme::Text::Color(iced::Color::from_rgb(0.3, 0.3, 0.3)))
+            let freshness = if active_tab.last_counter == current_counter(
+                effective_now(self.clock_offset_secs),
+                active_tab.period,
+            ) {
+                if active_tab.late_refresh { " · valid now (refreshed late)" } else { " · valid now" }
+            } else {
+                " · updating…"
+            };
+            text(format!(
+                "Code expires in {}{}",
+                format_remaining(active_tab.time_remaining),
+                freshness
+            ))
+                .size(self.scaled(14.0))
+                .style(iced::theme::Text::Color(muted_text))
         } else {
-            text("").size(14)
+            text("").size(self.scaled(14.0))
         };
 
-        // Token Output
+        // HOTP codes don't expire, so they get a button to advance the counter instead
+        let countdown = if active_tab.mode == OtpMode::Hotp {
+            let mut next_button = button(text("Generate next").size(self.scaled(14.0)))
+                .padding(6)
+                .style(theme::Button::Secondary);
+            if !active_tab.secret_key.is_empty() {
+                next_button = next_button.on_press(Message::GenerateNext(self.active_tab));
+            }
+
+            column![
+                text(format!("Counter: {}", active_tab.counter))
+                    .size(self.scaled(14.0))
+                    .style(iced::theme::Text::Color(muted_text)),
+                vertical_space(5),
+                next_button,
+            ]
+        } else {
+            let mut countdown_style_picker = row![
+                text("Countdown:").size(self.scaled(12.0)),
+                pick_list(
+                    &COUNTDOWN_STYLES[..],
+                    Some(self.countdown_style),
+                    Message::CountdownStyleChanged,
+                )
+                .text_size(self.scaled(12.0))
+                .padding(4),
+            ]
+            .spacing(5)
+            .align_items(Alignment::Center);
+            if self.countdown_style == CountdownStyle::Bar {
+                countdown_style_picker = countdown_style_picker.push(
+                    pick_list(&PROGRESS_FILLS[..], Some(self.progress_fill), Message::ProgressFillChanged)
+                        .text_size(self.scaled(12.0))
+                        .padding(4),
+                );
+            }
+
+            let tab_index = self.active_tab;
+            let notify_toggle = checkbox(
+                format!("Notify {}s before the code expires", EXPIRY_NOTIFY_SECS),
+                active_tab.notify_before_expiry,
+                move |notify| Message::ExpiryNotifyToggled(notify, tab_index),
+            )
+            .size(self.scaled(14.0))
+            .text_size(self.scaled(12.0));
+            let auto_copy_toggle = checkbox(
+                "Copy each new code automatically",
+                active_tab.auto_copy,
+                move |auto_copy| Message::AutoCopyToggled(auto_copy, tab_index),
+            )
+            .size(self.scaled(14.0))
+            .text_size(self.scaled(12.0));
+
+            // The ring sits next to the code, so only the bar and the dots need a place here
+            match self.countdown_style {
+                CountdownStyle::Bar => column![
+                    timer_text,
+                    vertical_space(5),
+                    progress_bar,
+                    vertical_space(5),
+                    countdown_style_picker,
+                    notify_toggle,
+                    auto_copy_toggle
+                ],
+                CountdownStyle::Dots => column![
+                    timer_text,
+                    vertical_space(5),
+                    countdown_dots,
+                    vertical_space(5),
+                    countdown_style_picker,
+                    notify_toggle,
+                    auto_copy_toggle
+                ],
+                CountdownStyle::Ring => column![
+                    timer_text,
+                    vertical_space(5),
+                    countdown_style_picker,
+                    notify_toggle,
+                    auto_copy_toggle
+                ],
+            }
+        }
+        .width(Length::Fill)
+        .align_items(Alignment::Center);
+
+        // Token Output. Every code of the active tab shown anywhere below (next code, grace
+        // window, history, time preview) is masked by the same rule as the main one.
+        let obscured = tokens_obscured(self.hide_when_unfocused, self.focused);
+        let codes_masked = code_masked(self.hide_tokens, active_tab.revealed, obscured);
         let token_display = if !active_tab.token.is_empty() {
-            // Format the token with spaces for better readability
-            // e.g., "123456" becomes "123 456" if 6 digits
-            let formatted_token = if active_tab.token.len() == 6 {
-                format!("{} {}", &active_tab.token[..3], &active_tab.token[3..])
-            } else if active_tab.token.len() == 8 {
-                format!("{} {}", &active_tab.token[..4], &active_tab.token[4..])
+            let masked = self.hide_tokens && !active_tab.revealed;
+            let formatted_token = if masked || obscured {
+                mask_token(&active_tab.token)
             } else {
-                active_tab.token.clone()
+                format_token(&active_tab.token)
             };
 
-            let token_container = container(
-                text(&formatted_token)
-                    .size(48)
-                    .style(iced::theme::Text::Color(iced::Color::from_rgb(0.2, 0.2, 0.2)))
-            )
-            .width(Length::Fill)
-            .padding(25)
-            .center_x();
+            let token_widget: Element<_> = if selectable_token_fallback(self.clipboard_unavailable, masked || obscured) {
+                // Without a clipboard the code sits in a field so it can be selected and copied
+                // by hand; edits are ignored, so it always shows the current code
+                text_input("", &copied_token(&active_tab.token, self.copy_grouped))
+                    .on_input(|_| Message::SelectableTokenEdited)
+                    .size(self.scaled(48.0))
+                    .padding([5, 15])
+                    .width(Length::Fixed(self.scaled(280.0)))
+                    .into()
+            } else {
+                // The code itself is a button too, styled as plain text with a hover highlight
+                button(
+                    text(&formatted_token)
+                        .size(self.scaled(48.0))
+                        .style(iced::theme::Text::Color(palette.text))
+                )
+                .padding([5, 15])
+                .style(theme::Button::Custom(Box::new(TokenButtonStyle)))
+                // A hidden code is revealed by the first click; the Copy button works either way
+                .on_press(if masked {
+                    Message::RevealToken(self.active_tab)
+                } else {
+                    Message::CopyToClipboard(self.active_tab)
+                })
+                .into()
+            };
+            let token_container = container(token_widget)
+                .width(Length::Fill)
+                .padding(20)
+                .center_x()
+                .style(if self.flashing {
+                    theme::Container::Custom(Box::new(RolloverFlashStyle))
+                } else {
+                    theme::Container::Transparent
+                });
 
             // Regular button with blue background for copy functionality
             let copy_button = button(
                 text("Copy")
-                    .size(16)
-                    .style(iced::theme::Text::Color(iced::Color::WHITE))
+                    .size(self.scaled(16.0))
+                    .style(iced::theme::Text::Color(self.theme().extended_palette().primary.base.text))
             )
             .padding(10)
             .style(theme::Button::Custom(Box::new(BlueButtonStyle)))
             .on_press(Message::CopyToClipboard(self.active_tab));
 
-            row![
-                token_container,
-                copy_button
-            ]
-            .spacing(10)
-            .align_items(Alignment::Center)
+            let copy_and_hide_button = button(text("Copy & hide").size(self.scaled(14.0)))
+                .padding(10)
+                .style(theme::Button::Secondary)
+                .on_press(Message::CopyAndHide(self.active_tab));
+
+            let mut token_row = row![].spacing(10).align_items(Alignment::Center);
+            if self.countdown_style == CountdownStyle::Ring && active_tab.mode != OtpMode::Hotp {
+                token_row = token_row.push(
+                    canvas(CountdownRing {
+                        sweep: countdown_sweep_angle(active_tab.time_remaining, active_tab.period),
+                        urgency: countdown_urgency(active_tab.time_remaining),
+                    })
+                    .width(Length::Fixed(self.scaled(48.0)))
+                    .height(Length::Fixed(self.scaled(48.0))),
+                );
+            }
+
+            token_row.push(token_container).push(copy_button).push(copy_and_hide_button)
         } else {
             row![container(text("").size(0)).width(Length::Fill)]
         };
 
+        // Dimmed preview of the upcoming code during the last few seconds of the window
+        let next_token_display = if active_tab.mode != OtpMode::Hotp
+            && !active_tab.token.is_empty()
+            && !active_tab.next_token.is_empty()
+            && active_tab.time_remaining <= NEXT_TOKEN_PREVIEW_SECS
+        {
+            text(format!(
+                "next: {}",
+                if codes_masked {
+                    mask_token(&active_tab.next_token)
+                } else {
+                    format_token(&active_tab.next_token)
+                }
+            ))
+                .size(self.scaled(16.0))
+                .style(iced::theme::Text::Color(muted_text))
+        } else {
+            text("").size(0)
+        };
+
+        // Codes a server accepting ±1 step would take, for working out which one it matched
+        let grace_window_display = if self.show_grace_window
+            && active_tab.mode != OtpMode::Hotp
+            && !active_tab.token.is_empty()
+        {
+            let now = effective_now(self.clock_offset_secs);
+            let labels = ["previous", "current", "next"];
+            grace_window_timestamps(now, active_tab.period)
+                .into_iter()
+                .zip(labels)
+                .fold(row![].spacing(30), |codes, (timestamp, label)| {
+                    let code = match token_for_timestamp(active_tab, timestamp, self.strict_base32) {
+                        Ok(token) if codes_masked => mask_token(&token),
+                        Ok(token) => format_token(&token),
+                        Err(_) => "—".to_string(),
+                    };
+                    codes.push(
+                        column![
+                            text(label)
+                                .size(self.scaled(12.0))
+                                .style(iced::theme::Text::Color(muted_text)),
+                            text(code).size(self.scaled(16.0)),
+                        ]
+                        .align_items(Alignment::Center),
+                    )
+                })
+        } else {
+            row![]
+        };
+
+        // Codes the active tab produced recently, newest first, for matching against server logs
+        let history_panel = if self.keep_history {
+            let header = button(
+                text(format!(
+                    "{} Recent codes ({})",
+                    if self.history_open { "▾" } else { "▸" },
+                    active_tab.history.len()
+                ))
+                .size(self.scaled(14.0)),
+            )
+            .padding(4)
+            .style(theme::Button::Text)
+            .on_press(Message::ToggleHistoryPanel);
+
+            let entries = if self.history_open {
+                active_tab.history.iter().rev().fold(column![].spacing(2), |entries, (timestamp, token)| {
+                    entries.push(
+                        text(format!(
+                            "{}  {}",
+                            format_utc_time(*timestamp),
+                            if codes_masked {
+                                mask_token(token)
+                            } else {
+                                format_token(token)
+                            }
+                        ))
+                        .size(self.scaled(12.0))
+                        .style(iced::theme::Text::Color(muted_text)),
+                    )
+                })
+            } else {
+                column![]
+            };
+            column![header, entries].align_items(Alignment::Center)
+        } else {
+            column![]
+        };
+
+        // Export the active tab as an otpauth:// URI or QR code (only once a secret is set)
+        let mut export_button = button(text("Export URI").size(self.scaled(14.0)))
+            .padding(6)
+            .style(theme::Button::Secondary);
+        let mut qr_button = button(
+            text(if active_tab.show_qr { "Hide QR" } else { "Show QR" }).size(self.scaled(14.0)),
+        )
+            .padding(6)
+            .style(theme::Button::Secondary);
+        if !active_tab.secret_key.is_empty() {
+            export_button = export_button.on_press(Message::ExportUri(self.active_tab));
+            qr_button = qr_button.on_press(Message::ShowQr(self.active_tab));
+        }
+        let import_button = button(text("Import file").size(self.scaled(14.0)))
+            .padding(6)
+            .style(theme::Button::Secondary)
+            .on_press(Message::ImportFile);
+        let import_csv_button = button(text("Import list").size(self.scaled(14.0)))
+            .padding(6)
+            .style(theme::Button::Secondary)
+            .on_press(Message::ImportCsvFile);
+        let export_csv_button = button(text("Export list").size(self.scaled(14.0)))
+            .padding(6)
+            .style(theme::Button::Secondary)
+            .on_press(Message::ExportCsv);
+        let export_row = row![export_button, qr_button, import_button, import_csv_button, export_csv_button]
+            .spacing(10);
+
+        // The QR code is rendered from the current URI, so it always reflects the tab's settings
+        let qr_display = match (active_tab.show_qr && !active_tab.secret_key.is_empty())
+            .then(|| build_otpauth_uri(active_tab, self.strict_base32).and_then(|uri| qr_code_handle(&uri)))
+        {
+            Some(Ok(handle)) => container(image(handle).width(Length::Fixed(220.0)))
+                .padding([20, 0, 0, 0])
+                .center_x(),
+            Some(Err(e)) => container(
+                text(e)
+                    .size(self.scaled(14.0))
+                    .style(iced::theme::Text::Color(self.theme().palette().danger))
+            )
+            .padding([20, 0, 0, 0]),
+            None => container(text("").size(0)),
+        };
+
         // Error or success message with improved styling
         let message_display = if let Some(error) = &active_tab.error {
             // Determine if this is actually a success message
-            let (message, color, icon) = if error.contains("copied to clipboard") {
-                (error.as_str(), iced::Color::from_rgb(0.0, 0.5, 0.0), "✓ ") // Green for success with checkmark
+            let (message, color, icon) = if is_success_message(error) {
+                (error.as_str(), palette.success, "✓ ") // Green for success with checkmark
             } else {
-                (error.as_str(), iced::Color::from_rgb(0.8, 0.0, 0.0), "⚠ ") // Red for error with warning icon
+                (error.as_str(), palette.danger, "⚠ ") // Red for error with warning icon
             };
             
+            // Timed success messages count down until they disappear
+            let countdown = if is_success_message(error) && active_tab.message_secs_left > 0 {
+                format!(" (clears in {}s)", active_tab.message_secs_left)
+            } else {
+                String::new()
+            };
+
             let styled_message = container(
-                text(format!("{}{}", icon, message))
-                    .size(14)
+                text(format!("{}{}{}", icon, message, countdown))
+                    .size(self.scaled(14.0))
                     .style(iced::theme::Text::Color(color))
             )
             .padding([8, 12, 8, 12])
-            .style(if error.contains("copied to clipboard") {
+            .style(if is_success_message(error) {
                 theme::Container::Custom(Box::new(SuccessMessageStyle))
             } else {
                 theme::Container::Custom(Box::new(ErrorMessageStyle))
@@ -418,313 +2193,6760 @@ impl Application for TotpGenerator {
         };
 
         // Simplified section without the label
-        let secret_key_section = container(secret_key_input)
+        // Dot next to the input: green once the secret decodes to a usable key, red while it
+        // doesn't, gray while the field is empty. Updates as you type, before any code is shown.
+        let validity_color = if active_tab.secret_key.trim().is_empty() {
+            muted_text
+        } else if secret_is_valid(active_tab, self.strict_base32) {
+            palette.success
+        } else {
+            palette.danger
+        };
+        let secret_key_section = container(
+            row![
+                secret_key_input,
+                secret_visibility_button,
+                refresh_button,
+                text("●")
+                    .size(self.scaled(16.0))
+                    .style(iced::theme::Text::Color(validity_color)),
+            ]
+            .spacing(8)
+            .align_items(Alignment::Center),
+        )
             .width(Length::Fill);
-        
-        let content = column![
-            title,
-            tab_row,
-            vertical_space(10),
-            tab_separator,
-            vertical_space(10),
-            secret_key_section,
-            vertical_space(30),  // Increased space before timer
-            token_display,
-            vertical_space(20),  // Consistent spacing
-            timer_text,
-            vertical_space(5),
-            progress_bar,
-            vertical_space(20),  // More space for messages
-            message_display
+
+        // Characters pasted along with the secret that decoding skips, so a mismatch caused by
+        // them isn't a mystery; generation goes ahead regardless
+        let ignored_chars_note = if active_tab.ignored_secret_chars > 0 && !active_tab.secret_key.trim().is_empty() {
+            let count = active_tab.ignored_secret_chars;
+            text(format!(
+                "Ignored {} whitespace/hidden character{} in the secret",
+                count,
+                if count == 1 { "" } else { "s" }
+            ))
+            .size(self.scaled(12.0))
+            .style(iced::theme::Text::Color(muted_text))
+        } else {
+            text("").size(0)
+        };
+
+        // Short keys still produce codes thanks to pad_key, so say so rather than hide it
+        let weak_secret_hint = match active_tab.decode_key(self.strict_base32).ok().and_then(|key| weak_key_hint(key.len())) {
+            Some(hint) => text(hint)
+                .size(self.scaled(12.0))
+                .style(iced::theme::Text::Color(urgency_color(Urgency::Warning, &self.theme()))),
+            None => text("").size(0),
+        };
+
+        // Type, algorithm, digit and period selectors for the active tab
+        let active_index = self.active_tab;
+        let mut mode_row = row![
+            text("Type").size(self.scaled(14.0)),
+            pick_list(
+                &OTP_MODES[..],
+                Some(active_tab.mode),
+                move |mode| Message::ModeChanged(mode, active_index),
+            )
+            .text_size(self.scaled(14.0))
+            .padding(6),
+            text("Format").size(self.scaled(14.0)),
+            pick_list(
+                &SECRET_FORMATS[..],
+                Some(active_tab.secret_format),
+                move |format| Message::SecretFormatChanged(format, active_index),
+            )
+            .text_size(self.scaled(14.0))
+            .padding(6),
         ]
-        .spacing(0)
-        .padding(30)  // Increased padding for better spacing
-        .max_width(500)  // Slightly reduced for a more compact look
-        .align_items(Alignment::Center);  // Center-align everything
-        
-        // Make the entire application use the light gray background
-        container(content)
-            .width(Length::Fill)
-            .height(Length::Fill)
-            .center_x()
-            .center_y()
-            .style(theme::Container::Box)
-            .into()
-    }
-}
+        .spacing(10)
+        .align_items(Alignment::Center);
 
-impl TotpGenerator {
-    // Helper function to decode secret keys
-    fn decode_secret(input: &str) -> Vec<u8> {
-        // Normalize the input: remove spaces and convert to uppercase
-        let normalized = input.to_uppercase().replace(" ", "");
-        
-        // Characters that are valid in Base32 encoding (RFC4648)
-        const BASE32_CHARS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
-        
-        // First, try the normalized input directly
-        if let Some(decoded) = base32::decode(
-            base32::Alphabet::RFC4648 { padding: false },
-            &normalized
-        ) {
-            if decoded.len() >= 16 {
-                return decoded;
-            } else {
-                return Self::pad_key(decoded);
+        // Only Base32 secrets have an alphabet to choose
+        let alphabet_row = if active_tab.secret_format == SecretFormat::Base32 {
+            row![
+                text("Alphabet").size(self.scaled(14.0)),
+                pick_list(
+                    &BASE32_ALPHABETS[..],
+                    Some(active_tab.base32_alphabet),
+                    move |alphabet| Message::Base32AlphabetChanged(alphabet, active_index),
+                )
+                .text_size(self.scaled(14.0))
+                .padding(6),
+            ]
+            .spacing(10)
+            .align_items(Alignment::Center)
+        } else {
+            row![]
+        };
+
+        if active_tab.mode != OtpMode::Hotp {
+            mode_row = mode_row
+                .push(text("Period").size(self.scaled(14.0)))
+                .push(
+                    text_input("30", &period_text(active_tab))
+                        .on_input(move |value| Message::PeriodInputChanged(value, active_index))
+                        .size(self.scaled(14.0))
+                        .padding(6)
+                        .width(Length::Fixed(50.0)),
+                );
+
+            // Common periods one click away; the one in use is highlighted
+            for preset in PERIOD_PRESETS {
+                mode_row = mode_row.push(
+                    button(text(format!("{}s", preset)).size(self.scaled(12.0)))
+                        .padding([4, 6])
+                        .style(if active_tab.period == preset {
+                            theme::Button::Primary
+                        } else {
+                            theme::Button::Secondary
+                        })
+                        .on_press(Message::PeriodChanged(preset, active_index)),
+                );
             }
         }
-        
-        // Try with padding added
-        let mut padded = normalized.clone();
-        while padded.len() % 8 != 0 {
-            padded.push('=');
+
+        // Steam codes always use SHA1 and 5 characters, so only TOTP and HOTP tabs get these
+        let settings_row = if active_tab.mode != OtpMode::Steam {
+            row![
+                text("Algorithm").size(self.scaled(14.0)),
+                pick_list(
+                    &ALGORITHMS[..],
+                    Some(active_tab.algorithm),
+                    move |algorithm| Message::AlgorithmChanged(algorithm, active_index),
+                )
+                .text_size(self.scaled(14.0))
+                .padding(6),
+                text("Digits").size(self.scaled(14.0)),
+                pick_list(
+                    &DIGIT_OPTIONS[..],
+                    Some(active_tab.digits),
+                    move |digits| Message::DigitsChanged(digits, active_index),
+                )
+                .text_size(self.scaled(14.0))
+                .padding(6),
+            ]
+            .spacing(10)
+            .align_items(Alignment::Center)
+        } else {
+            row![]
+        };
+
+        // Label color swatches for the active tab; the blank one clears the color
+        let mut color_row = row![text("Color").size(self.scaled(14.0))]
+            .spacing(6)
+            .align_items(Alignment::Center);
+        for color in std::iter::once(None).chain(TAB_COLORS.into_iter().map(Some)) {
+            color_row = color_row.push(
+                button(text("").size(self.scaled(12.0)))
+                    .width(Length::Fixed(self.scaled(20.0)))
+                    .height(Length::Fixed(self.scaled(20.0)))
+                    .style(theme::Button::Custom(Box::new(TabColorSwatchStyle {
+                        color,
+                        selected: active_tab.color == color,
+                    })))
+                    .on_press(Message::TabColorChanged(color, active_index)),
+            );
         }
         
-        if let Some(decoded) = base32::decode(
-            base32::Alphabet::RFC4648 { padding: true },
-            &padded
-        ) {
-            if decoded.len() >= 16 {
-                return decoded;
-            } else {
-                return Self::pad_key(decoded);
-            }
+        // Master password controls for encrypting the saved tabs
+        let has_password = self.vault_key.is_some();
+        let master_password_input = text_input(
+            if has_password { "New master password" } else { "Master password" },
+            &self.password_input,
+        )
+        .password()
+        .padding(6)
+        .size(self.scaled(14.0))
+        .on_input(Message::PasswordInputChanged)
+        .on_submit(Message::SetMasterPassword);
+
+        let mut master_password_row = row![
+            master_password_input,
+            button(
+                text(if has_password { "Change password" } else { "Set password" })
+                    .size(self.scaled(14.0)),
+            )
+                .padding(6)
+                .style(theme::Button::Secondary)
+                .on_press(Message::SetMasterPassword),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        if has_password {
+            master_password_row = master_password_row.push(
+                button(text("Remove").size(self.scaled(14.0)))
+                    .padding(6)
+                    .style(theme::Button::Destructive)
+                    .on_press(Message::RemoveMasterPassword),
+            );
         }
-        
-        // Try filtering out invalid characters
-        let filtered: String = normalized.chars()
-            .filter(|c| BASE32_CHARS.contains(*c))
-            .collect();
-            
-        if filtered != normalized {
-            // Try the filtered string
-            if let Some(decoded) = base32::decode(
-                base32::Alphabet::RFC4648 { padding: false },
-                &filtered
-            ) {
-                if decoded.len() >= 16 {
-                    return decoded;
-                } else {
-                    return Self::pad_key(decoded);
+
+        let high_contrast_label = if self.no_color {
+            "High contrast (black and white instead of soft grays; always on while NO_COLOR is set)"
+        } else {
+            "High contrast (black and white instead of soft grays)"
+        };
+        let high_contrast_toggle = checkbox(
+            high_contrast_label,
+            self.high_contrast,
+            Message::HighContrastToggled,
+        )
+        .size(self.scaled(16.0))
+        .text_size(self.scaled(14.0));
+
+        let hide_tokens_toggle = checkbox(
+            "Hide codes until clicked",
+            self.hide_tokens,
+            Message::HideTokensToggled,
+        )
+        .size(self.scaled(16.0))
+        .text_size(self.scaled(14.0));
+
+        let copy_grouped_toggle = checkbox(
+            "Copy codes with a space (123 456)",
+            self.copy_grouped,
+            Message::CopyGroupedToggled,
+        )
+        .size(self.scaled(16.0))
+        .text_size(self.scaled(14.0));
+
+        let verify_clipboard_toggle = checkbox(
+            "Check the clipboard after copying (slower, catches truncated copies)",
+            self.verify_clipboard,
+            Message::VerifyClipboardToggled,
+        )
+        .size(self.scaled(16.0))
+        .text_size(self.scaled(14.0));
+
+        let hide_unfocused_toggle = checkbox(
+            "Hide codes while the window isn't focused",
+            self.hide_when_unfocused,
+            Message::HideWhenUnfocusedToggled,
+        )
+        .size(self.scaled(16.0))
+        .text_size(self.scaled(14.0));
+
+        let favicons_toggle = checkbox(
+            "Show service icons on tabs (fetched from the issuer's website)",
+            self.show_favicons,
+            Message::ShowFaviconsToggled,
+        )
+        .size(self.scaled(16.0))
+        .text_size(self.scaled(14.0));
+
+        let strict_base32_toggle = checkbox(
+            "Strict Base32 (no spaces, dashes or look-alike digits in secrets)",
+            self.strict_base32,
+            Message::StrictBase32Toggled,
+        )
+        .size(self.scaled(16.0))
+        .text_size(self.scaled(14.0));
+
+        let flash_toggle = checkbox(
+            "Flash the code when it changes",
+            self.flash_on_rollover,
+            Message::FlashOnRolloverToggled,
+        )
+        .size(self.scaled(16.0))
+        .text_size(self.scaled(14.0));
+
+        let history_toggle = checkbox(
+            format!("Keep the last {} codes of each tab (in memory only)", HISTORY_LEN),
+            self.keep_history,
+            Message::HistoryToggled,
+        )
+        .size(self.scaled(16.0))
+        .text_size(self.scaled(14.0));
+
+        let auto_copy_background_toggle = checkbox(
+            "Auto-copy new codes from background tabs too",
+            self.auto_copy_background,
+            Message::AutoCopyBackgroundToggled,
+        )
+        .size(self.scaled(16.0))
+        .text_size(self.scaled(14.0));
+
+        let grace_window_toggle = checkbox(
+            "Show previous and next codes (±1 step)",
+            self.show_grace_window,
+            Message::GraceWindowToggled,
+        )
+        .size(self.scaled(16.0))
+        .text_size(self.scaled(14.0));
+
+        let debug_toggle = checkbox(
+            "Show debug info",
+            self.show_debug,
+            Message::DebugInfoToggled,
+        )
+        .size(self.scaled(16.0))
+        .text_size(self.scaled(14.0));
+
+        // Values behind the active tab's code, for comparing with a server. Only the
+        // key's length is shown, never the key itself.
+        let debug_panel = if self.show_debug {
+            let now = effective_now(self.clock_offset_secs);
+            let counter = match active_tab.mode {
+                OtpMode::Hotp => active_tab.counter,
+                OtpMode::Totp | OtpMode::Steam => current_counter(now, active_tab.period),
+            };
+            let key_length = match active_tab.decode_key(self.strict_base32) {
+                Ok(key) => format!("{} bytes", key.len()),
+                Err(_) => "invalid".to_string(),
+            };
+            container(
+                text(format!(
+                    "Counter: {} · Unix time: {} · Key length: {}",
+                    counter, now, key_length
+                ))
+                .size(self.scaled(12.0))
+                .style(iced::theme::Text::Color(muted_text)),
+            )
+        } else {
+            container(text("").size(0))
+        };
+
+        // Code the active tab would show at another instant, for checking a server's
+        // verification window without touching the live code
+        let time_preview_toggle = button(
+            text(if self.time_preview_open { "▾ Code at another time" } else { "▸ Code at another time" })
+                .size(self.scaled(14.0)),
+        )
+        .padding(4)
+        .style(theme::Button::Text)
+        .on_press(Message::ToggleTimePreview);
+
+        let time_preview_panel = if self.time_preview_open {
+            let now = effective_now(self.clock_offset_secs);
+            let requested = parse_preview_time(&self.time_preview_input, now);
+
+            let mut time_input = text_input("Unix time, +/- seconds or YYYY-MM-DD HH:MM:SS", &self.time_preview_input)
+                .on_input(Message::TimePreviewInputChanged)
+                .size(self.scaled(14.0))
+                .padding(6);
+            let mut show_button = button(text("Show").size(self.scaled(14.0)))
+                .padding(6)
+                .style(theme::Button::Secondary);
+            if let Some(timestamp) = requested {
+                time_input = time_input.on_submit(Message::PreviewAtTime(timestamp));
+                show_button = show_button.on_press(Message::PreviewAtTime(timestamp));
+            }
+
+            let result = match self.time_preview_at {
+                Some(_) if active_tab.mode == OtpMode::Hotp => {
+                    "HOTP codes depend on the counter, not the time".to_string()
+                }
+                Some(timestamp) => match token_for_timestamp(active_tab, timestamp, self.strict_base32) {
+                    Ok(token) => format!(
+                        "{} at {} (Unix {}, {:+} steps from now)",
+                        if codes_masked { mask_token(&token) } else { format_token(&token) },
+                        format_utc_time(timestamp),
+                        timestamp,
+                        current_counter(timestamp, active_tab.period) as i128
+                            - current_counter(now, active_tab.period) as i128,
+                    ),
+                    Err(e) => e,
+                },
+                None => String::new(),
+            };
+
+            container(
+                column![
+                    row![time_input, show_button].spacing(10).align_items(Alignment::Center),
+                    text(result)
+                        .size(self.scaled(14.0))
+                        .style(iced::theme::Text::Color(muted_text)),
+                ]
+                .spacing(5),
+            )
+        } else {
+            container(text("").size(0))
+        };
+
+        let close_to_tray_toggle = checkbox(
+            "Close to tray instead of quitting",
+            self.close_to_tray,
+            Message::CloseToTrayToggled,
+        )
+        .size(self.scaled(16.0))
+        .text_size(self.scaled(14.0));
+
+        let start_minimized_toggle = checkbox(
+            "Start hidden in the tray",
+            self.start_minimized,
+            Message::StartMinimizedToggled,
+        )
+        .size(self.scaled(16.0))
+        .text_size(self.scaled(14.0));
+
+        // System-wide shortcut that shows or hides the window, applied with Enter
+        let hotkey_row = row![
+            text("Show/hide shortcut").size(self.scaled(14.0)),
+            text_input(DEFAULT_HOTKEY, &self.hotkey_input)
+                .on_input(Message::HotkeyInputChanged)
+                .on_submit(Message::HotkeyConfirmed)
+                .size(self.scaled(14.0))
+                .padding(6)
+                .width(Length::Fixed(140.0)),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        let hotkey_error = match &self.hotkey_error {
+            Some(error) => text(format!("⚠ {}", error))
+                .size(self.scaled(12.0))
+                .style(iced::theme::Text::Color(palette.danger)),
+            None => text("").size(0),
+        };
+
+        // How long copied codes stay on the clipboard
+        let clipboard_clear_row = row![
+            text("Clear clipboard after").size(self.scaled(14.0)),
+            text_input("15", &self.clipboard_clear_secs.to_string())
+                .on_input(|value| {
+                    Message::ClipboardClearDelayChanged(value.trim().parse().unwrap_or(0))
+                })
+                .size(self.scaled(14.0))
+                .padding(6)
+                .width(Length::Fixed(50.0)),
+            text("seconds (0 = never)").size(self.scaled(14.0)),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        // How long "copied" and other success messages stay up
+        let message_timeout_row = row![
+            text("Hide messages after").size(self.scaled(14.0)),
+            text_input("3", &self.message_timeout_secs.to_string())
+                .on_input(|value| Message::MessageTimeoutChanged(value.trim().parse().unwrap_or(0)))
+                .size(self.scaled(14.0))
+                .padding(6)
+                .width(Length::Fixed(50.0)),
+            text("seconds (0 = never)").size(self.scaled(14.0)),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        // Manual correction for a system clock that has drifted
+        let adjusted_time = chrono::DateTime::from_timestamp(
+            effective_now(self.clock_offset_secs) as i64,
+            0,
+        )
+        .map(|time| time.format("%H:%M:%S UTC").to_string())
+        .unwrap_or_default();
+
+        let clock_offset_row = row![
+            text("Clock offset").size(self.scaled(14.0)),
+            text_input("0", &self.clock_offset_input)
+                .on_input(Message::ClockOffsetChanged)
+                .size(self.scaled(14.0))
+                .padding(6)
+                .width(Length::Fixed(60.0)),
+            text("seconds").size(self.scaled(14.0)),
+            text(format!("(adjusted time {})", adjusted_time))
+                .size(self.scaled(12.0))
+                .style(iced::theme::Text::Color(muted_text)),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        let mut sync_time_button = button(
+            text(if self.syncing_time { "Syncing…" } else { "Sync time" }).size(self.scaled(14.0)),
+        )
+            .padding(6)
+            .style(theme::Button::Secondary);
+        if !self.syncing_time {
+            sync_time_button = sync_time_button.on_press(Message::SyncTime);
+        }
+        let clock_offset_row = clock_offset_row.push(sync_time_button);
+
+        let time_sync_status = match &self.time_sync_status {
+            Some(Ok(message)) => container(
+                text(format!("✓ {}", message))
+                    .size(self.scaled(14.0))
+                    .style(iced::theme::Text::Color(palette.success)),
+            )
+            .padding([8, 12, 8, 12])
+            .style(theme::Container::Custom(Box::new(SuccessMessageStyle))),
+            Some(Err(error)) => container(
+                text(format!("⚠ {}", error))
+                    .size(self.scaled(14.0))
+                    .style(iced::theme::Text::Color(palette.danger)),
+            )
+            .padding([8, 12, 8, 12])
+            .style(theme::Container::Custom(Box::new(ErrorMessageStyle))),
+            None => container(text("").size(0)),
+        };
+
+        // Passphrase-protected backup of every tab
+        let backup_row = row![
+            text_input("Backup passphrase", &self.backup_passphrase)
+                .password()
+                .padding(6)
+                .size(self.scaled(14.0))
+                .on_input(Message::BackupPassphraseChanged),
+            button(text("Export backup").size(self.scaled(14.0)))
+                .padding(6)
+                .style(theme::Button::Secondary)
+                .on_press(Message::ExportBackup),
+            button(text("Import backup").size(self.scaled(14.0)))
+                .padding(6)
+                .style(theme::Button::Secondary)
+                .on_press(Message::ImportBackup),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        let master_password_error = match &self.password_error {
+            Some(error) => text(format!("⚠ {}", error))
+                .size(self.scaled(14.0))
+                .style(iced::theme::Text::Color(self.theme().palette().danger)),
+            None => text("").size(0),
+        };
+
+        // Keyboard shortcuts available in the main view
+        let shortcuts_hint = text(
+            "Shortcuts: Ctrl+C copy code · Ctrl+T new tab · Ctrl+W close tab · Ctrl+M mini window · F2 rename tab · Ctrl+Tab / Ctrl+Shift+Tab switch tabs · Ctrl+1-9 jump to a tab · Ctrl+= / Ctrl+- text size",
+        )
+            .size(self.scaled(12.0))
+            .style(iced::theme::Text::Color(muted_text));
+
+        // Footer with the time codes are generated for, refreshed by every Tick
+        let status_bar = text(status_line(effective_now(self.clock_offset_secs), self.clock_offset_secs))
+            .size(self.scaled(12.0))
+            .style(iced::theme::Text::Color(muted_text));
+
+        let content = column![
+            title,
+            filter_row,
+            vertical_space(5),
+            tab_bar,
+            vertical_space(10),
+            tab_separator,
+            filter_notice,
+            tab_menu,
+            removal_prompt,
+            vertical_space(10),
+            secret_key_section,
+            ignored_chars_note,
+            weak_secret_hint,
+            vertical_space(10),
+            mode_row,
+            vertical_space(10),
+            alphabet_row,
+            vertical_space(10),
+            settings_row,
+            vertical_space(10),
+            color_row,
+            vertical_space(10),
+            export_row,
+            qr_display,
+            vertical_space(30),  // Increased space before timer
+            token_display,
+            next_token_display,
+            grace_window_display,
+            history_panel,
+            vertical_space(20),  // Consistent spacing
+            countdown,
+            vertical_space(20),  // More space for messages
+            message_display,
+            vertical_space(30),
+            high_contrast_toggle,
+            vertical_space(10),
+            hide_tokens_toggle,
+            vertical_space(10),
+            hide_unfocused_toggle,
+            vertical_space(10),
+            favicons_toggle,
+            vertical_space(10),
+            copy_grouped_toggle,
+            vertical_space(10),
+            verify_clipboard_toggle,
+            vertical_space(10),
+            auto_copy_background_toggle,
+            vertical_space(10),
+            history_toggle,
+            vertical_space(10),
+            flash_toggle,
+            vertical_space(10),
+            grace_window_toggle,
+            vertical_space(10),
+            strict_base32_toggle,
+            vertical_space(10),
+            debug_toggle,
+            debug_panel,
+            vertical_space(10),
+            time_preview_toggle,
+            time_preview_panel,
+            vertical_space(10),
+            close_to_tray_toggle,
+            vertical_space(10),
+            start_minimized_toggle,
+            vertical_space(10),
+            hotkey_row,
+            hotkey_error,
+            vertical_space(10),
+            clipboard_clear_row,
+            vertical_space(10),
+            message_timeout_row,
+            vertical_space(10),
+            clock_offset_row,
+            time_sync_status,
+            vertical_space(10),
+            backup_row,
+            vertical_space(10),
+            master_password_row,
+            master_password_error,
+            vertical_space(20),
+            shortcuts_hint,
+            vertical_space(5),
+            status_bar
+        ]
+        .spacing(0)
+        .padding(30)  // Increased padding for better spacing
+        .max_width(500)  // Slightly reduced for a more compact look
+        .align_items(Alignment::Center);  // Center-align everything
+        
+        let body: Element<_> = match sidebar {
+            Some(sidebar) => row![
+                sidebar,
+                vertical_rule(1),
+                container(content).width(Length::Fill).center_x(),
+            ]
+            .into(),
+            None => content.into(),
+        };
+
+        // Make the entire application use the themed box background
+        container(body)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .center_x()
+            .center_y()
+            .style(theme::Container::Box)
+            .into()
+    }
+}
+
+impl TotpGenerator {
+    fn handle_message(&mut self, message: Message) -> Command<Message> {
+        match message {
+            Message::SecretKeyChanged(value, tab_index) => {
+                if tab_index < self.tabs.len() {
+                    // Every edit, clearing the field included, makes pending generations stale
+                    self.secret_edits += 1;
+                    let edit = self.secret_edits;
+                    let tab = &mut self.tabs[tab_index];
+                    tab.secret_edit = edit;
+
+                    // A Google Authenticator export adds every account it contains as a new tab
+                    if value.trim_start().to_lowercase().starts_with("otpauth-migration://") {
+                        match parse_migration_payload(value.trim()) {
+                            Ok((tabs, skipped)) => self.import_tabs(tab_index, tabs, skipped),
+                            Err(e) => {
+                                tab.secret_key = value;
+                                tab.token = String::new();
+                                tab.error = Some(e);
+                            }
+                        }
+                        return Command::none();
+                    }
+
+                    // A pasted otpauth:// URI fills in the whole tab rather than just the secret
+                    if is_otpauth_uri(&value) {
+                        match parse_otpauth_uri(value.trim()) {
+                            Ok(parsed) => {
+                                let warning = tab.apply_parsed_otp(parsed);
+                                self.generate_token(tab_index);
+                                if warning.is_some() {
+                                    self.tabs[tab_index].error = warning;
+                                }
+                                self.warn_about_duplicate(tab_index);
+                            }
+                            Err(e) => {
+                                tab.secret_key = value;
+                                tab.token = String::new();
+                                tab.error = Some(e);
+                            }
+                        }
+                        return Command::none();
+                    }
+
+                    tab.ignored_secret_chars = ignored_secret_chars(&value);
+                    tab.secret_key = value;
+                    tab.error = None;
+                    // The old code belongs to the previous secret, so don't keep showing it
+                    tab.token = String::new();
+                    tab.next_token = String::new();
+
+                    // Generate once typing pauses, rather than on every keystroke
+                    if !tab.secret_key.is_empty() {
+                        return Command::perform(
+                            async move {
+                                tokio::time::sleep(Duration::from_millis(SECRET_DEBOUNCE_MS)).await;
+                                edit
+                            },
+                            Message::GenerateDebounced,
+                        );
+                    }
                 }
             }
-            
-            // Try the filtered string with padding
-            let mut padded_filtered = filtered.clone();
-            while padded_filtered.len() % 8 != 0 {
-                padded_filtered.push('=');
+            Message::GenerateDebounced(edit) => {
+                // The edit id travels with the tab, so this finds it even if tabs were moved
+                // or removed meanwhile. Nothing matches if the secret was edited again.
+                if let Some(tab_index) = self.tabs.iter().position(|tab| tab.secret_edit == edit) {
+                    self.generate_token(tab_index);
+                    self.warn_about_duplicate(tab_index);
+                }
             }
-            
-            if let Some(decoded) = base32::decode(
-                base32::Alphabet::RFC4648 { padding: true },
-                &padded_filtered
-            ) {
-                if decoded.len() >= 16 {
-                    return decoded;
-                } else {
-                    return Self::pad_key(decoded);
+            Message::DigitsChanged(digits, tab_index) => {
+                if tab_index < self.tabs.len() && DIGIT_OPTIONS.contains(&digits) {
+                    self.tabs[tab_index].digits = digits;
+
+                    // Regenerate right away so the displayed code has the new length
+                    if !self.tabs[tab_index].secret_key.is_empty() {
+                        self.generate_token(tab_index);
+                    }
                 }
             }
-        }
-        
-        // Handle the case where 'I' might be confused with '1' or 'L', and 'O' with '0'
-        let substituted = normalized
-            .replace('1', "I")
-            .replace('0', "O")
-            .replace('8', "B")
-            .replace('L', "I");
-            
-        if substituted != normalized {
-            if let Some(decoded) = base32::decode(
-                base32::Alphabet::RFC4648 { padding: false },
-                &substituted
-            ) {
-                if decoded.len() >= 16 {
-                    return decoded;
+            Message::PeriodChanged(period, tab_index) => {
+                // A zero period would divide by zero in the countdown math
+                if tab_index < self.tabs.len() {
+                    if period == 0 {
+                        self.tabs[tab_index].error = Some("Period must be at least 1 second".to_string());
+                        return Command::none();
+                    }
+                    self.tabs[tab_index].period = period;
+                    self.tabs[tab_index].period_input = None;
+
+                    // Regenerate right away so the countdown uses the new window
+                    if !self.tabs[tab_index].secret_key.is_empty() {
+                        self.generate_token(tab_index);
+                    }
+                }
+            }
+            Message::PeriodInputChanged(value, tab_index) => {
+                // Keep partial input like "" or "12" (on the way to "120") on screen, and
+                // only switch periods once typing pauses
+                if let Some(tab) = self.tabs.get_mut(tab_index) {
+                    self.period_edits += 1;
+                    let edit = self.period_edits;
+                    tab.period_edit = edit;
+                    tab.period_input = Some(value);
+                    return Command::perform(
+                        async move {
+                            tokio::time::sleep(Duration::from_millis(SECRET_DEBOUNCE_MS)).await;
+                            edit
+                        },
+                        Message::PeriodDebounced,
+                    );
+                }
+            }
+            Message::PeriodDebounced(edit) => {
+                let Some(tab_index) = self.tabs.iter().position(|tab| tab.period_edit == edit) else {
+                    return Command::none();
+                };
+                let parsed = self.tabs[tab_index]
+                    .period_input
+                    .as_deref()
+                    .and_then(|input| input.trim().parse::<u64>().ok())
+                    .filter(|period| *period > 0);
+                if let Some(period) = parsed {
+                    return self.handle_message(Message::PeriodChanged(period, tab_index));
+                }
+            }
+            Message::GenerateToken(tab_index) => {
+                // Recompute the code now, e.g. after changing the clock offset
+                if self.tabs.get(tab_index).is_some_and(|tab| !tab.secret_key.is_empty()) {
+                    self.generate_token(tab_index);
+                }
+            }
+            Message::AlgorithmChanged(algorithm, tab_index) => {
+                if tab_index < self.tabs.len() {
+                    self.tabs[tab_index].algorithm = algorithm;
+
+                    // Regenerate right away so the displayed code matches the new algorithm
+                    if !self.tabs[tab_index].secret_key.is_empty() {
+                        self.generate_token(tab_index);
+                    }
+                }
+            }
+            Message::ModeChanged(mode, tab_index) => {
+                if tab_index < self.tabs.len() {
+                    self.tabs[tab_index].mode = mode;
+
+                    // Regenerate right away so the displayed code matches the new type
+                    if !self.tabs[tab_index].secret_key.is_empty() {
+                        self.generate_token(tab_index);
+                    }
+                }
+            }
+            Message::SecretFormatChanged(format, tab_index) => {
+                if tab_index < self.tabs.len() {
+                    self.tabs[tab_index].secret_format = format;
+
+                    // Re-read the secret in the new format
+                    if !self.tabs[tab_index].secret_key.is_empty() {
+                        self.generate_token(tab_index);
+                    }
+                }
+            }
+            Message::Base32AlphabetChanged(alphabet, tab_index) => {
+                if tab_index < self.tabs.len() {
+                    self.tabs[tab_index].base32_alphabet = alphabet;
+
+                    // Re-read the secret with the new alphabet
+                    if !self.tabs[tab_index].secret_key.is_empty() {
+                        self.generate_token(tab_index);
+                    }
+                }
+            }
+            Message::GenerateNext(tab_index) => {
+                // HOTP codes only advance when asked to
+                if tab_index < self.tabs.len() && self.tabs[tab_index].mode == OtpMode::Hotp {
+                    self.tabs[tab_index].counter = self.tabs[tab_index].counter.saturating_add(1);
+                    self.generate_token(tab_index);
+                }
+            }
+            Message::CopyToClipboard(tab_index) => {
+                if tab_index < self.tabs.len() && !self.tabs[tab_index].token.is_empty() {
+                    self.mark_used(tab_index);
+                    let token = copied_token(&self.tabs[tab_index].token, self.copy_grouped);
+                    return self.copy_to_clipboard(tab_index, token, "Code copied to clipboard!");
+                }
+            }
+            Message::CopyAndHide(tab_index) => {
+                // Hide only once the copy went through, so a failure isn't shown to no one.
+                // The clipboard clear and message timers keep running while hidden.
+                if tab_index >= self.tabs.len() || self.tabs[tab_index].token.is_empty() {
+                    return Command::none();
+                }
+                self.mark_used(tab_index);
+                let token = copied_token(&self.tabs[tab_index].token, self.copy_grouped);
+                let copy = self.try_copy_to_clipboard(tab_index, token, "Code copied to clipboard!");
+                let hide = hide_after_copy(copy.is_some(), self.tray.is_some())
+                    .map(|action| self.hide_window(action));
+                return Command::batch(copy.into_iter().chain(hide));
+            }
+            Message::ToggleSecretVisibility(idx) => {
+                if let Some(tab) = self.tabs.get_mut(idx) {
+                    tab.secret_visible = !tab.secret_visible;
+                }
+            }
+            Message::SelectableTokenEdited => {
+                // The fallback code field is read-only in effect
+            }
+            Message::CopyActiveToken => {
+                // Keyboard shortcut; does nothing if the active tab has no code yet
+                return self.handle_message(Message::CopyToClipboard(self.active_tab));
+            }
+            Message::CopyAllTokens => {
+                let (block, count) = all_tokens_text(&self.tabs, self.copy_grouped);
+                if count == 0 {
+                    self.tabs[self.active_tab].error = Some("No codes to copy yet".to_string());
+                    return Command::none();
+                }
+                let success = format!("Copied {} code{}", count, if count == 1 { "" } else { "s" });
+                return self.copy_to_clipboard(self.active_tab, block, &success);
+            }
+            Message::ExportUri(tab_index) => {
+                if tab_index < self.tabs.len() && !self.tabs[tab_index].secret_key.is_empty() {
+                    match build_otpauth_uri(&self.tabs[tab_index], self.strict_base32) {
+                        Ok(uri) => {
+                            return self.copy_to_clipboard(tab_index, uri, "URI copied to clipboard!");
+                        }
+                        Err(e) => self.tabs[tab_index].error = Some(e),
+                    }
+                }
+            }
+            Message::ImportFile => {
+                return Command::perform(
+                    async {
+                        let Some(file) = rfd::AsyncFileDialog::new()
+                            .set_title("Import Aegis backup")
+                            .add_filter("Aegis JSON backup", &["json"])
+                            .pick_file()
+                            .await
+                        else {
+                            return Ok(None);
+                        };
+                        fs::read_to_string(file.path())
+                            .map(Some)
+                            .map_err(|e| format!("Could not read {}: {}", file.file_name(), e))
+                    },
+                    Message::ImportFileLoaded,
+                );
+            }
+            Message::ImportFileLoaded(result) => {
+                // Existing tabs are left alone unless the whole file parses
+                match result.and_then(|json| json.map(|json| parse_aegis_backup(&json)).transpose()) {
+                    Ok(Some((tabs, skipped))) => self.import_tabs(self.active_tab, tabs, skipped),
+                    Ok(None) => {}
+                    Err(e) => self.tabs[self.active_tab].error = Some(e),
+                }
+            }
+            Message::ImportCsvFile => {
+                return Command::perform(
+                    async {
+                        let Some(file) = rfd::AsyncFileDialog::new()
+                            .set_title("Import account list")
+                            .add_filter("name,secret list", &["csv", "txt"])
+                            .pick_file()
+                            .await
+                        else {
+                            return Ok(None);
+                        };
+                        fs::read_to_string(file.path())
+                            .map(Some)
+                            .map_err(|e| format!("Could not read {}: {}", file.file_name(), e))
+                    },
+                    Message::CsvFileLoaded,
+                );
+            }
+            Message::CsvFileLoaded(result) => match result {
+                Ok(Some(text)) => return self.handle_message(Message::ImportCsv(text)),
+                Ok(None) => {}
+                Err(e) => self.tabs[self.active_tab].error = Some(e),
+            },
+            Message::ImportCsv(text) => {
+                // Good lines are imported even if others are rejected; the rejects are listed
+                let (tabs, errors) = parse_csv_accounts(&text, self.strict_base32);
+                let count = tabs.len();
+                if count == 0 && errors.is_empty() {
+                    self.tabs[self.active_tab].error = Some("No accounts found in the list".to_string());
+                } else if count == 0 {
+                    self.tabs[self.active_tab].error = Some(format!("No accounts imported: {}", errors.join("; ")));
                 } else {
-                    return Self::pad_key(decoded);
+                    self.import_tabs(self.active_tab, tabs, 0);
+                    if !errors.is_empty() {
+                        self.tabs[self.active_tab].error = Some(format!(
+                            "Imported {} account{}, skipped {} line{}: {}",
+                            count,
+                            if count == 1 { "" } else { "s" },
+                            errors.len(),
+                            if errors.len() == 1 { "" } else { "s" },
+                            errors.join("; ")
+                        ));
+                    }
                 }
             }
-        }
-        
-        // Last resort - use the raw bytes and extend if needed
-        let raw_bytes = normalized.as_bytes().to_vec();
-        if raw_bytes.len() >= 16 {
-            raw_bytes
-        } else {
-            Self::pad_key(raw_bytes)
-        }
+            Message::ExportCsv => {
+                let (csv, count) = accounts_csv(&self.tabs, self.strict_base32);
+                if count == 0 {
+                    self.tabs[self.active_tab].error = Some("No accounts with a valid secret to export".to_string());
+                    return Command::none();
+                }
+                return Command::perform(
+                    async move {
+                        let Some(file) = rfd::AsyncFileDialog::new()
+                            .set_title("Export accounts (unencrypted)")
+                            .set_file_name("ro-topt-accounts.csv")
+                            .save_file()
+                            .await
+                        else {
+                            return Ok(None);
+                        };
+                        fs::write(file.path(), csv)
+                            .map(|_| Some(file.file_name()))
+                            .map_err(|e| format!("Could not write {}: {}", file.file_name(), e))
+                    },
+                    Message::CsvExported,
+                );
+            }
+            Message::CsvExported(result) => match result {
+                Ok(Some(file_name)) => {
+                    // Secrets are in plain text, unlike "Export backup"
+                    self.tabs[self.active_tab].show_timed_message(
+                        format!("Exported to {} (unencrypted, keep it safe)", file_name),
+                        self.message_timeout_secs,
+                    );
+                }
+                Ok(None) => {}
+                Err(e) => self.tabs[self.active_tab].error = Some(e),
+            },
+            Message::FileDropped(path) => {
+                // Reading and scanning a large photo takes a while, so it runs on the blocking pool
+                return Command::perform(
+                    run_blocking(move || {
+                        let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+                        let bytes = fs::read(&path).map_err(|e| format!("Could not read {}: {}", name, e))?;
+                        qr_uri_from_image(&bytes).map_err(|e| format!("{}: {}", name, e))
+                    }),
+                    Message::QrImageDecoded,
+                );
+            }
+            Message::QrImageDecoded(result) => {
+                // The decoded QR is handled like a URI pasted into the secret field
+                let parsed = result.and_then(|uri| {
+                    if uri.to_lowercase().starts_with("otpauth-migration://") {
+                        parse_migration_payload(&uri).map(|(tabs, skipped)| (tabs, skipped, None))
+                    } else if is_otpauth_uri(&uri) {
+                        parse_otpauth_uri(&uri).map(|parsed| {
+                            let mut tab = Tab::default();
+                            let warning = tab.apply_parsed_otp(parsed);
+                            (vec![tab], 0, warning)
+                        })
+                    } else {
+                        Err("QR code does not contain an otpauth:// URI".to_string())
+                    }
+                });
+                match parsed {
+                    Ok((tabs, skipped, warning)) => {
+                        self.import_tabs(self.active_tab, tabs, skipped);
+                        // The warning replaces the "Imported" message so it isn't missed
+                        if warning.is_some() {
+                            self.tabs[self.active_tab].error = warning;
+                        }
+                    }
+                    Err(e) => self.tabs[self.active_tab].error = Some(e),
+                }
+            }
+            Message::BackupPassphraseChanged(value) => {
+                self.backup_passphrase = value;
+            }
+            Message::ExportBackup => {
+                if self.backup_passphrase.is_empty() {
+                    self.tabs[self.active_tab].error = Some("Please enter a backup passphrase".to_string());
+                    return Command::none();
+                }
+
+                let passphrase = self.backup_passphrase.clone();
+                let tabs = self.tabs.clone();
+                return Command::perform(
+                    async move {
+                        let json = run_blocking(move || encrypt_backup(&passphrase, &tabs)).await?;
+                        let Some(file) = rfd::AsyncFileDialog::new()
+                            .set_title("Export backup")
+                            .set_file_name("ro-topt-backup.json")
+                            .save_file()
+                            .await
+                        else {
+                            return Ok(None);
+                        };
+                        fs::write(file.path(), json)
+                            .map(|_| Some(file.file_name()))
+                            .map_err(|e| format!("Could not write {}: {}", file.file_name(), e))
+                    },
+                    Message::BackupExported,
+                );
+            }
+            Message::BackupExported(result) => match result {
+                Ok(Some(file_name)) => {
+                    self.backup_passphrase.clear();
+                    self.tabs[self.active_tab].show_timed_message(
+                        format!("Backup saved to {}", file_name),
+                        self.message_timeout_secs,
+                    );
+                }
+                Ok(None) => {}
+                Err(e) => self.tabs[self.active_tab].error = Some(e),
+            },
+            Message::ImportBackup => {
+                if self.backup_passphrase.is_empty() {
+                    self.tabs[self.active_tab].error = Some("Please enter a backup passphrase".to_string());
+                    return Command::none();
+                }
+
+                let passphrase = self.backup_passphrase.clone();
+                return Command::perform(
+                    async move {
+                        let Some(file) = rfd::AsyncFileDialog::new()
+                            .set_title("Import backup")
+                            .add_filter("ro-topt backup", &["json"])
+                            .pick_file()
+                            .await
+                        else {
+                            return Ok(None);
+                        };
+                        let json = fs::read_to_string(file.path())
+                            .map_err(|e| format!("Could not read {}: {}", file.file_name(), e))?;
+                        run_blocking(move || decrypt_backup(&passphrase, &json)).await.map(Some)
+                    },
+                    Message::BackupLoaded,
+                );
+            }
+            Message::BackupLoaded(result) => {
+                match result {
+                    Ok(Some(tabs)) => {
+                        self.backup_passphrase.clear();
+                        self.import_tabs(self.active_tab, tabs, 0);
+                    }
+                    Ok(None) => {}
+                    Err(e) => self.tabs[self.active_tab].error = Some(e),
+                }
+            }
+            Message::ToggleTimePreview => {
+                self.time_preview_open = !self.time_preview_open;
+            }
+            Message::TimePreviewInputChanged(value) => {
+                self.time_preview_input = value;
+            }
+            Message::PreviewAtTime(timestamp) => {
+                self.time_preview_at = Some(timestamp);
+            }
+            Message::ShowQr(tab_index) => {
+                // Toggle between the QR code and the normal view
+                if tab_index < self.tabs.len() {
+                    let tab = &mut self.tabs[tab_index];
+                    tab.show_qr = !tab.show_qr && !tab.secret_key.is_empty();
+                }
+            }
+            Message::ClearClipboard(value) => {
+                // Only wipe our own copy, and only if nothing newer has been copied since
+                if self.last_copied.as_deref() == Some(value.as_str()) {
+                    self.last_copied = None;
+
+                    let ctx: Result<clipboard::ClipboardContext, _> = ClipboardProvider::new();
+                    if let Ok(mut ctx) = ctx {
+                        if ctx.get_contents().ok().as_deref() == Some(value.as_str()) {
+                            let _ = ctx.set_contents(String::new());
+                        }
+                    }
+                }
+            }
+            Message::ClipboardClearDelayChanged(secs) => {
+                self.clipboard_clear_secs = secs;
+            }
+            Message::MessageTimeoutChanged(secs) => {
+                self.message_timeout_secs = secs;
+            }
+            Message::ClockOffsetChanged(value) => {
+                // Keep partial input like "-" on screen without changing the offset
+                if let Ok(offset) = value.trim().parse::<i64>() {
+                    self.set_clock_offset(offset);
+                } else if value.trim().is_empty() {
+                    self.set_clock_offset(0);
+                }
+                self.clock_offset_input = value;
+            }
+            Message::SyncTime => {
+                self.syncing_time = true;
+                self.time_sync_status = None;
+                return Command::perform(
+                    run_blocking(|| query_ntp_offset(NTP_HOST)),
+                    Message::TimeSynced,
+                );
+            }
+            Message::TimeSynced(result) => {
+                self.syncing_time = false;
+                self.time_sync_status = Some(match result {
+                    Ok(offset) => {
+                        self.set_clock_offset(offset);
+                        self.clock_offset_input = offset.to_string();
+                        Ok(format!("Clock offset set to {:+} seconds from {}", offset, NTP_HOST))
+                    }
+                    Err(e) => Err(format!("Time sync failed: {}", e)),
+                });
+            }
+            Message::Tick => {
+                // Update time remaining for all tabs
+                let now = effective_now(self.clock_offset_secs);
+                let previous_tick = self.last_tick.replace(now);
+                
+                // Collect indices that need regeneration, and the expiry notifications to send
+                let mut indices_to_regenerate = Vec::new();
+                let mut notifications = Vec::new();
+                
+                // First pass: update time remaining (HOTP tabs have no expiry)
+                for (idx, tab) in self.tabs.iter_mut().enumerate() {
+                    tab.tick_reveal();
+                    tab.tick_message();
+
+                    if !tab.token.is_empty() && tab.mode != OtpMode::Hotp {
+                        let period = safe_period(tab.period);
+                        let previous = tab.time_remaining;
+                        tab.time_remaining = period - (now % period);
+
+                        if tab.notify_before_expiry
+                            && crossed_expiry_threshold(previous, tab.time_remaining, EXPIRY_NOTIFY_SECS)
+                        {
+                            notifications.push((tab.display_name(), tab.time_remaining));
+                        }
+                    }
+
+                    // Mark for token regeneration once the time step has moved on, even if
+                    // a delayed tick skipped the exact rollover second, and after a sleep
+                    let woke = tab.mode != OtpMode::Hotp
+                        && !tab.token.is_empty()
+                        && previous_tick.is_some_and(|previous| woke_from_sleep(previous, now, tab.period));
+                    if woke || tab.needs_regeneration(now) {
+                        indices_to_regenerate.push(idx);
+                    }
+                }
+                
+                // Second pass: regenerate tokens for expired tabs, noting the ones whose
+                // tick came after the step had already started
+                let active_before = self.tabs[self.active_tab].token.clone();
+                for idx in indices_to_regenerate {
+                    self.generate_token(idx);
+                    let tab = &mut self.tabs[idx];
+                    tab.late_refresh = !tab.token.is_empty() && step_boundary_missed(now, tab.period);
+                }
+
+                // Showing a notification talks to the desktop's notification service and can
+                // block, so each one is sent from the blocking pool
+                let mut commands: Vec<Command<Message>> = notifications
+                    .into_iter()
+                    .map(|(name, remaining)| {
+                        Command::perform(run_blocking(move || notify_expiry(&name, remaining)), |_| {
+                            Message::ExpiryNotified
+                        })
+                    })
+                    .collect();
+
+                let in_view = self.focused && !self.window_hidden;
+                if self.flash_on_rollover
+                    && in_view
+                    && code_rolled_over(&active_before, &self.tabs[self.active_tab].token)
+                {
+                    commands.push(self.start_rollover_flash());
+                }
+                return Command::batch(commands);
+            }
+            Message::ExpiryNotified => {}
+            Message::AddTab => {
+                // Create a new tab with default values and add it to the list
+                let new_tab = Tab {
+                    issuer: format!("Tab {}", self.tabs.len() + 1),
+                    ..Default::default()
+                };
+                self.tabs.push(new_tab);
+                self.active_tab = self.tabs.len() - 1;
+                self.pending_removal = None;
+                self.tab_menu = None;
+            }
+            Message::RemoveTab(idx) => {
+                if self.tabs.len() > 1 && idx < self.tabs.len() {
+                    let removed = self.tabs.remove(idx);
+                    self.pending_removal = None;
+                    self.tab_menu = None;
+                    self.active_tab = active_after_removal(self.active_tab, idx, self.tabs.len());
+
+                    // Keep the tab around for a few seconds in case the removal was a mistake
+                    self.last_removed = Some((idx, removed));
+                    self.removal_generation += 1;
+                    let generation = self.removal_generation;
+                    return Command::perform(
+                        async move {
+                            tokio::time::sleep(Duration::from_secs(UNDO_REMOVE_SECS)).await;
+                            generation
+                        },
+                        Message::UndoExpired,
+                    );
+                }
+            }
+            Message::UndoRemoveTab => {
+                if let Some((idx, tab)) = self.last_removed.take() {
+                    // Other tabs may have been removed since, so the old position may be past the end
+                    let idx = idx.min(self.tabs.len());
+                    self.tabs.insert(idx, tab);
+                    self.active_tab = idx;
+                    self.pending_removal = None;
+                    self.tab_menu = None;
+                    if !self.tabs[idx].secret_key.is_empty() {
+                        self.generate_token(idx);
+                    }
+                }
+            }
+            Message::UndoExpired(generation) => {
+                if generation == self.removal_generation {
+                    self.last_removed = None;
+                }
+            }
+            Message::DuplicateTab(idx) => {
+                if idx < self.tabs.len() {
+                    self.tabs.insert(idx + 1, self.tabs[idx].duplicate());
+                    self.active_tab = idx + 1;
+                    self.pending_removal = None;
+                    self.tab_menu = None;
+                }
+            }
+            Message::RequestRemoveTab(idx) => {
+                // The last tab can't be removed, so there is nothing to confirm
+                if self.tabs.len() > 1 && idx < self.tabs.len() {
+                    self.pending_removal = Some(idx);
+                }
+            }
+            Message::RequestRemoveActiveTab => {
+                // Keyboard shortcut; the last-tab guard above makes it a no-op on a lone tab
+                return self.handle_message(Message::RequestRemoveTab(self.active_tab));
+            }
+            Message::ConfirmRemoveTab => {
+                if let Some(idx) = self.pending_removal.take() {
+                    return self.handle_message(Message::RemoveTab(idx));
+                }
+            }
+            Message::CancelRemoveTab => {
+                self.pending_removal = None;
+            }
+            Message::SelectTab(idx) => {
+                if idx < self.tabs.len() {
+                    // Switching tabs abandons a pending removal and masks the secret again
+                    if idx != self.active_tab {
+                        self.pending_removal = None;
+                        self.tab_menu = None;
+                        if let Some(tab) = self.tabs.get_mut(self.active_tab) {
+                            tab.secret_visible = false;
+                        }
+                        self.tabs[idx].secret_visible = false;
+                    }
+                    self.active_tab = idx;
+                    self.mark_used(idx);
+                }
+            }
+            Message::CycleTab(forward) => {
+                // Follow the order the tabs are shown in, with pinned tabs first
+                let order = self.ordered_tab_indices();
+                let position = order.iter().position(|&idx| idx == self.active_tab).unwrap_or(0);
+                let idx = order[cycle_index(position, order.len(), forward)];
+                return self.handle_message(Message::SelectTab(idx));
+            }
+            Message::SelectTabNumber(number) => {
+                // Numbers count tabs in the order they are shown
+                let order = self.ordered_tab_indices();
+                if let Some(position) = tab_for_number(number, order.len()) {
+                    return self.handle_message(Message::SelectTab(order[position]));
+                }
+            }
+            Message::ShowTabMenu(idx) => {
+                if idx < self.tabs.len() {
+                    self.tab_menu = Some(idx);
+                }
+            }
+            Message::CloseTabMenu => {
+                self.tab_menu = None;
+            }
+            Message::TabMenuSelected(action) => {
+                if let Some(idx) = self.tab_menu.take() {
+                    return self.handle_message(tab_menu_message(action, idx));
+                }
+            }
+            Message::TogglePinned(idx) => {
+                if idx < self.tabs.len() {
+                    self.tabs[idx].pinned = !self.tabs[idx].pinned;
+                }
+            }
+            Message::MoveTab { from, to } => {
+                if from < self.tabs.len() && to < self.tabs.len() && from != to {
+                    let tab = self.tabs.remove(from);
+                    self.tabs.insert(to, tab);
+                    // The pending index would now point at a different tab
+                    self.pending_removal = None;
+                    self.tab_menu = None;
+
+                    // Keep the same tab selected after the shuffle
+                    self.active_tab = if self.active_tab == from {
+                        to
+                    } else if from < self.active_tab && self.active_tab <= to {
+                        self.active_tab - 1
+                    } else if to <= self.active_tab && self.active_tab < from {
+                        self.active_tab + 1
+                    } else {
+                        self.active_tab
+                    };
+                }
+            }
+            Message::FilterChanged(filter) => {
+                self.filter = filter;
+            }
+            Message::RenameTabStarted(idx) => {
+                if idx < self.tabs.len() {
+                    self.tabs[idx].start_rename();
+                    return text_input::focus(rename_input_id(idx));
+                }
+            }
+            Message::RenameActiveTab => {
+                return self.handle_message(Message::RenameTabStarted(self.active_tab));
+            }
+            Message::ConfirmRenames => {
+                for tab in self.tabs.iter_mut().filter(|tab| tab.editing_name) {
+                    tab.confirm_rename();
+                }
+            }
+            Message::CancelRenames => {
+                for tab in self.tabs.iter_mut().filter(|tab| tab.editing_name) {
+                    tab.cancel_rename();
+                }
+            }
+            Message::IssuerChanged(issuer, idx) => {
+                if idx < self.tabs.len() && !self.apply_uri_in_name(&issuer, idx) {
+                    self.tabs[idx].issuer = issuer;
+                }
+            }
+            Message::AccountChanged(account, idx) => {
+                if idx < self.tabs.len() && !self.apply_uri_in_name(&account, idx) {
+                    self.tabs[idx].account = account;
+                }
+            }
+            Message::TabNameConfirmed(idx) => {
+                if idx < self.tabs.len() {
+                    self.tabs[idx].confirm_rename();
+                }
+            }
+            Message::PasswordInputChanged(value) => {
+                self.password_input = value;
+                self.password_error = None;
+            }
+            Message::Unlock => {
+                if let Some(vault) = self.locked_vault.clone() {
+                    if self.deriving_key {
+                        return Command::none();
+                    }
+                    self.deriving_key = true;
+                    self.password_error = None;
+                    let password = self.password_input.clone();
+                    return Command::perform(
+                        run_blocking(move || unlock_vault(&password, &vault)),
+                        Message::Unlocked,
+                    );
+                }
+            }
+            Message::Unlocked(result) => {
+                self.deriving_key = false;
+                // A second unlock attempt may have finished first
+                if self.locked_vault.is_none() {
+                    return Command::none();
+                }
+                match result {
+                    Ok((key, tabs)) => {
+                        self.vault_key = Some(key);
+                        self.locked_vault = None;
+                        self.locked = false;
+                        self.password_input.clear();
+                        self.password_error = None;
+                        self.restore_tabs(tabs);
+                        self.apply_startup_secret();
+                        self.apply_launch_uri();
+                    }
+                    Err(e) => {
+                        self.password_error = Some(e);
+                    }
+                }
+            }
+            Message::SetMasterPassword => {
+                if self.password_input.is_empty() {
+                    self.password_error = Some("Please enter a master password".to_string());
+                } else if !self.deriving_key {
+                    self.deriving_key = true;
+                    self.password_error = None;
+                    let password = std::mem::take(&mut self.password_input);
+                    return Command::perform(
+                        run_blocking(move || Ok(VaultKey::new(&password))),
+                        Message::MasterPasswordDerived,
+                    );
+                }
+            }
+            Message::MasterPasswordDerived(result) => {
+                self.deriving_key = false;
+                match result {
+                    Ok(key) => self.vault_key = Some(key),
+                    Err(e) => self.password_error = Some(e),
+                }
+            }
+            Message::RemoveMasterPassword => {
+                self.vault_key = None;
+                self.password_error = None;
+            }
+            Message::ToggleTheme => {
+                self.dark_theme = !self.dark_theme;
+            }
+            Message::HighContrastToggled(high_contrast) => {
+                self.high_contrast = high_contrast;
+            }
+            Message::HideTokensToggled(hide) => {
+                self.hide_tokens = hide;
+                for tab in &mut self.tabs {
+                    tab.hide();
+                }
+            }
+            Message::CopyGroupedToggled(grouped) => {
+                self.copy_grouped = grouped;
+            }
+            Message::VerifyClipboardToggled(verify) => {
+                self.verify_clipboard = verify;
+            }
+            Message::DebugInfoToggled(show) => {
+                self.show_debug = show;
+            }
+            Message::GraceWindowToggled(show) => {
+                self.show_grace_window = show;
+            }
+            Message::StrictBase32Toggled(strict) => {
+                // Secrets that only decoded leniently stop (or start) producing codes
+                self.strict_base32 = strict;
+                for idx in 0..self.tabs.len() {
+                    if !self.tabs[idx].secret_key.is_empty() {
+                        self.generate_token(idx);
+                    }
+                }
+            }
+            Message::HideWhenUnfocusedToggled(hide) => {
+                self.hide_when_unfocused = hide;
+            }
+            Message::ToggleMiniMode => {
+                // The full-size geometry is left alone while mini, so leaving restores it
+                self.mini = !self.mini;
+                let (width, height) = if self.mini {
+                    MINI_WINDOW_SIZE
+                } else {
+                    (self.window.width, self.window.height)
+                };
+                return Command::batch(vec![
+                    window::resize(width, height),
+                    window::change_always_on_top(self.mini && self.mini_on_top),
+                ]);
+            }
+            Message::MiniOnTopToggled(on_top) => {
+                self.mini_on_top = on_top;
+                if self.mini {
+                    return window::change_always_on_top(on_top);
+                }
+            }
+            Message::WindowFocusChanged(focused) => {
+                self.focused = focused;
+                // Catch the "recently used" order up once the user has left the window
+                if !focused {
+                    self.refresh_recency_order();
+                }
+            }
+            Message::ShowFaviconsToggled(show) => {
+                self.show_favicons = show;
+            }
+            Message::FaviconLoaded(domain, icon) => {
+                self.favicons.insert(domain, icon);
+            }
+            Message::RevealToken(idx) => {
+                if idx < self.tabs.len() {
+                    self.tabs[idx].reveal();
+                }
+            }
+            Message::AutoCopyToggled(auto_copy, idx) => {
+                if let Some(tab) = self.tabs.get_mut(idx) {
+                    tab.auto_copy = auto_copy;
+                }
+            }
+            Message::AutoCopyBackgroundToggled(background) => {
+                self.auto_copy_background = background;
+            }
+            Message::HistoryToggled(keep) => {
+                // Turning history off forgets what was recorded so far
+                self.keep_history = keep;
+                if !keep {
+                    self.history_open = false;
+                    for tab in &mut self.tabs {
+                        tab.history.clear();
+                    }
+                }
+            }
+            Message::ToggleHistoryPanel => {
+                self.history_open = !self.history_open;
+            }
+            Message::FlashOnRolloverToggled(flash) => {
+                self.flash_on_rollover = flash;
+            }
+            Message::RolloverFlashEnded(generation) => {
+                if generation == self.flash_generation {
+                    self.flashing = false;
+                }
+            }
+            Message::ExpiryNotifyToggled(notify, idx) => {
+                if idx < self.tabs.len() {
+                    self.tabs[idx].notify_before_expiry = notify;
+                }
+            }
+            Message::TabColorChanged(color, idx) => {
+                if idx < self.tabs.len() {
+                    self.tabs[idx].color = color;
+                }
+            }
+            Message::TabOrderChanged(order) => {
+                self.tab_order = order;
+                self.refresh_recency_order();
+            }
+            Message::TabLayoutChanged(layout) => {
+                self.tab_layout = layout;
+            }
+            Message::ToggleIssuerGroup(issuer) => {
+                let key = issuer_group_key(&issuer);
+                if !self.collapsed_groups.remove(&key) {
+                    self.collapsed_groups.insert(key);
+                }
+            }
+            Message::CountdownStyleChanged(style) => {
+                self.countdown_style = style;
+            }
+            Message::ProgressFillChanged(fill) => {
+                self.progress_fill = fill;
+            }
+            Message::ScaleUp => {
+                self.scale = clamp_ui_scale(self.scale + UI_SCALE_STEP);
+            }
+            Message::ScaleDown => {
+                self.scale = clamp_ui_scale(self.scale - UI_SCALE_STEP);
+            }
+            Message::ResetScale => {
+                self.scale = 1.0;
+            }
+            Message::CloseToTrayToggled(close_to_tray) => {
+                self.close_to_tray = close_to_tray;
+            }
+            Message::StartMinimizedToggled(start_minimized) => {
+                self.start_minimized = start_minimized;
+            }
+            Message::WindowCloseRequested => {
+                if self.close_to_tray && self.tray.is_some() {
+                    self.window_hidden = true;
+                    return window::change_mode(window::Mode::Hidden);
+                }
+                return window::close();
+            }
+            Message::ShowWindow => {
+                self.window_hidden = false;
+                return Command::batch(vec![
+                    window::change_mode(window::Mode::Windowed),
+                    window::gain_focus(),
+                ]);
+            }
+            Message::ToggleWindowVisibility => {
+                if self.window_hidden {
+                    return self.handle_message(Message::ShowWindow);
+                }
+                self.window_hidden = true;
+                return window::change_mode(window::Mode::Hidden);
+            }
+            Message::HotkeyInputChanged(value) => {
+                self.hotkey_input = value;
+            }
+            Message::HotkeyConfirmed => {
+                self.register_hotkey();
+            }
+            Message::Quit => {
+                return window::close();
+            }
+            Message::WindowResized(width, height) => {
+                // The mini window's size isn't the one to come back to
+                if self.mini {
+                    return Command::none();
+                }
+                self.window.width = width;
+                self.window.height = height;
+                return self.schedule_geometry_save();
+            }
+            Message::WindowMoved(x, y) => {
+                self.window.x = Some(x);
+                self.window.y = Some(y);
+                return self.schedule_geometry_save();
+            }
+            Message::GeometrySettled(generation) => {
+                if generation == self.geometry_generation {
+                    self.save_state();
+                }
+            }
+        }
+        Command::none()
+    }
+
+    // Save the window geometry once it has stopped changing for GEOMETRY_SAVE_MS, rather
+    // than on every event of a drag. Closing the window saves it right away.
+    fn schedule_geometry_save(&mut self) -> Command<Message> {
+        self.geometry_generation += 1;
+        let generation = self.geometry_generation;
+        Command::perform(
+            async move {
+                tokio::time::sleep(Duration::from_millis(GEOMETRY_SAVE_MS)).await;
+                generation
+            },
+            Message::GeometrySettled,
+        )
+    }
+
+    // Location of the saved state file under the OS config directory
+    fn state_path() -> Option<PathBuf> {
+        directories::ProjectDirs::from("com", "zfael", "ro-topt")
+            .map(|dirs| dirs.config_dir().join("state.json"))
+    }
+
+    // Write the tabs to disk; failures are ignored so a read-only config dir never breaks the app
+    fn save_state(&self) {
+        // Saving while locked would replace the encrypted tabs with the placeholder tab
+        if self.locked {
+            return;
+        }
+
+        let Some(path) = Self::state_path() else {
+            return;
+        };
+
+        let (tabs, active_tab) = saved_tabs(&self.tabs, self.active_tab);
+        // With a master password the tabs only go into the encrypted vault
+        let (tabs, vault) = match &self.vault_key {
+            Some(key) => match encrypt_tabs(key, &tabs) {
+                Ok(vault) => (Vec::new(), Some(vault)),
+                Err(_) => return,
+            },
+            None => (tabs, None),
+        };
+        let state = SavedState {
+            tabs,
+            vault,
+            active_tab,
+            dark_theme: self.dark_theme,
+            high_contrast: self.high_contrast,
+            window: Some(self.window),
+            clipboard_clear_secs: Some(self.clipboard_clear_secs),
+            message_timeout_secs: Some(self.message_timeout_secs),
+            clock_offset_secs: self.clock_offset_secs,
+            countdown_ring: self.countdown_style == CountdownStyle::Ring,
+            countdown_style: Some(self.countdown_style),
+            progress_fill: self.progress_fill,
+            tab_order: self.tab_order,
+            tab_layout: self.tab_layout,
+            ui_scale: Some(self.scale),
+            hide_tokens: self.hide_tokens,
+            copy_grouped: self.copy_grouped,
+            verify_clipboard: self.verify_clipboard,
+            show_debug: self.show_debug,
+            show_grace_window: self.show_grace_window,
+            strict_base32: self.strict_base32,
+            auto_copy_background: self.auto_copy_background,
+            keep_history: self.keep_history,
+            flash_on_rollover: self.flash_on_rollover,
+            hide_when_unfocused: self.hide_when_unfocused,
+            mini_on_top: self.mini_on_top,
+            show_favicons: self.show_favicons,
+            close_to_tray: self.close_to_tray,
+            start_minimized: self.start_minimized,
+            hotkey: Some(self.hotkey_input.trim().to_string()),
+        };
+
+        if let Ok(json) = serde_json::to_string_pretty(&state) {
+            if let Some(parent) = path.parent() {
+                let _ = fs::create_dir_all(parent);
+            }
+            let _ = write_private_file(&path, &json);
+        }
+    }
+
+    // Read the saved state, returning None if the file is missing or corrupt
+    fn load_state() -> Option<SavedState> {
+        let json = fs::read_to_string(Self::state_path()?).ok()?;
+        serde_json::from_str(&json).ok()
+    }
+
+    // Replace the tabs with ones loaded from disk and show their codes straight away.
+    // `active_tab` should already hold the saved index; it is clamped here.
+    fn restore_tabs(&mut self, tabs: Vec<Tab>) {
+        let tabs: Vec<Tab> = tabs
+            .into_iter()
+            .map(|tab| Tab {
+                editing_name: false,
+                // Guard against hand-edited files with unusable settings
+                digits: if DIGIT_OPTIONS.contains(&tab.digits) { tab.digits } else { 6 },
+                period: tab.period.max(1),
+                shown_last_used: tab.last_used,
+                ..tab
+            })
+            .collect();
+
+        if tabs.is_empty() {
+            self.active_tab = 0;
+            return;
+        }
+
+        self.tabs = tabs;
+        // The saved index may point past the end if the file was edited; use the last tab then
+        self.active_tab = self.active_tab.min(self.tabs.len() - 1);
+
+        for idx in 0..self.tabs.len() {
+            if !self.tabs[idx].secret_key.is_empty() {
+                self.generate_token(idx);
+            }
+        }
+    }
+
+    // Add imported tabs, taking the place of the tab at `tab_index` if it has no secret yet,
+    // and report how many were added on the first of them
+    fn import_tabs(&mut self, tab_index: usize, tabs: Vec<Tab>, skipped: usize) {
+        if tabs.is_empty() {
+            self.tabs[tab_index].error = Some(format!(
+                "No supported accounts found ({} skipped)",
+                skipped
+            ));
+            return;
+        }
+
+        let count = tabs.len();
+        let first = if self.tabs[tab_index].secret_key.trim().is_empty() {
+            self.tabs.splice(tab_index..=tab_index, tabs);
+            tab_index
+        } else {
+            self.tabs.extend(tabs);
+            self.tabs.len() - count
+        };
+
+        for idx in first..first + count {
+            self.generate_token(idx);
+        }
+        self.active_tab = first;
+        self.pending_removal = None;
+        self.tab_menu = None;
+
+        let mut message = format!("Imported {} account{}", count, if count == 1 { "" } else { "s" });
+        if skipped > 0 {
+            message.push_str(&format!(", skipped {} unsupported", skipped));
+        }
+        self.tabs[first].show_timed_message(message, self.message_timeout_secs);
+    }
+
+    // Put the secret given at launch into the first tab and show its code
+    fn apply_startup_secret(&mut self) {
+        let Some(secret) = self.startup_secret.take() else {
+            return;
+        };
+
+        let secret = match secret {
+            Ok(secret) => secret,
+            Err(e) => {
+                self.tabs[self.active_tab].error = Some(e);
+                return;
+            }
+        };
+
+        // The launch secret gets a tab of its own that isn't saved, so it never replaces
+        // a saved account. A lone empty tab is taken over rather than left next to it.
+        let tab = Tab {
+            issuer: String::from("Launch secret"),
+            secret_key: secret,
+            editing_name: false,
+            transient: true,
+            ..Default::default()
+        };
+        if self.tabs.len() == 1 && self.tabs[0].secret_key.is_empty() {
+            self.tabs[0] = tab;
+        } else {
+            self.tabs.push(tab);
+        }
+        self.active_tab = self.tabs.len() - 1;
+        self.generate_token(self.active_tab);
+    }
+
+    // Open the otpauth:// link given at launch: a tab with the same secret is selected,
+    // otherwise the link is set up in the active tab if that is still empty, or in a new one
+    fn apply_launch_uri(&mut self) {
+        let Some(uri) = self.launch_uri.take() else {
+            return;
+        };
+
+        let parsed = match parse_otpauth_uri(uri.trim()) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                self.tabs[self.active_tab].error = Some(e);
+                return;
+            }
+        };
+        let mut tab = Tab {
+            editing_name: false,
+            ..Default::default()
+        };
+        let warning = tab.apply_parsed_otp(parsed);
+
+        if let Ok(secret) = tab.decode_key(self.strict_base32) {
+            let existing = self.tabs.iter().position(|other| {
+                other.decode_key(self.strict_base32).ok().as_ref() == Some(&secret)
+            });
+            if let Some(idx) = existing {
+                self.active_tab = idx;
+                self.mark_used(idx);
+                return;
+            }
+        }
+
+        if self.tabs[self.active_tab].secret_key.trim().is_empty() {
+            self.tabs[self.active_tab] = tab;
+        } else {
+            self.tabs.push(tab);
+            self.active_tab = self.tabs.len() - 1;
+        }
+        self.generate_token(self.active_tab);
+        if warning.is_some() {
+            self.tabs[self.active_tab].error = warning;
+        }
+    }
+
+    // Password prompt shown instead of the tabs while the saved state is encrypted
+    fn locked_view(&self) -> Element<'_, Message> {
+        let password_input = text_input("Master password", &self.password_input)
+            .password()
+            .padding(12)
+            .size(self.scaled(16.0))
+            .on_input(Message::PasswordInputChanged)
+            .on_submit(Message::Unlock);
+
+        let unlock_button = button(
+            text(if self.deriving_key { "Unlocking…" } else { "Unlock" })
+                .size(self.scaled(16.0))
+                .style(iced::theme::Text::Color(self.theme().extended_palette().primary.base.text))
+        )
+        .padding(10)
+        .style(theme::Button::Custom(Box::new(BlueButtonStyle)))
+        .on_press(Message::Unlock);
+
+        let error_display = match &self.password_error {
+            Some(error) => container(
+                text(format!("⚠ {}", error))
+                    .size(self.scaled(14.0))
+                    .style(iced::theme::Text::Color(self.theme().palette().danger))
+            )
+            .padding([8, 12, 8, 12])
+            .style(theme::Container::Custom(Box::new(ErrorMessageStyle))),
+            None => container(text("").size(0)),
+        };
+
+        let content = column![
+            text("Enter your master password to unlock your tabs").size(self.scaled(18.0)),
+            vertical_space(20),
+            row![password_input, unlock_button]
+                .spacing(10)
+                .align_items(Alignment::Center),
+            vertical_space(20),
+            error_display
+        ]
+        .padding(30)
+        .max_width(500)
+        .align_items(Alignment::Center);
+
+        container(content)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .center_x()
+            .center_y()
+            .style(theme::Container::Box)
+            .into()
+    }
+
+    // Code shown in the mini window: the active tab's, masked where the full view would be
+    fn mini_token_text(&self) -> String {
+        let active_tab = &self.tabs[self.active_tab];
+        if active_tab.token.is_empty() {
+            "——————".to_string()
+        } else if (self.hide_tokens && !active_tab.revealed)
+            || tokens_obscured(self.hide_when_unfocused, self.focused)
+        {
+            mask_token(&active_tab.token)
+        } else {
+            format_token(&active_tab.token)
+        }
+    }
+
+    // Just the active code and its countdown, for a small window kept beside other apps
+    fn mini_view(&self) -> Element<'_, Message> {
+        let palette = self.theme().palette();
+        let muted_text = muted_text_color(&self.theme());
+        let active_tab = &self.tabs[self.active_tab];
+
+        let masked = self.hide_tokens && !active_tab.revealed;
+        let mut token_button = button(
+            text(self.mini_token_text())
+                .size(self.scaled(36.0))
+                .style(iced::theme::Text::Color(palette.text))
+        )
+        .padding([5, 10])
+        .style(theme::Button::Custom(Box::new(TokenButtonStyle)));
+        if !active_tab.token.is_empty() {
+            token_button = token_button.on_press(if masked {
+                Message::RevealToken(self.active_tab)
+            } else {
+                Message::CopyToClipboard(self.active_tab)
+            });
+        }
+
+        let countdown: Element<_> = if active_tab.mode == OtpMode::Hotp {
+            text(format!("Counter: {}", active_tab.counter))
+                .size(self.scaled(12.0))
+                .style(iced::theme::Text::Color(muted_text))
+                .into()
+        } else {
+            let progress = if active_tab.token.is_empty() {
+                0.0
+            } else {
+                progress_value(active_tab.time_remaining, active_tab.period, self.progress_fill)
+            };
+            column![
+                iced::widget::progress_bar(0.0..=1.0, progress)
+                    .height(Length::Fixed(4.0))
+                    .width(Length::Fill)
+                    .style(theme::ProgressBar::Custom(Box::new(CountdownBarStyle(
+                        countdown_urgency(active_tab.time_remaining),
+                    )))),
+                text(format_remaining(active_tab.time_remaining))
+                    .size(self.scaled(12.0))
+                    .style(iced::theme::Text::Color(muted_text)),
+            ]
+            .spacing(4)
+            .align_items(Alignment::Center)
+            .into()
+        };
+
+        let controls = row![
+            checkbox("On top", self.mini_on_top, Message::MiniOnTopToggled)
+                .size(self.scaled(12.0))
+                .text_size(self.scaled(12.0)),
+            horizontal_space(Length::Fill),
+            button(text("Full view").size(self.scaled(12.0)))
+                .padding(4)
+                .style(theme::Button::Secondary)
+                .on_press(Message::ToggleMiniMode),
+        ]
+        .align_items(Alignment::Center);
+
+        let content = column![
+            text(active_tab.display_name())
+                .size(self.scaled(12.0))
+                .style(iced::theme::Text::Color(muted_text)),
+            token_button,
+            countdown,
+            controls,
+        ]
+        .spacing(6)
+        .padding(10)
+        .align_items(Alignment::Center);
+
+        container(content)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .center_x()
+            .center_y()
+            .into()
+    }
+
+    // Put the window away, keeping track of it being hidden to the tray
+    fn hide_window(&mut self, action: HideAction) -> Command<Message> {
+        match action {
+            HideAction::ToTray => {
+                self.window_hidden = true;
+                window::change_mode(window::Mode::Hidden)
+            }
+            HideAction::Minimize => window::minimize(true),
+        }
+    }
+
+    // Copy `contents` to the clipboard, reporting the outcome in the tab's message area
+    fn copy_to_clipboard(&mut self, tab_index: usize, contents: String, success: &str) -> Command<Message> {
+        self.try_copy_to_clipboard(tab_index, contents, success)
+            .unwrap_or_else(Command::none)
+    }
+
+    // copy_to_clipboard that also tells whether the copy went through: None if the clipboard
+    // couldn't be written or read back something else
+    fn try_copy_to_clipboard(
+        &mut self,
+        tab_index: usize,
+        contents: String,
+        success: &str,
+    ) -> Option<Command<Message>> {
+        let mut ctx: clipboard::ClipboardContext = match ClipboardProvider::new() {
+            Ok(ctx) => ctx,
+            Err(_) => {
+                self.clipboard_unavailable = true;
+                self.tabs[tab_index].error =
+                    Some("Clipboard unavailable, select the code and copy it manually".to_string());
+                return None;
+            }
+        };
+        
+        if let Err(e) = ctx.set_contents(contents.clone()) {
+            self.tabs[tab_index].error = Some(format!("Failed to copy to clipboard: {}", e));
+            return None;
+        }
+
+        self.clipboard_unavailable = false;
+        let (warning, read_back) = if self.verify_clipboard {
+            let read_back = ctx.get_contents().ok();
+            (clipboard_readback_warning(&contents, read_back.as_deref()), read_back.is_some())
+        } else {
+            (None, false)
+        };
+        match warning {
+            // The clipboard holds something else, which isn't ours to clear later. If it only
+            // couldn't be read back, the copy most likely went through and is cleared as usual.
+            Some(warning) if read_back => {
+                self.tabs[tab_index].error = Some(warning);
+                self.last_copied = None;
+                return None;
+            }
+            Some(warning) => self.tabs[tab_index].error = Some(warning),
+            None => self.tabs[tab_index].show_timed_message(success.to_string(), self.message_timeout_secs),
+        }
+
+        if self.clipboard_clear_secs == 0 {
+            self.last_copied = None;
+            return Some(Command::none());
+        }
+
+        // Wipe the copied value from the clipboard once the delay has passed
+        self.last_copied = Some(contents.clone());
+        let delay = self.clipboard_clear_secs;
+        Some(Command::perform(
+            async move {
+                tokio::time::sleep(Duration::from_secs(delay)).await;
+                contents
+            },
+            Message::ClearClipboard,
+        ))
+    }
+    
+    // Index of another tab whose secret decodes to the same bytes as tab `idx`, so the
+    // same key written differently (spacing, case, Base32 vs hex) still counts
+    fn find_duplicate_secret(&self, idx: usize) -> Option<usize> {
+        let tab = self.tabs.get(idx)?;
+        let secret = tab.decode_key(self.strict_base32).ok()?;
+
+        self.tabs.iter().enumerate().position(|(other, other_tab)| {
+            other != idx
+                && other_tab.decode_key(self.strict_base32).ok().as_ref() == Some(&secret)
+        })
+    }
+
+    // Point out a duplicated secret without stopping the tab from working
+    fn warn_about_duplicate(&mut self, idx: usize) {
+        if self.tabs[idx].error.is_some() {
+            return;
+        }
+        if let Some(other) = self.find_duplicate_secret(idx) {
+            self.tabs[idx].error = Some(format!(
+                "This secret matches tab \"{}\"",
+                self.display_name(other)
+            ));
+        }
+    }
+
+    // An otpauth:// URI pasted into the issuer or account field sets up the whole tab, as if
+    // it had been pasted into the secret field. Returns false for anything else, which is
+    // then used as the name unchanged; a URI that doesn't parse is kept as typed with an error.
+    fn apply_uri_in_name(&mut self, value: &str, idx: usize) -> bool {
+        if !is_otpauth_uri(value) {
+            return false;
+        }
+
+        match parse_otpauth_uri(value.trim()) {
+            Ok(parsed) => {
+                let warning = self.tabs[idx].apply_parsed_otp(parsed);
+                self.generate_token(idx);
+                if warning.is_some() {
+                    self.tabs[idx].error = warning;
+                }
+                self.warn_about_duplicate(idx);
+                true
+            }
+            Err(e) => {
+                self.tabs[idx].error = Some(e);
+                false
+            }
+        }
+    }
+
+    // Indices into `tabs` in the order they are shown: pinned tabs first, each group
+    // keeping its saved order. `active_tab` and messages keep using the real indices.
+    fn ordered_tab_indices(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.tabs.len()).collect();
+        match self.tab_order {
+            // Stable sorts, so ties keep the manual order
+            TabOrder::Manual => order.sort_by_key(|&idx| !self.tabs[idx].pinned),
+            TabOrder::RecentlyUsed => order.sort_by(|&a, &b| compare_by_recency(&self.tabs[a], &self.tabs[b])),
+        }
+        order
+    }
+
+    // Tabs grouped by issuer for the sidebar, in display order: each group sits where its
+    // first tab would be. Issuers that differ only in case or surrounding spaces share a
+    // group, named as first seen; tabs without an issuer are grouped under "".
+    fn grouped_tabs(&self) -> Vec<(String, Vec<usize>)> {
+        let mut groups: Vec<(String, Vec<usize>)> = Vec::new();
+        for idx in self.ordered_tab_indices() {
+            let issuer = self.tabs[idx].issuer.trim();
+            match groups.iter_mut().find(|(name, _)| issuer_group_key(name) == issuer_group_key(issuer)) {
+                Some((_, indices)) => indices.push(idx),
+                None => groups.push((issuer.to_string(), vec![idx])),
+            }
+        }
+        groups
+    }
+
+    // Tab `idx`'s name as listed outside the tab bar, told apart from same-named tabs
+    fn display_name(&self, idx: usize) -> String {
+        external_tab_names(&self.tabs).swap_remove(idx)
+    }
+
+    // Record that a tab was just selected or copied from. The displayed order only takes
+    // this into account at the next refresh_recency_order.
+    fn mark_used(&mut self, idx: usize) {
+        self.tabs[idx].last_used = Some(effective_now(self.clock_offset_secs));
+    }
+
+    // Let the "recently used" order reflect the latest use of every tab
+    fn refresh_recency_order(&mut self) {
+        for tab in &mut self.tabs {
+            tab.shown_last_used = tab.last_used;
+        }
+    }
+
+    // Start loading the favicon of every issuer domain that hasn't been requested yet. Each
+    // domain is only fetched once per run, whether or not it has an icon.
+    fn fetch_missing_favicons(&mut self) -> Command<Message> {
+        if !self.show_favicons || self.locked {
+            return Command::none();
+        }
+        // Runs after every update, so bail out early unless an issuer was added or changed
+        if self.tabs.iter().map(|tab| &tab.issuer).eq(self.favicon_issuers.iter()) {
+            return Command::none();
+        }
+        self.favicon_issuers = self.tabs.iter().map(|tab| tab.issuer.clone()).collect();
+
+        let mut commands = Vec::new();
+        for domain in self.tabs.iter().filter_map(|tab| favicon_domain(&tab.issuer)) {
+            if self.favicons.contains_key(&domain) {
+                continue;
+            }
+            self.favicons.insert(domain.clone(), None);
+            commands.push(Command::perform(
+                async move {
+                    // The download blocks, so it runs on the blocking pool
+                    let fetched = domain.clone();
+                    let icon = run_blocking(move || Ok(fetch_favicon(&fetched))).await.ok().flatten();
+                    (domain, icon)
+                },
+                |(domain, icon)| Message::FaviconLoaded(domain, icon),
+            ));
+        }
+        Command::batch(commands)
+    }
+
+    // Loaded favicon for a tab's issuer, if icons are enabled
+    fn tab_favicon(&self, tab: &Tab) -> Option<image::Handle> {
+        if !self.show_favicons {
+            return None;
+        }
+        favicon_domain(&tab.issuer).and_then(|domain| self.favicons.get(&domain).cloned().flatten())
+    }
+
+    // Rebuild the tray menu if a tab was added, removed, renamed or got its first code
+    fn refresh_tray_menu(&mut self) {
+        let Some(tray) = &self.tray else {
+            return;
+        };
+
+        let entries = tray_menu_entries(&self.tabs, self.locked);
+        if entries != self.tray_entries {
+            tray.set_menu(Some(Box::new(build_tray_menu(&entries))));
+            self.tray_entries = entries;
+        }
+    }
+
+    // Swap the registered global shortcut for the one in `hotkey_input`. A shortcut another
+    // app already owns is reported next to the setting rather than treated as fatal.
+    fn register_hotkey(&mut self) {
+        let Some(manager) = &self.hotkey_manager else {
+            return;
+        };
+
+        if let Some(old) = self.hotkey.take() {
+            let _ = manager.unregister(old);
+        }
+        self.hotkey_error = None;
+
+        if self.hotkey_input.trim().is_empty() {
+            return;
+        }
+
+        match parse_hotkey(&self.hotkey_input) {
+            Ok(hotkey) => match manager.register(hotkey) {
+                Ok(()) => self.hotkey = Some(hotkey),
+                Err(e) => {
+                    self.hotkey_error = Some(format!("Could not register {}: {}", self.hotkey_input.trim(), e));
+                }
+            },
+            Err(e) => self.hotkey_error = Some(e),
+        }
+    }
+
+    // Text size after applying the user's scale setting
+    fn scaled(&self, base: f32) -> f32 {
+        base * self.scale
+    }
+
+    // Change the clock offset and refresh the codes that depend on it
+    fn set_clock_offset(&mut self, offset: i64) {
+        self.clock_offset_secs = offset;
+        for idx in 0..self.tabs.len() {
+            if !self.tabs[idx].token.is_empty() {
+                self.generate_token(idx);
+            }
+        }
+    }
+
+    // Highlight the active code and end the highlight after ROLLOVER_FLASH_MS
+    fn start_rollover_flash(&mut self) -> Command<Message> {
+        self.flashing = true;
+        self.flash_generation += 1;
+        let generation = self.flash_generation;
+        Command::perform(
+            async move {
+                tokio::time::sleep(Duration::from_millis(ROLLOVER_FLASH_MS)).await;
+                generation
+            },
+            Message::RolloverFlashEnded,
+        )
+    }
+
+    fn generate_token(&mut self, tab_index: usize) {
+        self.generate_token_at(tab_index, SystemTime::now());
+    }
+
+    // generate_token with the system clock reading `clock`, so tests can use a fixed time
+    fn generate_token_at(&mut self, tab_index: usize, clock: SystemTime) {
+        if tab_index >= self.tabs.len() {
+            return;
+        }
+        
+        let now = unix_time_at(clock).map(|now| now.saturating_add_signed(self.clock_offset_secs));
+        let strict = self.strict_base32;
+        let is_active = tab_index == self.active_tab;
+        let history_len = if self.keep_history { HISTORY_LEN } else { 0 };
+        let tab = &mut self.tabs[tab_index];
+        
+        if tab.secret_key.is_empty() {
+            tab.error = Some(SecretError::Empty.to_string());
+            tab.token = String::new();
+            return;
+        }
+
+        // A code from a wrong clock would look valid and just fail, so show none instead
+        let now = match now {
+            Ok(now) => now,
+            Err(e) => {
+                tab.error = Some(e);
+                tab.token = String::new();
+                tab.next_token = String::new();
+                return;
+            }
+        };
+
+        tab.late_refresh = false;
+        let previous_token = std::mem::take(&mut tab.token);
+        let generated = tab
+            .padded_key(strict)
+            .map_err(|e| e.to_string())
+            .and_then(|key| token_with_key(tab, &key, now).map(|token| (key, token)));
+        match generated {
+            Ok((key, token)) => {
+                tab.token = token;
+                tab.error = None;
+
+                // Update time remaining and the preview of the following code
+                if tab.mode != OtpMode::Hotp {
+                    let period = safe_period(tab.period);
+                    tab.time_remaining = period - (now % period);
+                    tab.last_counter = current_counter(now, period);
+                    tab.next_token = token_with_key(tab, &key, now.saturating_add(period)).unwrap_or_default();
+                }
+            }
+            Err(e) => {
+                tab.error = Some(e);
+                tab.token = String::new();
+                tab.next_token = String::new();
+            }
+        }
+
+        if !tab.token.is_empty() && tab.token != previous_token {
+            tab.record_history(now, tab.token.clone(), history_len);
+        }
+        if tab.token != previous_token && should_auto_copy(tab, is_active, self.auto_copy_background) {
+            self.pending_auto_copies.push(tab_index);
+        }
+    }
+
+    // Copy the codes queued by generate_token for tabs with auto-copy on. Only the last one
+    // ends up on the clipboard if several tabs rolled over together.
+    fn auto_copy_new_tokens(&mut self) -> Command<Message> {
+        let Some(tab_index) = self.pending_auto_copies.drain(..).last() else {
+            return Command::none();
+        };
+        match self.tabs.get(tab_index) {
+            Some(tab) if !tab.token.is_empty() => {
+                let token = copied_token(&tab.token, self.copy_grouped);
+                self.copy_to_clipboard(tab_index, token, "New code copied to clipboard")
+            }
+            _ => Command::none(),
+        }
+    }
+}
+
+// Focus id of a tab's issuer field while it is being renamed
+fn rename_input_id(idx: usize) -> text_input::Id {
+    text_input::Id::new(format!("rename-issuer-{}", idx))
+}
+
+// Text of a tab's period field: what is being typed, or else the period in use
+fn period_text(tab: &Tab) -> String {
+    tab.period_input.clone().unwrap_or_else(|| tab.period.to_string())
+}
+
+// Map runtime events to app messages
+fn handle_event(event: Event, status: event::Status) -> Option<Message> {
+    match event {
+        Event::Window(window::Event::Resized { width, height }) => {
+            Some(Message::WindowResized(width, height))
+        }
+        Event::Window(window::Event::Moved { x, y }) => Some(Message::WindowMoved(x, y)),
+        Event::Window(window::Event::CloseRequested) => Some(Message::WindowCloseRequested),
+        Event::Window(window::Event::FileDropped(path)) => Some(Message::FileDropped(path)),
+        Event::Window(window::Event::Focused) => Some(Message::WindowFocusChanged(true)),
+        Event::Window(window::Event::Unfocused) => Some(Message::WindowFocusChanged(false)),
+        // Ctrl+C copies the code unless a focused text field already handled it
+        Event::Keyboard(keyboard::Event::KeyPressed {
+            key_code: keyboard::KeyCode::C,
+            modifiers,
+        }) if modifiers.command() && status == event::Status::Ignored => {
+            Some(Message::CopyActiveToken)
+        }
+        // Ctrl+= / Ctrl+- / Ctrl+0 change the text size, like in a browser
+        Event::Keyboard(keyboard::Event::KeyPressed { key_code, modifiers }) if modifiers.command()
+            && matches!(
+                key_code,
+                keyboard::KeyCode::Equals | keyboard::KeyCode::Plus | keyboard::KeyCode::NumpadAdd
+            ) =>
+        {
+            Some(Message::ScaleUp)
+        }
+        Event::Keyboard(keyboard::Event::KeyPressed { key_code, modifiers }) if modifiers.command()
+            && matches!(key_code, keyboard::KeyCode::Minus | keyboard::KeyCode::NumpadSubtract) =>
+        {
+            Some(Message::ScaleDown)
+        }
+        Event::Keyboard(keyboard::Event::KeyPressed {
+            key_code: keyboard::KeyCode::Key0,
+            modifiers,
+        }) if modifiers.command() => Some(Message::ResetScale),
+        // F2 renames the active tab; Enter and Escape finish or cancel a rename. Both are left
+        // to a focused text field first: Enter submits it and Escape just leaves it.
+        Event::Keyboard(keyboard::Event::KeyPressed {
+            key_code: keyboard::KeyCode::F2,
+            ..
+        }) => Some(Message::RenameActiveTab),
+        Event::Keyboard(keyboard::Event::KeyPressed {
+            key_code: keyboard::KeyCode::Enter,
+            ..
+        }) if status == event::Status::Ignored => Some(Message::ConfirmRenames),
+        Event::Keyboard(keyboard::Event::KeyPressed {
+            key_code: keyboard::KeyCode::Escape,
+            ..
+        }) if status == event::Status::Ignored => Some(Message::CancelRenames),
+        // Ctrl+T opens a new tab and Ctrl+W asks to close the active one, like in a browser
+        Event::Keyboard(keyboard::Event::KeyPressed {
+            key_code: keyboard::KeyCode::T,
+            modifiers,
+        }) if modifiers.command() => Some(Message::AddTab),
+        Event::Keyboard(keyboard::Event::KeyPressed {
+            key_code: keyboard::KeyCode::W,
+            modifiers,
+        }) if modifiers.command() => Some(Message::RequestRemoveActiveTab),
+        // Ctrl+M switches between the full window and the mini one
+        Event::Keyboard(keyboard::Event::KeyPressed {
+            key_code: keyboard::KeyCode::M,
+            modifiers,
+        }) if modifiers.command() => Some(Message::ToggleMiniMode),
+        // Ctrl+1..Ctrl+8 jump to that tab and Ctrl+9 to the last one, like in a browser
+        Event::Keyboard(keyboard::Event::KeyPressed { key_code, modifiers })
+            if modifiers.command() && digit_key_number(key_code).is_some() =>
+        {
+            digit_key_number(key_code).map(Message::SelectTabNumber)
+        }
+        // Ctrl+Tab / Ctrl+Shift+Tab cycle through the tabs, even while typing
+        Event::Keyboard(keyboard::Event::KeyPressed {
+            key_code: keyboard::KeyCode::Tab,
+            modifiers,
+        }) if modifiers.control() => Some(Message::CycleTab(!modifiers.shift())),
+        _ => None,
+    }
+}
+
+// Tray menu entries for the tabs, in tab order. Nothing is listed while the vault is
+// locked, since the tabs shown then are only a placeholder.
+fn tray_menu_entries(tabs: &[Tab], locked: bool) -> Vec<TrayMenuEntry> {
+    if locked {
+        return Vec::new();
+    }
+
+    tabs.iter()
+        .zip(external_tab_names(tabs))
+        .map(|(tab, label)| TrayMenuEntry {
+            label,
+            enabled: !tab.token.is_empty(),
+        })
+        .collect()
+}
+
+// Whether the window stays hidden at launch. Starting minimized needs the tray icon to
+// reopen the window from, so without one the window is shown anyway.
+fn start_hidden(start_minimized: bool, tray_available: bool) -> bool {
+    start_minimized && tray_available
+}
+
+// Tray icon with the tab menu, or None where there is no system tray. On Linux tray-icon
+// panics rather than failing when GTK isn't initialised, so that counts as no tray too.
+fn build_tray_icon(entries: &[TrayMenuEntry]) -> Option<TrayIcon> {
+    let build = || {
+        TrayIconBuilder::new()
+            .with_tooltip("TOTP Token Generator")
+            .with_menu(Box::new(build_tray_menu(entries)))
+            .with_icon(tray_icon_image())
+            .build()
+            .ok()
+    };
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(build)).ok().flatten()
+}
+
+// Tray menu with one "copy" item per tab, followed by Show and Quit
+fn build_tray_menu(entries: &[TrayMenuEntry]) -> Menu {
+    let menu = Menu::new();
+    for (idx, entry) in entries.iter().enumerate() {
+        let item = MenuItem::with_id(
+            format!("{}{}", TRAY_COPY_PREFIX, idx),
+            &entry.label,
+            entry.enabled,
+            None,
+        );
+        let _ = menu.append(&item);
+    }
+    if !entries.is_empty() {
+        let _ = menu.append(&PredefinedMenuItem::separator());
+    }
+    let _ = menu.append(&MenuItem::with_id(TRAY_SHOW_ID, "Show window", true, None));
+    let _ = menu.append(&MenuItem::with_id(TRAY_QUIT_ID, "Quit", true, None));
+    menu
+}
+
+// Message for a tray menu item, identified by the id build_tray_menu gave it
+fn tray_menu_message(id: &str) -> Option<Message> {
+    match id {
+        TRAY_SHOW_ID => Some(Message::ShowWindow),
+        TRAY_QUIT_ID => Some(Message::Quit),
+        _ => id
+            .strip_prefix(TRAY_COPY_PREFIX)
+            .and_then(|idx| idx.parse().ok())
+            .map(Message::CopyToClipboard),
+    }
+}
+
+// Tray icon drawn at startup: a filled blue circle, so no image file has to ship with the binary
+fn tray_icon_image() -> Icon {
+    let center = (TRAY_ICON_SIZE as f32 - 1.0) / 2.0;
+    let radius = TRAY_ICON_SIZE as f32 / 2.0;
+    let mut rgba = Vec::with_capacity((TRAY_ICON_SIZE * TRAY_ICON_SIZE * 4) as usize);
+
+    for y in 0..TRAY_ICON_SIZE {
+        for x in 0..TRAY_ICON_SIZE {
+            let distance = ((x as f32 - center).powi(2) + (y as f32 - center).powi(2)).sqrt();
+            let alpha = if distance <= radius { 255 } else { 0 };
+            rgba.extend_from_slice(&[0x1E, 0x88, 0xE5, alpha]);
+        }
+    }
+
+    Icon::from_rgba(rgba, TRAY_ICON_SIZE, TRAY_ICON_SIZE).expect("tray icon has a valid size")
+}
+
+// Existing message carrying out a tab menu entry for tab `idx`
+fn tab_menu_message(action: TabMenuAction, idx: usize) -> Message {
+    match action {
+        TabMenuAction::Rename => Message::RenameTabStarted(idx),
+        TabMenuAction::Duplicate => Message::DuplicateTab(idx),
+        // Goes through the usual confirmation
+        TabMenuAction::Remove => Message::RequestRemoveTab(idx),
+        TabMenuAction::TogglePin => Message::TogglePinned(idx),
+        TabMenuAction::SetColor(color) => Message::TabColorChanged(color, idx),
+    }
+}
+
+// Read a shortcut such as "Ctrl+Alt+T" or "super+shift+F5". Modifier names are
+// case-insensitive; the key is a letter, a digit, or a key name like "F5" or "Space".
+fn parse_hotkey(input: &str) -> Result<HotKey, String> {
+    let parts: Vec<&str> = input.split('+').map(str::trim).collect();
+    let Some((key, modifier_names)) = parts.split_last() else {
+        return Err("Please enter a shortcut".to_string());
+    };
+
+    let mut modifiers = Modifiers::empty();
+    for name in modifier_names {
+        modifiers |= match name.to_lowercase().as_str() {
+            "ctrl" | "control" => Modifiers::CONTROL,
+            "alt" | "option" => Modifiers::ALT,
+            "shift" => Modifiers::SHIFT,
+            "super" | "cmd" | "command" | "meta" | "win" => Modifiers::SUPER,
+            _ => return Err(format!("Unknown modifier \"{}\" in shortcut", name)),
+        };
+    }
+    if modifiers.is_empty() {
+        return Err("Shortcut needs at least one modifier (Ctrl, Alt, Shift or Super)".to_string());
+    }
+
+    // Code names single characters "KeyT" and "Digit5"; everything else is passed through
+    let code_name = match key.chars().collect::<Vec<_>>().as_slice() {
+        [c] if c.is_ascii_alphabetic() => format!("Key{}", c.to_ascii_uppercase()),
+        [c] if c.is_ascii_digit() => format!("Digit{}", c),
+        _ => {
+            let mut chars = key.chars();
+            chars
+                .next()
+                .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+                .unwrap_or_default()
+        }
+    };
+    let code = Code::from_str(&code_name)
+        .ok()
+        .filter(|code| *code != Code::Unidentified)
+        .ok_or_else(|| format!("Unknown key \"{}\" in shortcut", key))?;
+
+    Ok(HotKey::new(Some(modifiers), code))
+}
+
+// Number on a 1-9 digit key, from either the top row or the numpad
+fn digit_key_number(key_code: keyboard::KeyCode) -> Option<u8> {
+    use keyboard::KeyCode::*;
+
+    match key_code {
+        Key1 | Numpad1 => Some(1),
+        Key2 | Numpad2 => Some(2),
+        Key3 | Numpad3 => Some(3),
+        Key4 | Numpad4 => Some(4),
+        Key5 | Numpad5 => Some(5),
+        Key6 | Numpad6 => Some(6),
+        Key7 | Numpad7 => Some(7),
+        Key8 | Numpad8 => Some(8),
+        Key9 | Numpad9 => Some(9),
+        _ => None,
+    }
+}
+
+// Position of the tab selected by Ctrl+<number>: 1-8 pick that tab if it exists and
+// 9 always picks the last one
+fn tab_for_number(number: u8, len: usize) -> Option<usize> {
+    match number {
+        9 => len.checked_sub(1),
+        1..=8 => Some(number as usize - 1).filter(|&idx| idx < len),
+        _ => None,
+    }
+}
+
+// Saved window size, clamped so a bad value (e.g. 0x0 while minimized) can't hide the window
+fn window_size(geometry: &WindowGeometry) -> (u32, u32) {
+    (
+        geometry.width.clamp(MIN_WINDOW_SIZE.0, MAX_WINDOW_SIZE.0),
+        geometry.height.clamp(MIN_WINDOW_SIZE.1, MAX_WINDOW_SIZE.1),
+    )
+}
+
+// Messages in the tab's message area that report success rather than an error
+fn is_success_message(message: &str) -> bool {
+    message.contains("copied to clipboard")
+        || message.starts_with("Copied")
+        || message.starts_with("Imported")
+        || message.starts_with("Backup saved")
+        || message.starts_with("Exported")
+}
+
+// Warning for when the clipboard, read back after a copy, doesn't hold what was written
+// (`None` means it couldn't be read). Some platforms cut long contents short silently.
+fn clipboard_readback_warning(written: &str, read_back: Option<&str>) -> Option<String> {
+    match read_back {
+        Some(read_back) if read_back == written => None,
+        Some(read_back) if !read_back.is_empty() && written.starts_with(read_back) => Some(format!(
+            "The clipboard only kept {} of {} characters",
+            read_back.chars().count(),
+            written.chars().count()
+        )),
+        Some(_) => Some("The clipboard doesn't hold what was copied".to_string()),
+        None => Some("Copied, but the clipboard couldn't be read back to check it".to_string()),
+    }
+}
+
+// One "name: code" line per tab that has a code, plus how many lines there are
+fn all_tokens_text(tabs: &[Tab], grouped: bool) -> (String, usize) {
+    let lines: Vec<String> = tabs
+        .iter()
+        .zip(external_tab_names(tabs))
+        .filter(|(tab, _)| !tab.token.is_empty())
+        .map(|(tab, name)| format!("{}: {}", name, copied_token(&tab.token, grouped)))
+        .collect();
+    (lines.join("\n"), lines.len())
+}
+
+// Names for listing tabs outside the tab bar (tray menu, "Copy all", messages). Unnamed tabs
+// become "Tab N", and a name shared by several tabs (ignoring case) gets each tab's number
+// appended, e.g. "New Tab #1" and "New Tab #3". The tabs themselves keep their names.
+fn external_tab_names(tabs: &[Tab]) -> Vec<String> {
+    let names: Vec<String> = tabs
+        .iter()
+        .enumerate()
+        .map(|(idx, tab)| {
+            let name = tab.display_name();
+            if name.is_empty() { format!("Tab {}", idx + 1) } else { name }
+        })
+        .collect();
+
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for name in &names {
+        *counts.entry(name.to_lowercase()).or_default() += 1;
+    }
+
+    names
+        .into_iter()
+        .enumerate()
+        .map(|(idx, name)| {
+            if counts[&name.to_lowercase()] > 1 {
+                format!("{} #{}", name, idx + 1)
+            } else {
+                name
+            }
+        })
+        .collect()
+}
+
+// Read the command-line options. --secret selects headless mode; otherwise the GUI starts,
+// optionally with a secret file. Both "--digits 8" and "--digits=8" are accepted.
+fn parse_cli_args(args: &[String]) -> Result<LaunchMode, String> {
+    let mut tab = Tab {
+        secret_key: String::new(),
+        editing_name: false,
+        ..Default::default()
+    };
+    let mut secret = None;
+    let mut secret_file = None;
+    let mut launch_uri = None;
+    let mut has_token_options = false;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        // A bare otpauth:// link, as passed by the browser when the app handles the scheme
+        if is_otpauth_uri(arg) {
+            launch_uri = Some(arg.trim().to_string());
+            continue;
+        }
+
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) => (flag, Some(value.to_string())),
+            None => (arg.as_str(), None),
+        };
+        let value = match inline_value {
+            Some(value) => value,
+            None => args
+                .next()
+                .cloned()
+                .ok_or_else(|| format!("{} needs a value", flag))?,
+        };
+
+        match flag {
+            "--secret" => secret = Some(value),
+            "--secret-file" => secret_file = Some(value),
+            "--digits" => {
+                tab.digits = value
+                    .parse()
+                    .ok()
+                    .filter(|digits| DIGIT_OPTIONS.contains(digits))
+                    .ok_or_else(|| format!("Unsupported digits value \"{}\" (use 6, 7 or 8)", value))?;
+            }
+            "--period" => {
+                tab.period = value
+                    .parse()
+                    .ok()
+                    .filter(|period| *period > 0)
+                    .ok_or_else(|| format!("Invalid period \"{}\"", value))?;
+            }
+            "--algorithm" => {
+                tab.algorithm = ALGORITHMS
+                    .into_iter()
+                    .find(|algorithm| algorithm.to_string().eq_ignore_ascii_case(&value))
+                    .ok_or_else(|| format!("Unsupported algorithm \"{}\"", value))?;
+            }
+            _ => return Err(format!("Unknown option {}", flag)),
+        }
+        has_token_options |= matches!(flag, "--digits" | "--period" | "--algorithm");
+    }
+
+    match (secret, secret_file) {
+        (Some(_), Some(_)) => Err("Use either --secret or --secret-file, not both".to_string()),
+        (Some(_), None) if launch_uri.is_some() => {
+            Err("Use either --secret or an otpauth:// link, not both".to_string())
+        }
+        (Some(secret), None) => {
+            tab.secret_key = secret;
+            Ok(LaunchMode::Headless(tab))
+        }
+        (None, _) if has_token_options => Err("--secret is required".to_string()),
+        (None, secret_file) => Ok(LaunchMode::Gui { secret_file, launch_uri }),
+    }
+}
+
+// Pick the launch secret: a --secret-file read (already attempted) wins over the
+// TOTP_SECRET variable. Surrounding whitespace and newlines are trimmed, and an
+// empty variable counts as unset.
+fn resolve_startup_secret(
+    file: Option<Result<String, String>>,
+    env: Option<String>,
+) -> Option<Result<String, String>> {
+    match file {
+        Some(contents) => Some(contents.map(|secret| secret.trim().to_string())),
+        None => env
+            .map(|secret| secret.trim().to_string())
+            .filter(|secret| !secret.is_empty())
+            .map(Ok),
+    }
+}
+
+// Case-insensitive substring match used by the tab search box
+fn tab_matches_filter(name: &str, filter: &str) -> bool {
+    name.to_lowercase().contains(&filter.trim().to_lowercase())
+}
+
+// Estimated width of a tab in the tab bar: its label plus `extra_buttons` small buttons.
+// iced doesn't report widget sizes to view(), so this goes by character count.
+fn estimated_tab_width(label: &str, text_size: f32, extra_buttons: usize) -> f32 {
+    let label = label.chars().count() as f32 * text_size * TAB_CHAR_WIDTH_EM + 16.0;
+    label + extra_buttons as f32 * (text_size + 12.0)
+}
+
+// How many of the tabs, with the given widths and in order, fit side by side in
+// `available` pixels; the rest overflow
+fn tabs_that_fit(widths: &[f32], available: f32) -> usize {
+    let mut used = 0.0;
+    widths
+        .iter()
+        .take_while(|&&width| {
+            used += width;
+            used <= available
+        })
+        .count()
+}
+
+// Active tab index once tab `removed` is gone and `remaining` tabs are left. The same tab
+// stays active if it wasn't the one removed (its index drops by one if an earlier tab went);
+// otherwise the tab that took its place is chosen, or the new last tab if it was the last.
+fn active_after_removal(active: usize, removed: usize, remaining: usize) -> usize {
+    let active = if removed < active { active - 1 } else { active };
+    active.min(remaining.saturating_sub(1))
+}
+
+// The tabs that go in the saved state, leaving out transient ones, and where the active
+// tab is among them (the nearest saved tab before it if it is transient itself)
+fn saved_tabs(tabs: &[Tab], active: usize) -> (Vec<Tab>, usize) {
+    let saved: Vec<Tab> = tabs.iter().filter(|tab| !tab.transient).cloned().collect();
+    let before_active = tabs.iter().take(active).filter(|tab| !tab.transient).count();
+    let active = if tabs.get(active).is_some_and(|tab| !tab.transient) {
+        before_active
+    } else {
+        before_active.saturating_sub(1)
+    };
+    (saved, active)
+}
+
+// Key an issuer's sidebar group is known by: issuers that differ only in case or
+// surrounding spaces share a group, whichever of them names it
+fn issuer_group_key(issuer: &str) -> String {
+    issuer.trim().to_lowercase()
+}
+
+// Index of the next (or previous) tab, wrapping around at either end
+fn cycle_index(current: usize, len: usize, forward: bool) -> usize {
+    if len == 0 {
+        return 0;
+    }
+
+    if forward {
+        (current + 1) % len
+    } else {
+        (current + len - 1) % len
+    }
+}
+
+// Where to open the window. iced doesn't expose monitor bounds, so this only
+// rejects positions that can't be on any screen (e.g. the -32000 Windows
+// reports for minimized windows) and centers the window instead.
+fn window_position(geometry: &WindowGeometry) -> window::Position {
+    match (geometry.x, geometry.y) {
+        (Some(x), Some(y))
+            if x.abs() <= MAX_WINDOW_COORDINATE
+                && y.abs() <= MAX_WINDOW_COORDINATE =>
+        {
+            window::Position::Specific(x, y)
+        }
+        _ => window::Position::Centered,
+    }
+}
+
+// Whether the gap between two ticks is too long to be a late tick, meaning the machine was
+// suspended (or the clock jumped) and every code should be regenerated right away
+fn woke_from_sleep(previous_tick: u64, now: u64, period: u64) -> bool {
+    now.saturating_sub(previous_tick) > safe_period(period).saturating_mul(2)
+}
+
+// Whether any tab has a code, revealed code or timed message that Tick needs to update
+fn needs_tick(tabs: &[Tab]) -> bool {
+    tabs.iter()
+        .any(|tab| !tab.token.is_empty() || tab.revealed || tab.message_secs_left > 0)
+}
+
+// Warning for a decoded key shorter than the length pad_key extends it to
+fn weak_key_hint(key_len: usize) -> Option<String> {
+    (key_len < PADDED_KEY_BYTES).then(|| {
+        format!("weak: secret is only {} bytes (padded to {})", key_len, PADDED_KEY_BYTES)
+    })
+}
+
+// Whether the tab's secret decodes, in its format and alphabet, to a key long enough to use
+fn secret_is_valid(tab: &Tab, strict: bool) -> bool {
+    tab.decode_key(strict).is_ok()
+}
+
+// Whether pasted text is an otpauth:// URI rather than a plain secret or name
+fn is_otpauth_uri(value: &str) -> bool {
+    value.trim_start().to_lowercase().starts_with("otpauth://")
+}
+
+// Parse an otpauth://totp/ or otpauth://hotp/ URI into its account settings, following
+// the Key Uri Format: otpauth://totp/Issuer:account?secret=...&issuer=...
+fn parse_otpauth_uri(uri: &str) -> Result<ParsedOtp, String> {
+    let decode = |value: &str| {
+        percent_decode_str(&value.replace('+', " "))
+            .decode_utf8()
+            .map(|decoded| decoded.into_owned())
+            .map_err(|_| "otpauth URI contains invalid UTF-8".to_string())
+    };
+
+    let scheme_len = "otpauth://".len();
+    if uri.len() < scheme_len || !uri[..scheme_len].eq_ignore_ascii_case("otpauth://") {
+        return Err("Not an otpauth:// URI".to_string());
+    }
+
+    let rest = &uri[scheme_len..];
+    let (otp_type, rest) = rest.split_once('/').unwrap_or((rest, ""));
+    let hotp = otp_type.eq_ignore_ascii_case("hotp");
+    if !hotp && !otp_type.eq_ignore_ascii_case("totp") {
+        return Err(format!("Unsupported OTP type \"{}\", only totp and hotp are supported", otp_type));
+    }
+
+    let (label, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let label = decode(label)?;
+
+    // The label is either "account" or "Issuer:account"
+    let (mut issuer, account) = match label.split_once(':') {
+        Some((issuer, account)) => (Some(issuer.trim().to_string()), account.trim().to_string()),
+        None => (None, label.trim().to_string()),
+    };
+
+    let mut secret = None;
+    let mut requested_digits = None;
+    let mut period = 30;
+    let mut algorithm = Algorithm::SHA1;
+    let mut mode = if hotp { OtpMode::Hotp } else { OtpMode::Totp };
+    let mut counter = 0;
+    let mut adjusted = Vec::new();
+
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let value = decode(value)?;
+
+        match key.to_lowercase().as_str() {
+            "secret" => secret = Some(value),
+            "issuer" => issuer = Some(value),
+            "digits" => {
+                let requested: u64 = value
+                    .parse()
+                    .map_err(|_| format!("Invalid digits value \"{}\" in otpauth URI", value))?;
+                requested_digits = Some(requested);
+            }
+            // A zero period would never produce a new code, so there's nothing sane to clamp to
+            "period" => {
+                period = value
+                    .parse()
+                    .ok()
+                    .filter(|period| *period > 0)
+                    .ok_or_else(|| format!("Invalid period \"{}\" in otpauth URI", value))?;
+            }
+            "algorithm" => {
+                algorithm = ALGORITHMS
+                    .into_iter()
+                    .find(|algorithm| algorithm.to_string().eq_ignore_ascii_case(&value))
+                    .ok_or_else(|| format!("Unsupported algorithm \"{}\" in otpauth URI", value))?;
+            }
+            // Only HOTP accounts have a counter; a stray one on a TOTP URI is ignored
+            "counter" if hotp => {
+                counter = value
+                    .parse()
+                    .map_err(|_| format!("Invalid counter \"{}\" in otpauth URI", value))?;
+            }
+            // Non-standard parameter used by Steam Guard exporters
+            "encoder" if !hotp && value.eq_ignore_ascii_case("steam") => mode = OtpMode::Steam,
+            // Unknown parameters (e.g. image) are ignored
+            _ => {}
+        }
+    }
+
+    let secret = secret
+        .filter(|secret| !secret.trim().is_empty())
+        .ok_or_else(|| "otpauth URI is missing the secret parameter".to_string())?;
+
+    // Out-of-range digit counts are clamped to the supported 6-8 rather than rejected. Steam
+    // codes are always 5 characters whatever the URI says, so their digits are left alone.
+    let digits = match requested_digits {
+        Some(requested) if mode != OtpMode::Steam => {
+            let (min, max) = (DIGIT_OPTIONS[0], DIGIT_OPTIONS[DIGIT_OPTIONS.len() - 1]);
+            let digits = requested.clamp(min.into(), max.into()) as u8;
+            if requested != u64::from(digits) {
+                adjusted.push(format!("digits={} is not supported, using {}", requested, digits));
+            }
+            digits
+        }
+        _ => 6,
+    };
+
+    Ok(ParsedOtp {
+        issuer: issuer.filter(|issuer| !issuer.is_empty()),
+        account,
+        secret,
+        digits,
+        period,
+        algorithm,
+        mode,
+        counter,
+        adjusted,
+    })
+}
+
+// Read a "name,secret" list, one account per line, in CSV form: quoted names may contain
+// commas and quotes, and a leading "name,secret" header row is skipped. Optional digits,
+// period and algorithm columns, as written by accounts_csv, follow the secret. Blank lines
+// and lines starting with # are skipped too. Returns the tabs for the good lines and a
+// message for each bad one.
+fn parse_csv_accounts(text: &str, strict: bool) -> (Vec<Tab>, Vec<String>) {
+    let mut tabs = Vec::new();
+    let mut errors = Vec::new();
+
+    for (record_idx, (number, fields)) in csv_records(text).into_iter().enumerate() {
+        let fields = match fields {
+            Ok(fields) => fields,
+            Err(e) => {
+                errors.push(format!("line {}: {}", number, e));
+                continue;
+            }
+        };
+        if record_idx == 0 && is_csv_header(&fields) {
+            continue;
+        }
+
+        let (name, secret) = match fields.as_slice() {
+            [name, secret, ..] if !secret.is_empty() => (name, secret),
+            _ => {
+                errors.push(format!("line {}: expected name,secret", number));
+                continue;
+            }
+        };
+
+        if let Err(e) = decode_secret(secret, SecretFormat::Base32, Base32Alphabet::Rfc4648, strict) {
+            errors.push(format!("line {}: {}", number, e));
+            continue;
+        }
+        let (digits, period, algorithm) = match csv_code_settings(&fields[2..]) {
+            Ok(settings) => settings,
+            Err(e) => {
+                errors.push(format!("line {}: {}", number, e));
+                continue;
+            }
+        };
+
+        tabs.push(Tab {
+            issuer: name.clone(),
+            secret_key: secret.clone(),
+            digits,
+            period,
+            algorithm,
+            editing_name: false,
+            ..Default::default()
+        });
+    }
+
+    (tabs, errors)
+}
+
+// Digits, period and algorithm from the columns after the secret; missing or empty ones
+// keep the usual 6 digits, 30 seconds and SHA1. Anything after the algorithm is ignored.
+fn csv_code_settings(columns: &[String]) -> Result<(u8, u64, Algorithm), String> {
+    let column = |idx: usize| columns.get(idx).map(String::as_str).filter(|value| !value.is_empty());
+
+    let digits = match column(0) {
+        Some(value) => value
+            .parse()
+            .ok()
+            .filter(|digits| DIGIT_OPTIONS.contains(digits))
+            .ok_or_else(|| format!("unsupported digits \"{}\"", value))?,
+        None => 6,
+    };
+    let period = match column(1) {
+        Some(value) => value
+            .parse()
+            .ok()
+            .filter(|period| *period > 0)
+            .ok_or_else(|| format!("invalid period \"{}\"", value))?,
+        None => 30,
+    };
+    let algorithm = match column(2) {
+        Some(value) => ALGORITHMS
+            .into_iter()
+            .find(|algorithm| algorithm.to_string().eq_ignore_ascii_case(value))
+            .ok_or_else(|| format!("unsupported algorithm \"{}\"", value))?,
+        None => Algorithm::SHA1,
+    };
+
+    Ok((digits, period, algorithm))
+}
+
+// Whether a CSV record is the "name,secret,..." header row rather than an account
+fn is_csv_header(fields: &[String]) -> bool {
+    matches!(fields, [name, secret, ..]
+        if name.eq_ignore_ascii_case("name") && secret.eq_ignore_ascii_case("secret"))
+}
+
+// Split CSV text into records, each with the line it starts on. Blank lines and lines
+// starting with # are skipped. A quoted field may run over several lines.
+fn csv_records(text: &str) -> Vec<(usize, Result<Vec<String>, String>)> {
+    let mut records = Vec::new();
+    let mut lines = text.lines().enumerate();
+
+    while let Some((idx, line)) = lines.next() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        // Only a quoted field that is still open at the end of the line carries on to the
+        // next one; a stray quote elsewhere is an error for this line alone
+        let mut record = line.to_string();
+        let fields = loop {
+            match parse_csv_record(&record) {
+                Ok(None) => match lines.next() {
+                    Some((_, next)) => {
+                        record.push('\n');
+                        record.push_str(next);
+                    }
+                    None => break csv_fields(&record),
+                },
+                Ok(Some(fields)) => break Ok(fields),
+                Err(e) => break Err(e),
+            }
+        };
+        records.push((idx + 1, fields));
+    }
+
+    records
+}
+
+// Fields of one CSV record as in RFC 4180: a field may be wrapped in double quotes, with
+// "" standing for a quote inside it. Unquoted fields are trimmed; quoted ones kept as is.
+fn csv_fields(record: &str) -> Result<Vec<String>, String> {
+    parse_csv_record(record)?.ok_or_else(|| "quoted field is never closed".to_string())
+}
+
+// csv_fields, but with None rather than an error when a quoted field is still open at the
+// end, so the record can continue on the next line
+fn parse_csv_record(record: &str) -> Result<Option<Vec<String>>, String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut was_quoted = false;
+    let mut chars = record.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            if c != '"' {
+                field.push(c);
+            } else if chars.next_if_eq(&'"').is_some() {
+                field.push('"');
+            } else {
+                in_quotes = false;
+            }
+            continue;
+        }
+
+        match c {
+            ',' => {
+                fields.push(if was_quoted { field } else { field.trim().to_string() });
+                field = String::new();
+                was_quoted = false;
+            }
+            '"' if !was_quoted && field.trim().is_empty() => {
+                field.clear();
+                in_quotes = true;
+                was_quoted = true;
+            }
+            '"' => return Err("unexpected quote inside a field".to_string()),
+            c if was_quoted && !c.is_whitespace() => {
+                return Err("unexpected text after a quoted field".to_string())
+            }
+            c if was_quoted => {}
+            c => field.push(c),
+        }
+    }
+
+    if in_quotes {
+        return Ok(None);
+    }
+    fields.push(if was_quoted { field } else { field.trim().to_string() });
+    Ok(Some(fields))
+}
+
+// Every tab with a usable secret as CSV with a header row, plus how many accounts it holds.
+// Secrets are re-encoded as unpadded RFC 4648 Base32 whatever format they were entered in.
+fn accounts_csv(tabs: &[Tab], strict: bool) -> (String, usize) {
+    let rows: Vec<String> = tabs.iter().filter_map(|tab| csv_row(tab, strict)).collect();
+    let count = rows.len();
+
+    let mut csv = String::from("name,secret,digits,period,algorithm\n");
+    for row in rows {
+        csv.push_str(&row);
+        csv.push('\n');
+    }
+    (csv, count)
+}
+
+// One CSV line for a tab, or None if its secret can't be decoded
+fn csv_row(tab: &Tab, strict: bool) -> Option<String> {
+    let secret = base32::encode(base32::Alphabet::RFC4648 { padding: false }, &tab.decode_key(strict).ok()?);
+    Some(format!(
+        "{},{},{},{},{}",
+        csv_field(&tab.display_name()),
+        secret,
+        tab.digits,
+        tab.period,
+        tab.algorithm
+    ))
+}
+
+// Quote a CSV field if it contains a comma, quote or line break, doubling inner quotes
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// Build the otpauth:// URI for a tab, with the secret re-encoded as unpadded Base32
+fn build_otpauth_uri(tab: &Tab, strict: bool) -> Result<String, String> {
+    let secret = base32::encode(
+        base32::Alphabet::RFC4648 { padding: false },
+        &tab.decode_key(strict).map_err(|e| e.to_string())?,
+    );
+
+    let issuer = tab.issuer.trim();
+    let issuer_param = if issuer.is_empty() {
+        String::new()
+    } else {
+        format!("&issuer={}", utf8_percent_encode(issuer, URI_COMPONENT))
+    };
+
+    Ok(match tab.mode {
+        OtpMode::Totp => format!(
+            "otpauth://totp/{}?secret={}&digits={}&period={}&algorithm={}{}",
+            otpauth_label(tab),
+            secret,
+            tab.digits,
+            tab.period,
+            tab.algorithm,
+            issuer_param,
+        ),
+        OtpMode::Hotp => format!(
+            "otpauth://hotp/{}?secret={}&digits={}&counter={}&algorithm={}{}",
+            otpauth_label(tab),
+            secret,
+            tab.digits,
+            tab.counter,
+            tab.algorithm,
+            issuer_param,
+        ),
+        OtpMode::Steam => format!(
+            "otpauth://totp/{}?secret={}&digits=5&period={}&algorithm=SHA1&encoder=steam{}",
+            otpauth_label(tab),
+            secret,
+            tab.period,
+            issuer_param,
+        ),
+    })
+}
+
+// URI label in the "Issuer:account" form. The colon is kept when there is no
+// account so the issuer isn't read back as the account name.
+fn otpauth_label(tab: &Tab) -> String {
+    let issuer = utf8_percent_encode(tab.issuer.trim(), URI_COMPONENT).to_string();
+    let account = utf8_percent_encode(tab.account.trim(), URI_COMPONENT).to_string();
+
+    if issuer.is_empty() {
+        account
+    } else {
+        format!("{}:{}", issuer, account)
+    }
+}
+
+// Replace the file at `path` with `contents`, readable only by the user since it may hold
+// plaintext secrets. The data goes to a temporary file that is then renamed over the old
+// one, so a crash mid-write leaves the previous contents intact.
+fn write_private_file(path: &Path, contents: &str) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let temp = path.with_extension("tmp");
+    // A leftover from an earlier crash may have other permissions, which open wouldn't change
+    let _ = fs::remove_file(&temp);
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let written = options.open(&temp).and_then(|mut file| {
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()
+    });
+    match written.and_then(|_| fs::rename(&temp, path)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let _ = fs::remove_file(&temp);
+            Err(e)
+        }
+    }
+}
+
+// Run slow synchronous work (key derivation, file and network I/O) on tokio's blocking
+// pool, so a Command future doesn't hold up the executor that drives the UI
+async fn run_blocking<T, F>(work: F) -> Result<T, String>
+where
+    F: FnOnce() -> Result<T, String> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(work)
+        .await
+        .map_err(|e| format!("Background task failed: {}", e))?
+}
+
+// A field read from a protobuf message: varints and length-delimited bytes are all
+// the migration format uses
+enum ProtoValue<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+}
+
+// Read a base-128 varint starting at `*pos`, advancing past it
+fn read_varint(data: &[u8], pos: &mut usize) -> Result<u64, String> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *data.get(*pos).ok_or("Migration data ends unexpectedly")?;
+        *pos += 1;
+        value |= u64::from(byte & 0x7F) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err("Migration data has an overlong number".to_string())
+}
+
+// Split a protobuf message into (field number, value) pairs, skipping fixed-width fields
+fn proto_fields(data: &[u8]) -> Result<Vec<(u64, ProtoValue<'_>)>, String> {
+    let mut fields = Vec::new();
+    let mut pos = 0;
+
+    while pos < data.len() {
+        let key = read_varint(data, &mut pos)?;
+        let value = match key & 0x07 {
+            0 => ProtoValue::Varint(read_varint(data, &mut pos)?),
+            2 => {
+                let len = read_varint(data, &mut pos)? as usize;
+                let bytes = pos
+                    .checked_add(len)
+                    .and_then(|end| data.get(pos..end))
+                    .ok_or("Migration data ends unexpectedly")?;
+                pos += len;
+                ProtoValue::Bytes(bytes)
+            }
+            1 => {
+                pos += 8;
+                continue;
+            }
+            5 => {
+                pos += 4;
+                continue;
+            }
+            wire_type => return Err(format!("Migration data has unknown field type {}", wire_type)),
+        };
+        fields.push((key >> 3, value));
+    }
+
+    if pos > data.len() {
+        return Err("Migration data ends unexpectedly".to_string());
+    }
+    Ok(fields)
+}
+
+// Decode a Google Authenticator `otpauth-migration://offline?data=...` export into tabs.
+// Returns the tabs along with the number of accounts skipped for using unsupported settings.
+fn parse_migration_payload(uri: &str) -> Result<(Vec<Tab>, usize), String> {
+    let (scheme, rest) = uri.split_once("://").ok_or("Not an otpauth-migration URI")?;
+    if !scheme.eq_ignore_ascii_case("otpauth-migration") {
+        return Err("Not an otpauth-migration URI".to_string());
+    }
+
+    let query = rest.split_once('?').map(|(_, query)| query).unwrap_or("");
+    let data = query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("data="))
+        .ok_or("otpauth-migration URI is missing the data parameter")?;
+    let data = percent_decode_str(data)
+        .decode_utf8()
+        .map_err(|_| "otpauth-migration data is not valid UTF-8".to_string())?
+        // A '+' that went through form decoding comes back as a space
+        .replace(' ', "+");
+    let payload = MIGRATION_BASE64
+        .decode(data)
+        .map_err(|_| "otpauth-migration data is not valid Base64".to_string())?;
+
+    let mut tabs = Vec::new();
+    let mut skipped = 0;
+
+    for (field, value) in proto_fields(&payload)? {
+        // Field 1 of the payload is the repeated list of accounts
+        let (1, ProtoValue::Bytes(entry)) = (field, value) else {
+            continue;
+        };
+        match migration_entry_tab(entry)? {
+            Some(tab) => tabs.push(tab),
+            None => skipped += 1,
+        }
+    }
+
+    Ok((tabs, skipped))
+}
+
+// Build a tab from one migration account, or None if it uses settings we can't generate
+fn migration_entry_tab(entry: &[u8]) -> Result<Option<Tab>, String> {
+    let mut secret = Vec::new();
+    let mut name = String::new();
+    let mut issuer = String::new();
+    let mut algorithm = 0;
+    let mut digits = 0;
+    let mut otp_type = 0;
+    let mut counter = 0;
+
+    for (field, value) in proto_fields(entry)? {
+        match (field, value) {
+            (1, ProtoValue::Bytes(bytes)) => secret = bytes.to_vec(),
+            (2, ProtoValue::Bytes(bytes)) => name = String::from_utf8_lossy(bytes).into_owned(),
+            (3, ProtoValue::Bytes(bytes)) => issuer = String::from_utf8_lossy(bytes).into_owned(),
+            (4, ProtoValue::Varint(value)) => algorithm = value,
+            (5, ProtoValue::Varint(value)) => digits = value,
+            (6, ProtoValue::Varint(value)) => otp_type = value,
+            (7, ProtoValue::Varint(value)) => counter = value,
+            _ => {}
+        }
+    }
+
+    // Enum values from Google's migration.proto; 0 means unspecified and gets the usual default
+    let algorithm = match algorithm {
+        0 | 1 => Algorithm::SHA1,
+        2 => Algorithm::SHA256,
+        3 => Algorithm::SHA512,
+        _ => return Ok(None),
+    };
+    let digits = match digits {
+        0 | 1 => 6,
+        2 => 8,
+        _ => return Ok(None),
+    };
+    let mode = match otp_type {
+        1 => OtpMode::Hotp,
+        0 | 2 => OtpMode::Totp,
+        _ => return Ok(None),
+    };
+    if secret.is_empty() {
+        return Ok(None);
+    }
+
+    // Names are often stored as "Issuer:account"
+    let account = match name.split_once(':') {
+        Some((prefix, account)) if issuer.is_empty() || prefix.trim() == issuer.trim() => {
+            if issuer.is_empty() {
+                issuer = prefix.trim().to_string();
+            }
+            account.trim().to_string()
+        }
+        _ => name.trim().to_string(),
+    };
+
+    Ok(Some(Tab {
+        issuer,
+        account,
+        secret_key: base32::encode(base32::Alphabet::RFC4648 { padding: false }, &secret),
+        algorithm,
+        digits,
+        mode,
+        counter,
+        editing_name: false,
+        ..Default::default()
+    }))
+}
+
+// Unencrypted Aegis Authenticator backup, keeping only the fields we import
+#[derive(Deserialize)]
+struct AegisBackup {
+    db: AegisDb,
+}
+
+// An encrypted backup stores the database as a Base64 string instead of an object
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AegisDb {
+    Plain { entries: Vec<AegisEntry> },
+    Encrypted(#[allow(dead_code)] String),
+}
+
+#[derive(Deserialize)]
+struct AegisEntry {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    issuer: String,
+    info: AegisInfo,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct AegisInfo {
+    secret: String,
+    algo: String,
+    digits: u8,
+    period: u64,
+    counter: u64,
+}
+
+impl Default for AegisInfo {
+    fn default() -> Self {
+        Self {
+            secret: String::new(),
+            algo: String::from("SHA1"),
+            digits: 6,
+            period: 30,
+            counter: 0,
+        }
+    }
+}
+
+// Turn an Aegis JSON backup into tabs. Returns the tabs along with the number of
+// entries skipped for using a type or setting we can't generate.
+fn parse_aegis_backup(json: &str) -> Result<(Vec<Tab>, usize), String> {
+    let backup: AegisBackup =
+        serde_json::from_str(json).map_err(|e| format!("Not a valid Aegis backup: {}", e))?;
+    let entries = match backup.db {
+        AegisDb::Plain { entries } => entries,
+        AegisDb::Encrypted(_) => {
+            return Err("Encrypted Aegis backups can't be imported; export without encryption".to_string())
+        }
+    };
+
+    let mut tabs = Vec::new();
+    let mut skipped = 0;
+
+    for entry in entries {
+        let mode = match entry.kind.as_str() {
+            "totp" => OtpMode::Totp,
+            "hotp" => OtpMode::Hotp,
+            "steam" => OtpMode::Steam,
+            _ => {
+                skipped += 1;
+                continue;
+            }
+        };
+        let Some(algorithm) = ALGORITHMS
+            .into_iter()
+            .find(|algorithm| algorithm.to_string().eq_ignore_ascii_case(&entry.info.algo))
+        else {
+            skipped += 1;
+            continue;
+        };
+        // Steam entries carry 5 digits, which the Steam mode handles on its own
+        if (mode != OtpMode::Steam && !DIGIT_OPTIONS.contains(&entry.info.digits))
+            || entry.info.secret.trim().is_empty()
+        {
+            skipped += 1;
+            continue;
+        }
+
+        tabs.push(Tab {
+            issuer: entry.issuer,
+            account: entry.name,
+            secret_key: entry.info.secret,
+            algorithm,
+            digits: if mode == OtpMode::Steam { 6 } else { entry.info.digits },
+            period: entry.info.period.max(1),
+            mode,
+            counter: entry.info.counter,
+            editing_name: false,
+            ..Default::default()
+        });
+    }
+
+    Ok((tabs, skipped))
+}
+
+impl VaultKey {
+    // Derive a key for a new master password with a fresh random salt
+    fn new(password: &str) -> Self {
+        let mut salt = vec![0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        Self {
+            key: derive_key(password, &salt),
+            salt,
+        }
+    }
+
+    // Derive the key for an existing vault using its stored salt
+    fn from_vault(password: &str, vault: &Vault) -> Result<Self, String> {
+        let salt = decode_vault_field(&vault.salt)?;
+        Ok(Self {
+            key: derive_key(password, &salt),
+            salt,
+        })
+    }
+}
+
+// Derive the key for `vault` from `password` and decrypt its tabs with it. Slow on purpose
+// (it runs the KDF), so call it through run_blocking.
+fn unlock_vault(password: &str, vault: &Vault) -> Result<(VaultKey, Vec<Tab>), String> {
+    let key = VaultKey::from_vault(password, vault)?;
+    let tabs = decrypt_tabs(&key, vault)?;
+    Ok((key, tabs))
+}
+
+// Stretch the master password into a 256-bit cipher key
+fn derive_key(password: &str, salt: &[u8]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), salt, KDF_ROUNDS, &mut key);
+    key
+}
+
+fn decode_vault_field(value: &str) -> Result<Vec<u8>, String> {
+    base32::decode(base32::Alphabet::RFC4648 { padding: false }, value)
+        .ok_or_else(|| "Saved data is corrupted".to_string())
+}
+
+// Encrypt the tab list with ChaCha20-Poly1305 under a fresh nonce
+fn encrypt_tabs(key: &VaultKey, tabs: &[Tab]) -> Result<Vault, String> {
+    let plaintext = serde_json::to_vec(tabs).map_err(|e| format!("Failed to serialize tabs: {}", e))?;
+    let cipher = ChaCha20Poly1305::new(&key.key.into());
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext.as_slice())
+        .map_err(|_| "Failed to encrypt tabs".to_string())?;
+
+    let encode = |bytes: &[u8]| base32::encode(base32::Alphabet::RFC4648 { padding: false }, bytes);
+    Ok(Vault {
+        salt: encode(&key.salt),
+        nonce: encode(&nonce),
+        ciphertext: encode(&ciphertext),
+    })
+}
+
+// Decrypt the tab list; a wrong password fails the AEAD tag check
+fn decrypt_tabs(key: &VaultKey, vault: &Vault) -> Result<Vec<Tab>, String> {
+    let nonce = decode_vault_field(&vault.nonce)?;
+    let ciphertext = decode_vault_field(&vault.ciphertext)?;
+    if nonce.len() != 12 {
+        return Err("Saved data is corrupted".to_string());
+    }
+
+    let cipher = ChaCha20Poly1305::new(&key.key.into());
+    let plaintext = cipher
+        .decrypt(nonce.as_slice().into(), ciphertext.as_slice())
+        .map_err(|_| "Wrong password or corrupted data".to_string())?;
+
+    serde_json::from_slice(&plaintext).map_err(|_| "Saved data is corrupted".to_string())
+}
+
+// Serialize and encrypt every tab into a backup file under a fresh key for `passphrase`
+fn encrypt_backup(passphrase: &str, tabs: &[Tab]) -> Result<String, String> {
+    let backup = BackupFile {
+        format: BACKUP_FORMAT.to_string(),
+        version: BACKUP_VERSION,
+        vault: encrypt_tabs(&VaultKey::new(passphrase), tabs)?,
+    };
+    serde_json::to_string_pretty(&backup).map_err(|e| format!("Failed to write backup: {}", e))
+}
+
+// Read the tabs back out of a backup file
+fn decrypt_backup(passphrase: &str, json: &str) -> Result<Vec<Tab>, String> {
+    let backup: BackupFile =
+        serde_json::from_str(json).map_err(|_| "Not a ro-topt backup file".to_string())?;
+    if backup.format != BACKUP_FORMAT {
+        return Err("Not a ro-topt backup file".to_string());
+    }
+    if backup.version > BACKUP_VERSION {
+        return Err(format!(
+            "Backup was made by a newer version of ro-topt (format {})",
+            backup.version
+        ));
+    }
+
+    let key = VaultKey::from_vault(passphrase, &backup.vault)?;
+    decrypt_tabs(&key, &backup.vault)
+}
+
+// How close the displayed code is to expiring
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Urgency {
+    Normal,
+    Warning,
+    Critical,
+}
+
+// Current Unix time in seconds, or an error if the system clock can't be right
+fn current_unix_time() -> Result<u64, String> {
+    unix_time_at(SystemTime::now())
+}
+
+// Seconds since the Unix epoch at `time`, rejecting times before 1970 and the other
+// implausibly early ones below MIN_PLAUSIBLE_UNIX_TIME
+fn unix_time_at(time: SystemTime) -> Result<u64, String> {
+    time.duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|elapsed| elapsed.as_secs())
+        .filter(|&secs| secs >= MIN_PLAUSIBLE_UNIX_TIME)
+        .ok_or_else(|| "System clock appears incorrect, fix the date and time to get codes".to_string())
+}
+
+// Current Unix time in seconds shifted by `offset`, clamped at the ends of the u64 range.
+// The offset is the user's choice, so only the system clock itself is checked.
+fn effective_time(offset: i64) -> Result<u64, String> {
+    current_unix_time().map(|now| now.saturating_add_signed(offset))
+}
+
+// effective_time for countdowns and other displays, which show 1970 on a broken clock while
+// generate_token reports the error
+fn effective_now(offset: i64) -> u64 {
+    effective_time(offset).unwrap_or_default()
+}
+
+// Current Unix time in fractional seconds, for NTP round-trip timing
+fn unix_time_secs_f64() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
+
+// Ask an SNTP server how far the local clock is off, in whole seconds.
+// Blocks for up to a few seconds, so it is only called through run_blocking.
+fn query_ntp_offset(host: &str) -> Result<i64, String> {
+    let socket = UdpSocket::bind("0.0.0.0:0").map_err(|e| format!("Could not open socket: {}", e))?;
+    socket
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .map_err(|e| e.to_string())?;
+    socket
+        .connect((host, 123))
+        .map_err(|e| format!("Could not reach {}: {}", host, e))?;
+
+    // LI = 0, version = 3, mode = 3 (client); the rest of the request is zero
+    let mut request = [0u8; 48];
+    request[0] = 0x1B;
+
+    let sent = unix_time_secs_f64();
+    socket
+        .send(&request)
+        .map_err(|e| format!("Could not reach {}: {}", host, e))?;
+
+    let mut response = [0u8; 48];
+    let len = socket
+        .recv(&mut response)
+        .map_err(|e| format!("No response from {}: {}", host, e))?;
+    let received = unix_time_secs_f64();
+
+    ntp_offset(&response[..len], sent, received)
+}
+
+// Clock offset from an SNTP reply, given the local Unix times the request was
+// sent and the reply received. Uses the standard ((t1 - t0) + (t2 - t3)) / 2.
+fn ntp_offset(packet: &[u8], sent: f64, received: f64) -> Result<i64, String> {
+    if packet.len() < 48 {
+        return Err("NTP response is too short".to_string());
+    }
+    if packet[0] & 0x07 != 4 {
+        return Err("NTP response is not a server reply".to_string());
+    }
+    // Stratum 0 is a "kiss-o'-death" telling the client to back off
+    if packet[1] == 0 {
+        return Err("NTP server refused the request".to_string());
+    }
+
+    let server_received = ntp_timestamp(&packet[32..40]);
+    let server_sent = ntp_timestamp(&packet[40..48]);
+    if server_sent == 0.0 {
+        return Err("NTP response has no transmit time".to_string());
+    }
+
+    let offset = ((server_received - sent) + (server_sent - received)) / 2.0;
+    Ok(offset.round() as i64)
+}
+
+// Convert a 64-bit NTP timestamp to Unix seconds (0.0 if unset). Seconds values
+// with the top bit clear are read as the era starting in 2036.
+fn ntp_timestamp(bytes: &[u8]) -> f64 {
+    let secs = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    let fraction = u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+    if secs == 0 && fraction == 0 {
+        return 0.0;
+    }
+
+    let secs = if secs & 0x8000_0000 == 0 {
+        secs as f64 + 4_294_967_296.0
+    } else {
+        secs as f64
+    };
+    secs - NTP_UNIX_EPOCH_DELTA + fraction as f64 / 4_294_967_296.0
+}
+
+// Whether the countdown passed `threshold` seconds on this tick. Only the tick that goes
+// from above the threshold to at or below it counts, so each period notifies once; a new
+// code resets the countdown above the threshold again.
+fn crossed_expiry_threshold(previous_remaining: u64, remaining: u64, threshold: u64) -> bool {
+    previous_remaining > threshold && remaining <= threshold
+}
+
+// Desktop notification that a tab's code is about to change. Blocks while the desktop
+// takes it, so it is sent through run_blocking. Failures (e.g. no notification daemon)
+// are ignored by the caller; the countdown is still on screen.
+fn notify_expiry(name: &str, remaining: u64) -> Result<(), String> {
+    notify_rust::Notification::new()
+        .summary("TOTP Token Generator")
+        .body(&format!("Code for {} expires in {}s", name, remaining))
+        .show()
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Whether a regeneration at `now` came after the first second of its time step
+fn step_boundary_missed(now: u64, period: u64) -> bool {
+    now % safe_period(period) != 0
+}
+
+// RFC 6238 time step for `now`: floor(unix_time / period)
+fn current_counter(now: u64, period: u64) -> u64 {
+    now / safe_period(period)
+}
+
+// Period to divide by, with 0 (e.g. from a hand-edited or imported file) treated as 1
+fn safe_period(period: u64) -> u64 {
+    period.max(1)
+}
+
+// Position of the countdown bar from 0.0 to 1.0: the share of the period left, or for a
+// filling bar the share already elapsed
+fn progress_value(remaining: u64, period: u64, fill: ProgressFill) -> f32 {
+    let period = safe_period(period);
+    let remaining = remaining.min(period) as f32 / period as f32;
+    match fill {
+        ProgressFill::Remaining => remaining,
+        ProgressFill::Elapsed => 1.0 - remaining,
+    }
+}
+
+// Angle of the countdown ring's arc in radians: a full circle at the start of the
+// period, shrinking to nothing as it runs out
+fn countdown_sweep_angle(time_remaining: u64, period: u64) -> f32 {
+    if period == 0 {
+        return 0.0;
+    }
+    let fraction = time_remaining.min(period) as f32 / period as f32;
+    fraction * std::f32::consts::TAU
+}
+
+// Number of lit dots and total dots in the dots countdown: one dot per COUNTDOWN_DOT_SECS
+// seconds of the period (at most COUNTDOWN_MAX_DOTS), all lit at the start of the period
+// and the last one going out only when the code expires
+fn countdown_lit_dots(time_remaining: u64, period: u64) -> (u64, u64) {
+    let period = safe_period(period);
+    let total = period.div_ceil(COUNTDOWN_DOT_SECS).clamp(1, COUNTDOWN_MAX_DOTS);
+    let lit = (time_remaining.min(period) * total).div_ceil(period);
+    (lit, total)
+}
+
+// Tab bar order for "recently used": pinned tabs first, then the most recently used, with
+// never-used tabs last. Equal tabs compare equal so a stable sort keeps their manual order.
+fn compare_by_recency(a: &Tab, b: &Tab) -> Ordering {
+    b.pinned
+        .cmp(&a.pinned)
+        .then_with(|| b.shown_last_used.cmp(&a.shown_last_used))
+}
+
+// Keep the text scale within the supported range, rounded to the step size so repeated
+// steps don't drift (e.g. 1.2000001)
+fn clamp_ui_scale(scale: f32) -> f32 {
+    if !scale.is_finite() {
+        return 1.0;
+    }
+    ((scale / UI_SCALE_STEP).round() * UI_SCALE_STEP).clamp(MIN_UI_SCALE, MAX_UI_SCALE)
+}
+
+fn countdown_urgency(time_remaining: u64) -> Urgency {
+    if time_remaining < COUNTDOWN_CRITICAL_SECS {
+        Urgency::Critical
+    } else if time_remaining < COUNTDOWN_WARNING_SECS {
+        Urgency::Warning
+    } else {
+        Urgency::Normal
+    }
+}
+
+// Format a token with spaces for better readability. This is the one place codes are
+// grouped, for the display, the copy form and the masked form alike: 6 digits become
+// "123 456", 7 and 8 digits "1234 567" and "1234 5678", and anything else (e.g. Steam's
+// 5-character codes) is left as it is.
+fn format_token(token: &str) -> String {
+    match token.len() {
+        6 => format!("{} {}", &token[..3], &token[3..]),
+        // Put the longer group first, e.g. "1234567" becomes "1234 567"
+        7 | 8 => format!("{} {}", &token[..4], &token[4..]),
+        _ => token.to_string(),
+    }
+}
+
+// Time left on a code: plain seconds up to a minute, then m:ss (e.g. "1:15")
+fn format_remaining(secs: u64) -> String {
+    if secs <= 60 {
+        format!("{} second{}", secs, if secs == 1 { "" } else { "s" })
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+// How "Copy and hide" puts the window away once the copy is done: to the tray if there is
+// one to come back from, minimized otherwise, and not at all if the copy failed so the
+// error stays in view
+fn hide_after_copy(copied: bool, tray_available: bool) -> Option<HideAction> {
+    match (copied, tray_available) {
+        (false, _) => None,
+        (true, true) => Some(HideAction::ToTray),
+        (true, false) => Some(HideAction::Minimize),
+    }
+}
+
+// A code as it goes on the clipboard: grouped like the display, or as one run of digits
+fn copied_token(token: &str, grouped: bool) -> String {
+    let compact = token.replace(' ', "");
+    if grouped {
+        format_token(&compact)
+    } else {
+        compact
+    }
+}
+
+// How many characters of a typed secret are likely accidental and skipped by decoding:
+// whitespace at either end, plus non-ASCII whitespace (e.g. no-break spaces) and hidden
+// characters anywhere. Plain spaces and dashes inside the key are grouping, not counted.
+fn ignored_secret_chars(input: &str) -> usize {
+    let inner = input.trim_matches(|c: char| c.is_whitespace() || is_hidden_char(c));
+    let edges = input.chars().count() - inner.chars().count();
+    let hidden = inner
+        .chars()
+        .filter(|&c| is_hidden_char(c) || (c.is_whitespace() && !c.is_ascii()))
+        .count();
+    edges + hidden
+}
+
+// Whether a regeneration replaced one shown code with another, as opposed to a code
+// appearing for the first time or going away after an error
+fn code_rolled_over(previous: &str, current: &str) -> bool {
+    !previous.is_empty() && !current.is_empty() && previous != current
+}
+
+// Whether a tab's freshly generated code should go to the clipboard: only for tabs with
+// auto-copy on, and only for the active tab unless background tabs are allowed too
+fn should_auto_copy(tab: &Tab, is_active: bool, background_allowed: bool) -> bool {
+    tab.auto_copy && (is_active || background_allowed)
+}
+
+// Whether the active code is shown as selectable text for copying by hand: only once the
+// clipboard turned out to be unavailable, and never for a masked code
+fn selectable_token_fallback(clipboard_unavailable: bool, masked: bool) -> bool {
+    clipboard_unavailable && !masked
+}
+
+// Whether a tab's codes are masked: hidden codes stay masked until revealed, and every
+// code is masked while the window is obscured, revealed or not
+fn code_masked(hide_tokens: bool, revealed: bool, obscured: bool) -> bool {
+    (hide_tokens && !revealed) || obscured
+}
+
+// Whether every code should be masked right now, regardless of per-tab reveals
+fn tokens_obscured(hide_when_unfocused: bool, focused: bool) -> bool {
+    hide_when_unfocused && !focused
+}
+
+// Hidden form of a code with the same grouping as format_token, e.g. "••• •••"
+fn mask_token(token: &str) -> String {
+    format_token(token)
+        .chars()
+        .map(|c| if c == ' ' { ' ' } else { '•' })
+        .collect()
+}
+
+// Code a tab shows at `timestamp` (HOTP tabs use their counter instead)
+fn token_for_timestamp(tab: &Tab, timestamp: u64, strict: bool) -> Result<String, String> {
+    // Decode the key, extending it to the 128-bit minimum if needed
+    let decoded_key = pad_key(tab.decode_key(strict).map_err(|e| e.to_string())?);
+    token_with_key(tab, &decoded_key, timestamp)
+}
+
+// Code a tab shows at `timestamp`, from its already decoded and padded key
+fn token_with_key(tab: &Tab, decoded_key: &[u8], timestamp: u64) -> Result<String, String> {
+    match tab.mode {
+        OtpMode::Totp => {
+            generate_totp_code(decoded_key, timestamp, safe_period(tab.period), tab.digits, tab.algorithm)
+        }
+        OtpMode::Hotp => Ok(generate_hotp_code(decoded_key, tab.counter, tab.digits, tab.algorithm)),
+        OtpMode::Steam => Ok(generate_steam_code(decoded_key, timestamp, safe_period(tab.period))),
+    }
+}
+
+// Timestamps one step before `now`, `now` itself and one step after, i.e. the codes a
+// server with a ±1 step window accepts
+fn grace_window_timestamps(now: u64, period: u64) -> [u64; 3] {
+    let period = safe_period(period);
+    [now.saturating_sub(period), now, now.saturating_add(period)]
+}
+
+// Instant typed into the "code at another time" panel: a Unix timestamp, an offset from
+// `now` such as "+90" or "-30", or a UTC date and time like "2024-01-31 12:00:00"
+fn parse_preview_time(input: &str, now: u64) -> Option<u64> {
+    let input = input.trim();
+    if let Some(secs) = input.strip_prefix('+') {
+        return now.checked_add(secs.trim().parse().ok()?);
+    }
+    if let Some(secs) = input.strip_prefix('-') {
+        return now.checked_sub(secs.trim().parse().ok()?);
+    }
+    if let Ok(timestamp) = input.parse() {
+        return Some(timestamp);
+    }
+    chrono::NaiveDateTime::parse_from_str(input, "%Y-%m-%d %H:%M:%S")
+        .ok()
+        .and_then(|time| u64::try_from(time.and_utc().timestamp()).ok())
+}
+
+// Status bar text: the effective time used for generation and the offset applied to the
+// system clock, e.g. "Code time 2024-01-31 12:00:03 UTC · clock offset +3s"
+fn status_line(now: u64, offset: i64) -> String {
+    let offset = if offset == 0 {
+        "no clock offset".to_string()
+    } else {
+        format!("clock offset {:+}s", offset)
+    };
+    format!("Code time {} · {}", format_utc_time(now), offset)
+}
+
+// Unix time as "2024-01-31 12:00:00 UTC"
+fn format_utc_time(timestamp: u64) -> String {
+    i64::try_from(timestamp)
+        .ok()
+        .and_then(|timestamp| chrono::DateTime::from_timestamp(timestamp, 0))
+        .map(|time| time.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+        .unwrap_or_else(|| "out of range".to_string())
+}
+
+// Steam Guard code for `time`: HMAC-SHA1 over the time step, truncated as in
+// RFC 4226 and then spelled out in Steam's 26-character alphabet
+fn generate_steam_code(secret: &[u8], time: u64, period: u64) -> String {
+    TOTP::new_unchecked(Algorithm::Steam, 5, 1, period, secret.to_vec()).generate(time)
+}
+
+// RFC 4226 HOTP code for `counter`. A one-second TOTP step makes the time
+// value the moving factor, so totp-rs computes the HOTP value directly.
+fn generate_hotp_code(secret: &[u8], counter: u64, digits: u8, algorithm: Algorithm) -> String {
+    TOTP::new_unchecked(algorithm, digits as usize, 0, 1, secret.to_vec()).generate(counter)
+}
+
+// Read the text of the single QR code in a PNG or JPEG image
+fn qr_uri_from_image(bytes: &[u8]) -> Result<String, String> {
+    let luma = ::image::load_from_memory(bytes)
+        .map_err(|_| "not a supported image (use PNG or JPEG)".to_string())?
+        .to_luma8();
+
+    let mut prepared = rqrr::PreparedImage::prepare_from_greyscale(
+        luma.width() as usize,
+        luma.height() as usize,
+        |x, y| luma.get_pixel(x as u32, y as u32)[0],
+    );
+    let grids = prepared.detect_grids();
+
+    match grids.as_slice() {
+        [] => Err("no QR code found in the image".to_string()),
+        [grid] => grid
+            .decode()
+            .map(|(_, content)| content.trim().to_string())
+            .map_err(|e| format!("could not decode the QR code: {}", e)),
+        _ => Err(format!(
+            "image contains {} QR codes, crop it to just one",
+            grids.len()
+        )),
+    }
+}
+
+// Domain a tab's favicon is fetched from, which is also its cache key: an issuer that
+// looks like a host ("github.com", "https://www.github.com/login") is used as is, and a
+// plain name like "GitHub" or "Google Cloud" becomes "<first word>.com"
+fn favicon_domain(issuer: &str) -> Option<String> {
+    let issuer = issuer.trim().to_lowercase();
+    let host = issuer
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .split(['/', '?', '#', ':'])
+        .next()
+        .unwrap_or("")
+        .trim_start_matches("www.");
+
+    let is_label = |label: &str| {
+        !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    };
+    if host.contains('.') {
+        return host.split('.').all(is_label).then(|| host.to_string());
+    }
+
+    let name = host.split_whitespace().next().unwrap_or("");
+    is_label(name).then(|| format!("{}.com", name))
+}
+
+#[cfg(feature = "favicons")]
+fn favicon_url(domain: &str) -> String {
+    format!("https://{}/favicon.ico", domain)
+}
+
+// Download and decode a favicon; any network or image error just means no icon
+#[cfg(feature = "favicons")]
+fn fetch_favicon(domain: &str) -> Option<image::Handle> {
+    use std::io::Read;
+
+    let response = ureq::get(&favicon_url(domain))
+        .timeout(Duration::from_secs(FAVICON_TIMEOUT_SECS))
+        .call()
+        .ok()?;
+    let mut bytes = Vec::new();
+    response
+        .into_reader()
+        .take(FAVICON_MAX_BYTES)
+        .read_to_end(&mut bytes)
+        .ok()?;
+
+    let icon = ::image::load_from_memory(&bytes)
+        .ok()?
+        .thumbnail(FAVICON_SIZE, FAVICON_SIZE)
+        .to_rgba8();
+    Some(image::Handle::from_pixels(icon.width(), icon.height(), icon.into_raw()))
+}
+
+// Built without the "favicons" feature: tabs just don't get icons
+#[cfg(not(feature = "favicons"))]
+fn fetch_favicon(_domain: &str) -> Option<image::Handle> {
+    None
+}
+
+// Render an otpauth URI as a QR code image
+fn qr_code_handle(uri: &str) -> Result<image::Handle, String> {
+    let rendered = qr_code_image(uri)?;
+    Ok(image::Handle::from_pixels(
+        rendered.width(),
+        rendered.height(),
+        rendered.into_raw(),
+    ))
+}
+
+// Pixels of the QR code for `uri`, at least 220 pixels across
+fn qr_code_image(uri: &str) -> Result<::image::RgbaImage, String> {
+    let code = QrCode::new(uri.as_bytes()).map_err(|e| format!("Failed to create QR code: {}", e))?;
+    Ok(code
+        .render::<::image::Rgba<u8>>()
+        .min_dimensions(220, 220)
+        .build())
+}
+
+// Custom styles for message containers, buttons and tabs
+struct SuccessMessageStyle;
+struct ErrorMessageStyle;
+struct BlueButtonStyle;
+struct ActiveTabButtonStyle(Option<TabColor>);
+struct InactiveTabButtonStyle(Option<TabColor>);
+struct TabColorSwatchStyle {
+    color: Option<TabColor>,
+    selected: bool,
+}
+struct CountdownBarStyle(Urgency);
+struct TokenButtonStyle;
+struct RolloverFlashStyle;
+
+impl iced::widget::container::StyleSheet for SuccessMessageStyle {
+    type Style = iced::Theme;
+
+    fn appearance(&self, style: &Self::Style) -> iced::widget::container::Appearance {
+        let success = style.palette().success;
+
+        iced::widget::container::Appearance {
+            background: Some(iced::Background::Color(iced::Color { a: 0.1, ..success })),
+            border_radius: 4.0,
+            border_width: 1.0,
+            border_color: success,
+            ..Default::default()
+        }
+    }
+}
+
+impl iced::widget::container::StyleSheet for ErrorMessageStyle {
+    type Style = iced::Theme;
+
+    fn appearance(&self, style: &Self::Style) -> iced::widget::container::Appearance {
+        let danger = style.palette().danger;
+
+        iced::widget::container::Appearance {
+            background: Some(iced::Background::Color(iced::Color { a: 0.1, ..danger })),
+            border_radius: 4.0,
+            border_width: 1.0,
+            border_color: danger,
+            ..Default::default()
+        }
+    }
+}
+
+impl iced::widget::container::StyleSheet for RolloverFlashStyle {
+    type Style = iced::Theme;
+
+    fn appearance(&self, style: &Self::Style) -> iced::widget::container::Appearance {
+        let primary = style.palette().primary;
+
+        iced::widget::container::Appearance {
+            background: Some(iced::Background::Color(iced::Color { a: 0.2, ..primary })),
+            border_radius: 6.0,
+            border_width: 2.0,
+            border_color: primary,
+            ..Default::default()
+        }
+    }
+}
+
+impl iced::widget::button::StyleSheet for BlueButtonStyle {
+    type Style = iced::Theme;
+
+    fn active(&self, style: &Self::Style) -> iced::widget::button::Appearance {
+        let primary = style.extended_palette().primary.base;
+
+        iced::widget::button::Appearance {
+            background: Some(iced::Background::Color(primary.color)),
+            border_radius: 4.0,
+            text_color: primary.text,
+            ..Default::default()
+        }
+    }
+    
+    fn hovered(&self, style: &Self::Style) -> iced::widget::button::Appearance {
+        let active = self.active(style);
+        
+        iced::widget::button::Appearance {
+            background: Some(iced::Background::Color(style.extended_palette().primary.strong.color)),
+            ..active
+        }
+    }
+}
+
+impl iced::widget::button::StyleSheet for TokenButtonStyle {
+    type Style = iced::Theme;
+
+    fn active(&self, style: &Self::Style) -> iced::widget::button::Appearance {
+        iced::widget::button::Appearance {
+            background: None,
+            border_radius: 6.0,
+            text_color: style.palette().text,
+            ..Default::default()
+        }
+    }
+
+    fn hovered(&self, style: &Self::Style) -> iced::widget::button::Appearance {
+        let active = self.active(style);
+        let background = style.extended_palette().background;
+
+        iced::widget::button::Appearance {
+            background: Some(iced::Background::Color(background.weak.color)),
+            ..active
+        }
+    }
+
+    fn pressed(&self, style: &Self::Style) -> iced::widget::button::Appearance {
+        let active = self.active(style);
+        let background = style.extended_palette().background;
+
+        iced::widget::button::Appearance {
+            background: Some(iced::Background::Color(background.strong.color)),
+            ..active
+        }
+    }
+}
+
+impl iced::widget::button::StyleSheet for ActiveTabButtonStyle {
+    type Style = iced::Theme;
+
+    fn active(&self, style: &Self::Style) -> iced::widget::button::Appearance {
+        let background = style.extended_palette().background;
+
+        // A labelled tab is filled with its color when selected
+        if let Some(color) = self.0.map(TabColor::color) {
+            return iced::widget::button::Appearance {
+                background: Some(iced::Background::Color(color)),
+                border_radius: 6.0,
+                border_width: 1.0,
+                border_color: background.strong.color,
+                shadow_offset: iced::Vector::new(0.0, 0.0),
+                text_color: iced::Color::WHITE,
+            };
+        }
+
+        iced::widget::button::Appearance {
+            background: Some(iced::Background::Color(background.weak.color)),
+            border_radius: 6.0,
+            border_width: 1.0,
+            border_color: background.strong.color,
+            shadow_offset: iced::Vector::new(0.0, 0.0),
+            text_color: background.weak.text,
+            ..Default::default()
+        }
+    }
+    
+    fn hovered(&self, style: &Self::Style) -> iced::widget::button::Appearance {
+        let active = self.active(style);
+        let background = match self.0.map(TabColor::color) {
+            Some(color) => iced::Color { a: 0.85, ..color },
+            None => style.extended_palette().background.base.color,
+        };
+        
+        iced::widget::button::Appearance {
+            background: Some(iced::Background::Color(background)),
+            ..active
+        }
+    }
+}
+
+impl iced::widget::button::StyleSheet for InactiveTabButtonStyle {
+    type Style = iced::Theme;
+
+    fn active(&self, style: &Self::Style) -> iced::widget::button::Appearance {
+        let background = style.extended_palette().background;
+
+        // In high contrast, unselected tabs are outlined on the plain background instead
+        if is_high_contrast(style) {
+            let palette = style.palette();
+            return iced::widget::button::Appearance {
+                background: Some(iced::Background::Color(palette.background)),
+                border_radius: 6.0,
+                border_width: 2.0,
+                border_color: self.0.map(TabColor::color).unwrap_or(palette.text),
+                shadow_offset: iced::Vector::new(0.0, 0.0),
+                text_color: palette.text,
+            };
+        }
+
+        // Unselected labelled tabs show a faded version of their color
+        let (fill, border) = match self.0.map(TabColor::color) {
+            Some(color) => (iced::Color { a: 0.35, ..color }, color),
+            None => (background.strong.color, background.strong.color),
+        };
+
+        iced::widget::button::Appearance {
+            background: Some(iced::Background::Color(fill)),
+            border_radius: 6.0,
+            border_width: 1.0,
+            border_color: border,
+            shadow_offset: iced::Vector::new(0.0, 0.0),
+            text_color: iced::Color { a: 0.7, ..background.strong.text },
+            ..Default::default()
+        }
+    }
+    
+    fn hovered(&self, style: &Self::Style) -> iced::widget::button::Appearance {
+        let active = self.active(style);
+        if is_high_contrast(style) {
+            return iced::widget::button::Appearance { border_width: 3.0, ..active };
+        }
+
+        let background = style.extended_palette().background;
+        let fill = match self.0.map(TabColor::color) {
+            Some(color) => iced::Color { a: 0.6, ..color },
+            None => background.weak.color,
+        };
+        
+        iced::widget::button::Appearance {
+            background: Some(iced::Background::Color(fill)),
+            text_color: background.weak.text,
+            ..active
+        }
+    }
+}
+
+impl iced::widget::button::StyleSheet for TabColorSwatchStyle {
+    type Style = iced::Theme;
+
+    // Small filled square in the label color, outlined when it's the tab's current color
+    fn active(&self, style: &Self::Style) -> iced::widget::button::Appearance {
+        let background = style.extended_palette().background;
+
+        iced::widget::button::Appearance {
+            background: Some(iced::Background::Color(
+                self.color.map(TabColor::color).unwrap_or(background.weak.color),
+            )),
+            border_radius: 4.0,
+            border_width: if self.selected { 2.0 } else { 1.0 },
+            border_color: if self.selected { background.base.text } else { background.strong.color },
+            shadow_offset: iced::Vector::new(0.0, 0.0),
+            text_color: background.weak.text,
+        }
+    }
+}
+
+impl iced::widget::progress_bar::StyleSheet for CountdownBarStyle {
+    type Style = iced::Theme;
+
+    fn appearance(&self, style: &Self::Style) -> iced::widget::progress_bar::Appearance {
+        let palette = style.extended_palette();
+
+        iced::widget::progress_bar::Appearance {
+            background: iced::Background::Color(palette.background.strong.color),
+            bar: iced::Background::Color(urgency_color(self.0, style)),
+            border_radius: 2.0,
+        }
+    }
+}
+
+// Colors the app is drawn with. The high-contrast pairs are pure black and white, and the
+// accents keep at least a 7:1 contrast ratio with the background (WCAG AAA).
+fn app_palette(dark: bool, high_contrast: bool) -> iced::theme::Palette {
+    match (high_contrast, dark) {
+        (false, false) => Theme::Light.palette(),
+        (false, true) => Theme::Dark.palette(),
+        (true, false) => iced::theme::Palette {
+            background: iced::Color::WHITE,
+            text: iced::Color::BLACK,
+            primary: iced::Color::from_rgb8(0x00, 0x33, 0x99),
+            success: iced::Color::from_rgb8(0x00, 0x5C, 0x00),
+            danger: iced::Color::from_rgb8(0x99, 0x00, 0x00),
+        },
+        (true, true) => iced::theme::Palette {
+            background: iced::Color::BLACK,
+            text: iced::Color::WHITE,
+            primary: iced::Color::from_rgb8(0x8C, 0xC8, 0xFF),
+            success: iced::Color::from_rgb8(0x66, 0xE6, 0x66),
+            danger: iced::Color::from_rgb8(0xFF, 0x8C, 0x8C),
+        },
+    }
+}
+
+// The high-contrast palette is the only custom theme the app uses
+fn is_high_contrast(theme: &iced::Theme) -> bool {
+    matches!(theme, Theme::Custom(_))
+}
+
+// Secondary text (hints, countdowns): dimmed, except in high contrast
+fn muted_text_color(theme: &iced::Theme) -> iced::Color {
+    let text = theme.palette().text;
+    if is_high_contrast(theme) {
+        text
+    } else {
+        iced::Color { a: 0.7, ..text }
+    }
+}
+
+// Color of the countdown bar or ring for a given urgency
+fn urgency_color(urgency: Urgency, theme: &iced::Theme) -> iced::Color {
+    let palette = theme.extended_palette();
+    match urgency {
+        Urgency::Normal => palette.primary.base.color,
+        Urgency::Warning => iced::Color::from_rgb(1.0, 0.65, 0.0), // Amber
+        Urgency::Critical => palette.danger.base.color,
+    }
+}
+
+// Circular countdown drawn next to the code; the arc starts at 12 o'clock and runs clockwise
+struct CountdownRing {
+    sweep: f32,
+    urgency: Urgency,
+}
+
+impl canvas::Program<Message> for CountdownRing {
+    type State = ();
+
+    fn draw(
+        &self,
+        _state: &(),
+        theme: &iced::Theme,
+        bounds: iced::Rectangle,
+        _cursor: canvas::Cursor,
+    ) -> Vec<canvas::Geometry> {
+        let mut frame = canvas::Frame::new(bounds.size());
+        let center = frame.center();
+        let width = 5.0;
+        let radius = frame.width().min(frame.height()) / 2.0 - width;
+
+        let track = canvas::Path::circle(center, radius);
+        frame.stroke(
+            &track,
+            canvas::Stroke::default()
+                .with_color(theme.extended_palette().background.strong.color)
+                .with_width(width),
+        );
+
+        if self.sweep > 0.0 {
+            let start_angle = -std::f32::consts::FRAC_PI_2;
+            let arc = canvas::Path::new(|path| {
+                path.arc(canvas::path::Arc {
+                    center,
+                    radius,
+                    start_angle,
+                    end_angle: start_angle + self.sweep,
+                });
+            });
+            frame.stroke(
+                &arc,
+                canvas::Stroke::default()
+                    .with_color(urgency_color(self.urgency, theme))
+                    .with_width(width)
+                    .with_line_cap(canvas::LineCap::Round),
+            );
+        }
+
+        vec![frame.into_geometry()]
+    }
+}
+
+// Wraps a widget and publishes a message when it is right-clicked. iced has no
+// right-click handler on buttons, so the tab menu needs this.
+struct RightClickArea<'a, Message, Renderer> {
+    content: iced_native::Element<'a, Message, Renderer>,
+    on_right_click: Message,
+}
+
+impl<'a, Message, Renderer> RightClickArea<'a, Message, Renderer> {
+    fn new(
+        content: impl Into<iced_native::Element<'a, Message, Renderer>>,
+        on_right_click: Message,
+    ) -> Self {
+        Self {
+            content: content.into(),
+            on_right_click,
+        }
+    }
+}
+
+impl<'a, Message, Renderer> iced_native::Widget<Message, Renderer> for RightClickArea<'a, Message, Renderer>
+where
+    Message: Clone,
+    Renderer: iced_native::Renderer,
+{
+    fn children(&self) -> Vec<iced_native::widget::Tree> {
+        vec![iced_native::widget::Tree::new(&self.content)]
+    }
+
+    fn diff(&self, tree: &mut iced_native::widget::Tree) {
+        tree.diff_children(std::slice::from_ref(&self.content));
+    }
+
+    fn width(&self) -> Length {
+        self.content.as_widget().width()
+    }
+
+    fn height(&self) -> Length {
+        self.content.as_widget().height()
+    }
+
+    fn layout(&self, renderer: &Renderer, limits: &iced_native::layout::Limits) -> iced_native::layout::Node {
+        self.content.as_widget().layout(renderer, limits)
+    }
+
+    fn operate(
+        &self,
+        tree: &mut iced_native::widget::Tree,
+        layout: iced_native::Layout<'_>,
+        renderer: &Renderer,
+        operation: &mut dyn iced_native::widget::Operation<Message>,
+    ) {
+        self.content
+            .as_widget()
+            .operate(&mut tree.children[0], layout, renderer, operation);
+    }
+
+    fn on_event(
+        &mut self,
+        tree: &mut iced_native::widget::Tree,
+        event: Event,
+        layout: iced_native::Layout<'_>,
+        cursor_position: iced::Point,
+        renderer: &Renderer,
+        clipboard: &mut dyn iced_native::Clipboard,
+        shell: &mut iced_native::Shell<'_, Message>,
+    ) -> event::Status {
+        if let Event::Mouse(iced::mouse::Event::ButtonPressed(iced::mouse::Button::Right)) = event {
+            if layout.bounds().contains(cursor_position) {
+                shell.publish(self.on_right_click.clone());
+                return event::Status::Captured;
+            }
+        }
+
+        self.content.as_widget_mut().on_event(
+            &mut tree.children[0],
+            event,
+            layout,
+            cursor_position,
+            renderer,
+            clipboard,
+            shell,
+        )
+    }
+
+    fn mouse_interaction(
+        &self,
+        tree: &iced_native::widget::Tree,
+        layout: iced_native::Layout<'_>,
+        cursor_position: iced::Point,
+        viewport: &iced::Rectangle,
+        renderer: &Renderer,
+    ) -> iced::mouse::Interaction {
+        self.content
+            .as_widget()
+            .mouse_interaction(&tree.children[0], layout, cursor_position, viewport, renderer)
+    }
+
+    fn draw(
+        &self,
+        tree: &iced_native::widget::Tree,
+        renderer: &mut Renderer,
+        theme: &Renderer::Theme,
+        style: &iced_native::renderer::Style,
+        layout: iced_native::Layout<'_>,
+        cursor_position: iced::Point,
+        viewport: &iced::Rectangle,
+    ) {
+        self.content.as_widget().draw(
+            &tree.children[0],
+            renderer,
+            theme,
+            style,
+            layout,
+            cursor_position,
+            viewport,
+        );
+    }
+
+    fn overlay<'b>(
+        &'b mut self,
+        tree: &'b mut iced_native::widget::Tree,
+        layout: iced_native::Layout<'_>,
+        renderer: &Renderer,
+    ) -> Option<iced_native::overlay::Element<'b, Message, Renderer>> {
+        self.content
+            .as_widget_mut()
+            .overlay(&mut tree.children[0], layout, renderer)
+    }
+}
+
+impl<'a, Message, Renderer> From<RightClickArea<'a, Message, Renderer>> for iced_native::Element<'a, Message, Renderer>
+where
+    Message: Clone + 'a,
+    Renderer: iced_native::Renderer + 'a,
+{
+    fn from(area: RightClickArea<'a, Message, Renderer>) -> Self {
+        iced_native::Element::new(area)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Tab generating codes for `secret` with `algorithm`, as set from the dropdown
+    fn tab_with(secret: &str, algorithm: Algorithm, digits: u8) -> Tab {
+        Tab {
+            secret_key: secret.to_string(),
+            algorithm,
+            digits,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn sha256_tab_matches_rfc6238_vector() {
+        // RFC 6238 appendix B SHA-256 key, "12345678901234567890123456789012" in Base32
+        let tab = tab_with(
+            "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZA====",
+            Algorithm::SHA256,
+            8,
+        );
+        assert_eq!(token_for_timestamp(&tab, 59, false).unwrap(), "46119246");
+        assert_eq!(token_for_timestamp(&tab, 1_111_111_109, false).unwrap(), "68084774");
+    }
+
+    #[test]
+    fn algorithm_changes_the_code() {
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        let sha1 = token_for_timestamp(&tab_with(secret, Algorithm::SHA1, 8), 59, false).unwrap();
+        let sha512 = token_for_timestamp(&tab_with(secret, Algorithm::SHA512, 8), 59, false).unwrap();
+        assert_eq!(sha1, "94287082");
+        assert_ne!(sha1, sha512);
+    }
+
+    #[test]
+    fn tabs_with_different_periods_count_down_separately() {
+        let mut app = TotpGenerator {
+            tabs: vec![
+                Tab { period: 30, ..tab_with("JBSWY3DPEHPK3PXP", Algorithm::SHA1, 6) },
+                Tab { period: 60, ..tab_with("JBSWY3DPEHPK3PXP", Algorithm::SHA1, 6) },
+            ],
+            ..Default::default()
+        };
+        let clock = UNIX_EPOCH + Duration::from_secs(1_700_000_050);
+        app.generate_token_at(0, clock);
+        app.generate_token_at(1, clock);
+        assert_eq!(app.tabs[0].time_remaining, 20);
+        assert_eq!(app.tabs[1].time_remaining, 50);
+    }
+
+    #[test]
+    fn period_field_commits_only_a_valid_period_once_typing_pauses() {
+        let mut app = TotpGenerator {
+            tabs: vec![tab_with("JBSWY3DPEHPK3PXP", Algorithm::SHA1, 6)],
+            ..Default::default()
+        };
+        assert_eq!(period_text(&app.tabs[0]), "30");
+
+        // The field can be emptied without the period changing or an error showing
+        let _ = app.handle_message(Message::PeriodInputChanged(String::new(), 0));
+        let _ = app.handle_message(Message::PeriodDebounced(app.tabs[0].period_edit));
+        assert_eq!(period_text(&app.tabs[0]), "");
+        assert_eq!(app.tabs[0].period, 30);
+        assert_eq!(app.tabs[0].error, None);
+
+        // Only the last of "1", "12", "120" is committed
+        let _ = app.handle_message(Message::PeriodInputChanged("1".to_string(), 0));
+        let first = app.tabs[0].period_edit;
+        let _ = app.handle_message(Message::PeriodInputChanged("12".to_string(), 0));
+        let _ = app.handle_message(Message::PeriodInputChanged("120".to_string(), 0));
+        let _ = app.handle_message(Message::PeriodDebounced(first));
+        assert_eq!(app.tabs[0].period, 30);
+        let _ = app.handle_message(Message::PeriodDebounced(app.tabs[0].period_edit));
+        assert_eq!(app.tabs[0].period, 120);
+        assert_eq!(period_text(&app.tabs[0]), "120");
+        assert_eq!(app.tabs[0].period_input, None);
+
+        // A preset replaces whatever was being typed
+        let _ = app.handle_message(Message::PeriodInputChanged("4".to_string(), 0));
+        let _ = app.handle_message(Message::PeriodChanged(60, 0));
+        assert_eq!(period_text(&app.tabs[0]), "60");
+    }
+
+    #[test]
+    fn parses_otpauth_uri_with_issuer_and_parameters() {
+        let parsed = parse_otpauth_uri(
+            "otpauth://totp/ACME%20Co:john@example.com?secret=JBSWY3DPEHPK3PXP&issuer=ACME%20Co&algorithm=SHA256&digits=8&period=60",
+        )
+        .unwrap();
+        assert_eq!(parsed.issuer.as_deref(), Some("ACME Co"));
+        assert_eq!(parsed.account, "john@example.com");
+        assert_eq!(parsed.secret, "JBSWY3DPEHPK3PXP");
+        assert_eq!(parsed.algorithm, Algorithm::SHA256);
+        assert_eq!((parsed.digits, parsed.period), (8, 60));
+        assert_eq!(parsed.mode, OtpMode::Totp);
+        assert!(parsed.adjusted.is_empty());
+    }
+
+    #[test]
+    fn otpauth_uri_defaults_apply_to_missing_parameters() {
+        let parsed = parse_otpauth_uri("otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP").unwrap();
+        assert_eq!(parsed.issuer, None);
+        assert_eq!(parsed.account, "alice");
+        assert_eq!(parsed.algorithm, Algorithm::SHA1);
+        assert_eq!((parsed.digits, parsed.period), (6, 30));
+    }
+
+    #[test]
+    fn rejects_bad_otpauth_uris() {
+        assert!(parse_otpauth_uri("https://example.com/?secret=JBSWY3DPEHPK3PXP").is_err());
+        assert!(parse_otpauth_uri("otpauth://totp/alice?digits=6").is_err());
+        assert!(parse_otpauth_uri("otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP&algorithm=MD5").is_err());
+        assert!(parse_otpauth_uri("otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP&period=0").is_err());
+    }
+
+    #[test]
+    fn builds_otpauth_uri_for_a_tab() {
+        let tab = Tab {
+            issuer: "ACME Co".to_string(),
+            account: "john@example.com".to_string(),
+            ..tab_with("jbsw y3dp ehpk 3pxp", Algorithm::SHA256, 8)
+        };
+        assert_eq!(
+            build_otpauth_uri(&tab, false).unwrap(),
+            "otpauth://totp/ACME%20Co:john%40example.com?secret=JBSWY3DPEHPK3PXP&digits=8&period=30&algorithm=SHA256&issuer=ACME%20Co"
+        );
+    }
+
+    #[test]
+    fn built_otpauth_uri_parses_back_to_the_same_account() {
+        let tab = Tab {
+            issuer: "Example Corp".to_string(),
+            account: "a&b=c".to_string(),
+            period: 60,
+            ..tab_with("JBSWY3DPEHPK3PXP", Algorithm::SHA512, 7)
+        };
+        let parsed = parse_otpauth_uri(&build_otpauth_uri(&tab, false).unwrap()).unwrap();
+
+        assert_eq!(parsed.issuer.as_deref(), Some("Example Corp"));
+        assert_eq!(parsed.account, "a&b=c");
+        // The 80-bit key is exported as entered, without the padding used for generation
+        assert_eq!(parsed.secret, "JBSWY3DPEHPK3PXP");
+        assert_eq!((parsed.digits, parsed.period, parsed.algorithm), (7, 60, Algorithm::SHA512));
+    }
+
+    #[test]
+    fn otpauth_uri_needs_a_valid_secret() {
+        assert!(build_otpauth_uri(&tab_with("not base32!", Algorithm::SHA1, 6), false).is_err());
+    }
+
+    #[test]
+    fn qr_code_holds_the_export_uri() {
+        let tab = Tab {
+            issuer: "ACME Co".to_string(),
+            account: "alice@example.com".to_string(),
+            ..tab_with("jbsw y3dp ehpk 3pxp", Algorithm::SHA256, 8)
+        };
+        let uri = build_otpauth_uri(&tab, false).unwrap();
+
+        let mut png = Vec::new();
+        ::image::DynamicImage::ImageRgba8(qr_code_image(&uri).unwrap())
+            .write_to(&mut png, ::image::ImageOutputFormat::Png)
+            .unwrap();
+        assert_eq!(qr_uri_from_image(&png), Ok(uri));
+    }
+
+    #[test]
+    fn saved_state_round_trips_through_json() {
+        let state = SavedState {
+            tabs: vec![Tab {
+                issuer: "GitHub".to_string(),
+                account: "octocat".to_string(),
+                period: 60,
+                pinned: true,
+                // Never written: the code is generated again on load
+                token: "123456".to_string(),
+                ..tab_with("JBSWY3DPEHPK3PXP", Algorithm::SHA256, 8)
+            }],
+            active_tab: 0,
+            dark_theme: true,
+            clock_offset_secs: -42,
+            hotkey: Some("Ctrl+Shift+O".to_string()),
+            ..Default::default()
+        };
+        let json = serde_json::to_string_pretty(&state).unwrap();
+        let loaded: SavedState = serde_json::from_str(&json).unwrap();
+
+        let tab = &loaded.tabs[0];
+        assert_eq!((tab.issuer.as_str(), tab.account.as_str()), ("GitHub", "octocat"));
+        assert_eq!(tab.secret_key, "JBSWY3DPEHPK3PXP");
+        assert_eq!((tab.algorithm, tab.digits, tab.period), (Algorithm::SHA256, 8, 60));
+        assert!(tab.pinned);
+        assert!(tab.token.is_empty());
+        assert!(loaded.dark_theme);
+        assert_eq!(loaded.clock_offset_secs, -42);
+        assert_eq!(loaded.hotkey.as_deref(), Some("Ctrl+Shift+O"));
+
+        // Fields missing from an older file fall back to their defaults
+        let old: SavedState = serde_json::from_str(r#"{"tabs":[],"active_tab":0}"#).unwrap();
+        assert_eq!(old.window, None);
+        assert!(!old.dark_theme);
+    }
+
+    #[test]
+    fn private_file_replaces_the_old_one_for_the_user_only() {
+        let dir = std::env::temp_dir().join(format!("ro-topt-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("state.json");
+        fs::write(&path, "old").unwrap();
+
+        write_private_file(&path, "new").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert!(!path.with_extension("tmp").exists());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn vault_round_trips_tabs_and_rejects_a_wrong_password() {
+        let tabs = vec![Tab {
+            issuer: "GitHub".to_string(),
+            ..tab_with("JBSWY3DPEHPK3PXP", Algorithm::SHA1, 6)
+        }];
+        let vault = encrypt_tabs(&VaultKey::new("correct horse"), &tabs).unwrap();
+
+        let (key, unlocked) = unlock_vault("correct horse", &vault).unwrap();
+        assert_eq!(unlocked.len(), 1);
+        assert_eq!(unlocked[0].issuer, "GitHub");
+        assert_eq!(unlocked[0].secret_key, "JBSWY3DPEHPK3PXP");
+
+        // The cached key keeps working for later saves without deriving it again
+        let resaved = encrypt_tabs(&key, &unlocked).unwrap();
+        assert_eq!(decrypt_tabs(&key, &resaved).unwrap()[0].secret_key, "JBSWY3DPEHPK3PXP");
+
+        assert!(unlock_vault("wrong horse", &vault).is_err());
+    }
+
+    #[test]
+    fn steam_code_matches_known_answer() {
+        // RFC 6238 SHA-1 key; Steam spells the truncated HMAC in its own 26-letter alphabet
+        let key = b"12345678901234567890";
+        assert_eq!(generate_steam_code(key, 59, 30), "PV9M4");
+        assert_eq!(generate_steam_code(key, 1_111_111_109, 30), "PY4YB");
+
+        let tab = Tab {
+            mode: OtpMode::Steam,
+            ..tab_with("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ", Algorithm::SHA1, 6)
+        };
+        assert_eq!(token_for_timestamp(&tab, 59, false).unwrap(), "PV9M4");
+    }
+
+    #[test]
+    fn hotp_matches_rfc4226_appendix_d() {
+        const EXPECTED: [&str; 10] = [
+            "755224", "287082", "359152", "969429", "338314",
+            "254676", "287922", "162583", "399871", "520489",
+        ];
+        let key = b"12345678901234567890";
+        for (counter, expected) in EXPECTED.iter().enumerate() {
+            assert_eq!(generate_hotp_code(key, counter as u64, 6, Algorithm::SHA1), *expected);
+        }
+
+        // An HOTP tab uses its counter and ignores the time
+        let tab = Tab {
+            mode: OtpMode::Hotp,
+            counter: 9,
+            ..tab_with("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ", Algorithm::SHA1, 6)
+        };
+        assert_eq!(token_for_timestamp(&tab, 1_234_567_890, false).unwrap(), "520489");
+    }
+
+    #[test]
+    fn restored_active_tab_is_clamped_to_the_loaded_tabs() {
+        let mut app = TotpGenerator { active_tab: 5, ..Default::default() };
+        app.restore_tabs(vec![Tab::default(), Tab::default()]);
+        assert_eq!(app.active_tab, 1);
+
+        let mut app = TotpGenerator { active_tab: 1, ..Default::default() };
+        app.restore_tabs(vec![Tab::default(), Tab::default(), Tab::default()]);
+        assert_eq!(app.active_tab, 1);
+
+        // Nothing saved keeps the starting tab
+        let mut app = TotpGenerator { active_tab: 3, ..Default::default() };
+        app.restore_tabs(Vec::new());
+        assert_eq!((app.tabs.len(), app.active_tab), (1, 0));
+    }
+
+    #[test]
+    fn saved_geometry_is_clamped_and_off_screen_positions_are_centered() {
+        let geometry = |width, height, x, y| WindowGeometry { width, height, x, y };
+        assert_eq!(window_size(&geometry(640, 480, None, None)), (640, 480));
+        assert_eq!(window_size(&geometry(0, 0, None, None)), MIN_WINDOW_SIZE);
+        assert_eq!(window_size(&geometry(u32::MAX, u32::MAX, None, None)), MAX_WINDOW_SIZE);
+
+        assert!(matches!(
+            window_position(&geometry(640, 480, Some(100), Some(-20))),
+            window::Position::Specific(100, -20)
+        ));
+        // Windows reports minimized windows at -32000
+        assert!(matches!(
+            window_position(&geometry(640, 480, Some(-32000), Some(-32000))),
+            window::Position::Centered
+        ));
+        assert!(matches!(window_position(&geometry(640, 480, Some(100), None)), window::Position::Centered));
+    }
+
+    #[test]
+    fn ctrl_c_copies_the_active_code_unless_a_field_handled_it() {
+        let ctrl_c = || {
+            Event::Keyboard(keyboard::Event::KeyPressed {
+                key_code: keyboard::KeyCode::C,
+                modifiers: keyboard::Modifiers::COMMAND,
+            })
+        };
+        assert!(matches!(handle_event(ctrl_c(), event::Status::Ignored), Some(Message::CopyActiveToken)));
+        assert!(handle_event(ctrl_c(), event::Status::Captured).is_none());
+
+        // Without a code there is nothing to copy and nothing to report
+        let mut app = TotpGenerator::default();
+        let _ = app.handle_message(Message::CopyActiveToken);
+        assert_eq!(app.tabs[0].error, None);
+        assert_eq!(app.last_copied, None);
+    }
+
+    #[test]
+    fn tab_cycling_wraps_in_both_directions() {
+        assert_eq!(cycle_index(0, 3, true), 1);
+        assert_eq!(cycle_index(2, 3, true), 0);
+        assert_eq!(cycle_index(0, 3, false), 2);
+        assert_eq!(cycle_index(1, 3, false), 0);
+        // A single tab stays where it is, and no tabs can't panic
+        assert_eq!(cycle_index(0, 1, true), 0);
+        assert_eq!(cycle_index(0, 1, false), 0);
+        assert_eq!(cycle_index(0, 0, true), 0);
+
+        let mut app = TotpGenerator {
+            tabs: vec![Tab::default(), Tab::default(), Tab::default()],
+            ..Default::default()
+        };
+        let _ = app.handle_message(Message::CycleTab(false));
+        assert_eq!(app.active_tab, 2);
+        let _ = app.handle_message(Message::CycleTab(true));
+        assert_eq!(app.active_tab, 0);
+    }
+
+    #[test]
+    fn moving_a_tab_keeps_its_data_and_the_selection() {
+        let names = |app: &TotpGenerator| app.tabs.iter().map(|tab| tab.issuer.clone()).collect::<Vec<_>>();
+        let mut app = TotpGenerator {
+            tabs: ["A", "B", "C"]
+                .iter()
+                .map(|issuer| Tab { issuer: issuer.to_string(), ..tab_with("JBSWY3DPEHPK3PXP", Algorithm::SHA1, 6) })
+                .collect(),
+            active_tab: 0,
+            ..Default::default()
+        };
+        app.tabs[0].digits = 8;
+
+        let _ = app.handle_message(Message::MoveTab { from: 0, to: 2 });
+        assert_eq!(names(&app), ["B", "C", "A"]);
+        assert_eq!(app.active_tab, 2);
+        assert_eq!(app.tabs[2].digits, 8);
+        assert_eq!(app.tabs[2].secret_key, "JBSWY3DPEHPK3PXP");
+
+        // Moving another tab past the selected one shifts the selection with it
+        let _ = app.handle_message(Message::MoveTab { from: 1, to: 2 });
+        assert_eq!(names(&app), ["B", "A", "C"]);
+        assert_eq!(app.active_tab, 1);
+
+        // Moves beyond either end do nothing
+        let _ = app.handle_message(Message::MoveTab { from: 0, to: 3 });
+        let _ = app.handle_message(Message::MoveTab { from: 5, to: 0 });
+        assert_eq!(names(&app), ["B", "A", "C"]);
+        assert_eq!(app.active_tab, 1);
+    }
+
+    #[test]
+    fn tab_filter_matches_names_ignoring_case() {
+        assert!(tab_matches_filter("GitHub (octocat)", "github"));
+        assert!(tab_matches_filter("GitHub (octocat)", "OCTO"));
+        assert!(tab_matches_filter("GitHub (octocat)", "  hub "));
+        assert!(tab_matches_filter("GitHub", ""));
+        assert!(!tab_matches_filter("GitHub", "gitlab"));
+    }
+
+    #[test]
+    fn token_for_timestamp_follows_consecutive_steps() {
+        // RFC 6238 SHA-1 vectors for steps 37037036 and 37037037
+        let tab = tab_with("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ", Algorithm::SHA1, 8);
+        assert_eq!(token_for_timestamp(&tab, 1_111_111_109, false).unwrap(), "07081804");
+        assert_eq!(token_for_timestamp(&tab, 1_111_111_109 + 30, false).unwrap(), "14050471");
+        assert_eq!(token_for_timestamp(&tab, 1_111_111_111, false).unwrap(), "14050471");
+    }
+
+    #[test]
+    fn countdown_turns_amber_then_red_near_expiry() {
+        assert_eq!(countdown_urgency(30), Urgency::Normal);
+        assert_eq!(countdown_urgency(COUNTDOWN_WARNING_SECS), Urgency::Normal);
+        assert_eq!(countdown_urgency(COUNTDOWN_WARNING_SECS - 1), Urgency::Warning);
+        assert_eq!(countdown_urgency(COUNTDOWN_CRITICAL_SECS), Urgency::Warning);
+        assert_eq!(countdown_urgency(COUNTDOWN_CRITICAL_SECS - 1), Urgency::Critical);
+        assert_eq!(countdown_urgency(0), Urgency::Critical);
+    }
+
+    #[test]
+    fn invalid_secrets_show_why_instead_of_a_code() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let cases = [
+            ("", SecretError::Empty),
+            ("!!!!", SecretError::InvalidBase32),
+            ("JBSWY3DP", SecretError::TooShort { bits: 40 }),
+        ];
+        for (secret, expected) in cases {
+            let mut app = TotpGenerator {
+                tabs: vec![tab_with("JBSWY3DPEHPK3PXP", Algorithm::SHA1, 6)],
+                ..Default::default()
+            };
+            app.generate_token_at(0, now);
+            assert!(!app.tabs[0].token.is_empty());
+
+            app.tabs[0].secret_key = secret.to_string();
+            app.generate_token_at(0, now);
+            assert!(app.tabs[0].token.is_empty(), "{:?} still produced a code", secret);
+            assert_eq!(app.tabs[0].error, Some(expected.to_string()));
+        }
+    }
+
+    #[test]
+    fn hex_secret_matches_rfc6238_vectors() {
+        // The RFC's own SHA-1 key, written in hex as in its reference implementation
+        let tab = Tab {
+            secret_format: SecretFormat::Hex,
+            ..tab_with("3132333435363738393031323334353637383930", Algorithm::SHA1, 8)
+        };
+        assert_eq!(token_for_timestamp(&tab, 59, false).unwrap(), "94287082");
+        assert_eq!(token_for_timestamp(&tab, 2_000_000_000, false).unwrap(), "69279037");
+
+        let prefixed = Tab { secret_key: "0x3132333435363738393031323334353637383930".to_string(), ..tab };
+        assert_eq!(token_for_timestamp(&prefixed, 1_234_567_890, false).unwrap(), "89005924");
+    }
+
+    #[test]
+    fn clock_offset_saturates_instead_of_wrapping() {
+        let now = current_unix_time().unwrap();
+        // Allow for the clock ticking over between the two readings
+        assert!(effective_now(0) - now <= 1);
+        assert!(effective_now(-60) + 60 - now <= 1);
+        assert!(effective_now(3_600) - 3_600 - now <= 1);
+        assert_eq!(effective_now(i64::MIN), 0);
+        assert!(effective_now(i64::MAX) > i64::MAX as u64);
+    }
+
+    // SNTP server reply whose receive and transmit times are both `server_time`
+    fn ntp_reply(server_time: u32) -> [u8; 48] {
+        let mut packet = [0u8; 48];
+        packet[0] = 0x24; // version 4, mode 4 (server)
+        packet[1] = 1; // stratum 1
+        packet[32..36].copy_from_slice(&server_time.to_be_bytes());
+        packet[40..44].copy_from_slice(&server_time.to_be_bytes());
+        packet
+    }
+
+    #[test]
+    fn ntp_offset_averages_both_legs_of_the_round_trip() {
+        // Server is 10 s ahead; the request took 0.2 s there and back
+        let server_time = (1_700_000_010 + NTP_UNIX_EPOCH_DELTA as u64) as u32;
+        let packet = ntp_reply(server_time);
+        assert_eq!(ntp_offset(&packet, 1_699_999_999.9, 1_700_000_000.1), Ok(10));
+        assert_eq!(ntp_offset(&packet, 1_700_000_019.9, 1_700_000_020.1), Ok(-10));
+    }
+
+    #[test]
+    fn ntp_offset_rejects_bad_replies() {
+        let server_time = (1_700_000_000 + NTP_UNIX_EPOCH_DELTA as u64) as u32;
+        let now = 1_700_000_000.0;
+        assert!(ntp_offset(&ntp_reply(server_time)[..47], now, now).is_err());
+
+        let mut client_mode = ntp_reply(server_time);
+        client_mode[0] = 0x23;
+        assert!(ntp_offset(&client_mode, now, now).is_err());
+
+        let mut kiss_of_death = ntp_reply(server_time);
+        kiss_of_death[1] = 0;
+        assert!(ntp_offset(&kiss_of_death, now, now).is_err());
+
+        assert!(ntp_offset(&ntp_reply(0), now, now).is_err());
+    }
+
+    #[test]
+    fn ntp_timestamps_wrap_into_the_2036_era() {
+        let unix = |secs: u32| ntp_timestamp(&[secs.to_be_bytes(), [0; 4]].concat());
+        assert_eq!(unix(3_908_988_800), 1_700_000_000.0);
+        // Top bit clear: seconds count from the 2036 rollover
+        assert_eq!(unix(1), 4_294_967_297.0 - NTP_UNIX_EPOCH_DELTA);
+        assert_eq!(ntp_timestamp(&[0, 0, 0, 0, 0x80, 0, 0, 0]), 4_294_967_296.0 - NTP_UNIX_EPOCH_DELTA + 0.5);
+    }
+
+    #[test]
+    fn migration_payload_imports_a_known_export() {
+        // Three accounts: "ACME:alice" (TOTP, SHA1, 6 digits), GitHub "bob" (HOTP, SHA256,
+        // 8 digits, counter 5) and "old" with algorithm MD5, which can't be imported
+        let uri = "otpauth-migration://offline?data=Ch4KCkhlbGxvId6tvu8SCkFDTUU6YWxpY2UgASgBMAIKKwoUMTIzNDU2Nzg5MDEyMzQ1Njc4OTASA2JvYhoGR2l0SHViIAIoAjABOAUKIQoUMTIzNDU2Nzg5MDEyMzQ1Njc4OTASA29sZCAEKAEwAhABGAEgACi5YA%3D%3D";
+        let (tabs, skipped) = parse_migration_payload(uri).unwrap();
+        assert_eq!(skipped, 1);
+        assert_eq!(tabs.len(), 2);
+
+        // Without an issuer field, the issuer comes from the "Issuer:account" name
+        assert_eq!((tabs[0].issuer.as_str(), tabs[0].account.as_str()), ("ACME", "alice"));
+        assert_eq!(tabs[0].secret_key, "JBSWY3DPEHPK3PXP");
+        assert_eq!((tabs[0].mode, tabs[0].algorithm, tabs[0].digits), (OtpMode::Totp, Algorithm::SHA1, 6));
+
+        assert_eq!((tabs[1].issuer.as_str(), tabs[1].account.as_str()), ("GitHub", "bob"));
+        assert_eq!(tabs[1].secret_key, "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!((tabs[1].mode, tabs[1].algorithm, tabs[1].digits), (OtpMode::Hotp, Algorithm::SHA256, 8));
+        assert_eq!(tabs[1].counter, 5);
+    }
+
+    #[test]
+    fn migration_payload_rejects_bad_input() {
+        assert!(parse_migration_payload("otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP").is_err());
+        assert!(parse_migration_payload("otpauth-migration://offline?other=1").is_err());
+        assert!(parse_migration_payload("otpauth-migration://offline?data=%%%").is_err());
+        // Valid Base64 but a truncated protobuf message
+        assert!(parse_migration_payload("otpauth-migration://offline?data=Ch4K").is_err());
+    }
+
+    #[test]
+    fn aegis_backup_imports_supported_entries() {
+        let json = r#"{
+            "version": 1,
+            "header": {"slots": null, "params": null},
+            "db": {
+                "version": 2,
+                "entries": [
+                    {"type": "totp", "name": "alice", "issuer": "ACME",
+                     "info": {"secret": "JBSWY3DPEHPK3PXP", "algo": "SHA256", "digits": 8, "period": 60}},
+                    {"type": "steam", "name": "gaben", "issuer": "Steam",
+                     "info": {"secret": "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ", "algo": "SHA1", "digits": 5, "period": 30}},
+                    {"type": "hotp", "name": "bob", "issuer": "GitHub",
+                     "info": {"secret": "JBSWY3DPEHPK3PXP", "algo": "SHA1", "digits": 6, "counter": 7}},
+                    {"type": "yandex", "name": "carol", "issuer": "Yandex",
+                     "info": {"secret": "JBSWY3DPEHPK3PXP", "algo": "SHA256", "digits": 8, "period": 30}},
+                    {"type": "totp", "name": "dave", "issuer": "Five",
+                     "info": {"secret": "JBSWY3DPEHPK3PXP", "algo": "SHA1", "digits": 5, "period": 30}}
+                ]
+            }
+        }"#;
+        let (tabs, skipped) = parse_aegis_backup(json).unwrap();
+        // The unknown "yandex" type and the 5-digit TOTP entry are skipped
+        assert_eq!(skipped, 2);
+        assert_eq!(tabs.len(), 3);
+
+        assert_eq!((tabs[0].issuer.as_str(), tabs[0].account.as_str()), ("ACME", "alice"));
+        assert_eq!((tabs[0].algorithm, tabs[0].digits, tabs[0].period), (Algorithm::SHA256, 8, 60));
+        // Steam's 5 characters come from the mode; the tab keeps a valid digit count
+        assert_eq!((tabs[1].mode, tabs[1].digits), (OtpMode::Steam, 6));
+        assert_eq!((tabs[2].mode, tabs[2].counter), (OtpMode::Hotp, 7));
+        assert!(tabs.iter().all(|tab| !tab.editing_name));
+    }
+
+    #[test]
+    fn aegis_backup_rejects_encrypted_and_malformed_files() {
+        let encrypted = r#"{"version": 1, "header": {"slots": [], "params": {}}, "db": "c2VjcmV0IHN0dWZm"}"#;
+        assert_eq!(
+            parse_aegis_backup(encrypted).unwrap_err(),
+            "Encrypted Aegis backups can't be imported; export without encryption"
+        );
+        assert!(parse_aegis_backup("{\"db\": 42}").is_err());
+        assert!(parse_aegis_backup("not json").is_err());
+    }
+
+    #[test]
+    fn backup_round_trips_and_rejects_a_wrong_passphrase() {
+        let tabs = vec![tab_with("JBSWY3DPEHPK3PXP", Algorithm::SHA256, 8)];
+        let json = encrypt_backup("backup pass", &tabs).unwrap();
+
+        let restored = decrypt_backup("backup pass", &json).unwrap();
+        assert_eq!(restored[0].secret_key, "JBSWY3DPEHPK3PXP");
+        assert_eq!(restored[0].algorithm, Algorithm::SHA256);
+        assert!(decrypt_backup("other pass", &json).is_err());
+    }
+
+    #[test]
+    fn launch_secret_opens_a_transient_tab_and_is_not_saved() {
+        let saved = Tab {
+            issuer: "GitHub".to_string(),
+            editing_name: false,
+            ..tab_with("JBSWY3DPEHPK3PXP", Algorithm::SHA1, 6)
+        };
+        let mut app = TotpGenerator {
+            tabs: vec![saved],
+            startup_secret: Some(Ok("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ".to_string())),
+            ..Default::default()
+        };
+        app.apply_startup_secret();
+
+        // The saved account is untouched and the launch secret is in use in its own tab
+        assert_eq!(app.tabs.len(), 2);
+        assert_eq!(app.tabs[0].secret_key, "JBSWY3DPEHPK3PXP");
+        assert_eq!(app.active_tab, 1);
+        assert!(app.tabs[1].transient);
+        assert_eq!(app.tabs[1].secret_key, "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(app.tabs[1].error, None);
+        assert_eq!(app.tabs[1].token.len(), 6);
+
+        let (tabs, active) = saved_tabs(&app.tabs, app.active_tab);
+        assert_eq!(tabs.len(), 1);
+        assert_eq!(tabs[0].secret_key, "JBSWY3DPEHPK3PXP");
+        assert_eq!(active, 0);
+    }
+
+    #[test]
+    fn launch_secret_takes_over_a_lone_empty_tab() {
+        let mut app = TotpGenerator {
+            startup_secret: Some(Ok("JBSWY3DPEHPK3PXP".to_string())),
+            ..Default::default()
+        };
+        app.apply_startup_secret();
+
+        assert_eq!(app.tabs.len(), 1);
+        assert!(app.tabs[0].transient);
+        assert!(saved_tabs(&app.tabs, app.active_tab).0.is_empty());
+    }
+
+    #[test]
+    fn saved_tabs_keeps_the_active_index_on_saved_tabs() {
+        let transient = || Tab { transient: true, ..Default::default() };
+        let tabs = vec![Tab::default(), transient(), Tab::default(), Tab::default()];
+        assert_eq!(saved_tabs(&tabs, 0).1, 0);
+        assert_eq!(saved_tabs(&tabs, 1).1, 0);
+        assert_eq!(saved_tabs(&tabs, 3).1, 2);
+        assert_eq!(saved_tabs(&tabs, 3).0.len(), 3);
+    }
+
+    #[test]
+    fn countdown_ring_is_full_at_the_start_and_empty_at_expiry() {
+        use std::f32::consts::TAU;
+        assert_eq!(countdown_sweep_angle(30, 30), TAU);
+        assert_eq!(countdown_sweep_angle(0, 30), 0.0);
+        assert!((countdown_sweep_angle(15, 30) - TAU / 2.0).abs() < 1e-6);
+        assert_eq!(countdown_sweep_angle(90, 30), TAU);
+        assert_eq!(countdown_sweep_angle(10, 0), 0.0);
+    }
+
+    #[test]
+    fn duplicate_secrets_match_however_they_are_written() {
+        let hex = Tab {
+            secret_format: SecretFormat::Hex,
+            ..tab_with("48656c6c6f21deadbeef", Algorithm::SHA1, 6)
+        };
+        let app = TotpGenerator {
+            tabs: vec![
+                tab_with("JBSWY3DPEHPK3PXP", Algorithm::SHA1, 6),
+                tab_with("jbsw y3dp-ehpk 3pxp", Algorithm::SHA1, 6),
+                hex,
+                tab_with("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ", Algorithm::SHA1, 6),
+                tab_with("not a secret!", Algorithm::SHA1, 6),
+                tab_with("not a secret!", Algorithm::SHA1, 6),
+            ],
+            ..Default::default()
+        };
+        // Spacing, case and Base32 vs hex don't hide a duplicate
+        assert_eq!(app.find_duplicate_secret(0), Some(1));
+        assert_eq!(app.find_duplicate_secret(1), Some(0));
+        assert_eq!(app.find_duplicate_secret(2), Some(0));
+        assert_eq!(app.find_duplicate_secret(3), None);
+        // Secrets that don't decode never count as duplicates
+        assert_eq!(app.find_duplicate_secret(4), None);
+        assert_eq!(app.find_duplicate_secret(9), None);
+    }
+
+    #[test]
+    fn ui_scale_is_clamped_and_rounded_to_the_step() {
+        assert_eq!(clamp_ui_scale(0.1), MIN_UI_SCALE);
+        assert_eq!(clamp_ui_scale(5.0), MAX_UI_SCALE);
+        assert_eq!(clamp_ui_scale(f32::NAN), 1.0);
+        assert_eq!(clamp_ui_scale(f32::INFINITY), 1.0);
+
+        // Repeated steps land on the same values as typing them in
+        let mut scale = 1.0;
+        for _ in 0..2 {
+            scale = clamp_ui_scale(scale + UI_SCALE_STEP);
+        }
+        assert_eq!(scale, clamp_ui_scale(1.2));
+
+        let app = TotpGenerator {
+            scale: 1.5,
+            ..Default::default()
+        };
+        assert_eq!(app.scaled(14.0), 21.0);
+    }
+
+    #[test]
+    fn tray_menu_lists_every_tab_with_unique_labels() {
+        let named = |issuer: &str, token: &str| Tab {
+            issuer: issuer.to_string(),
+            token: token.to_string(),
+            ..Default::default()
+        };
+        let tabs = vec![named("GitHub", "123456"), named("AWS", ""), named("github", "654321")];
+
+        let entries = tray_menu_entries(&tabs, false);
+        let labels: Vec<&str> = entries.iter().map(|entry| entry.label.as_str()).collect();
+        assert_eq!(labels, ["GitHub #1", "AWS", "github #3"]);
+        // A tab without a code has nothing to copy
+        let enabled: Vec<bool> = entries.iter().map(|entry| entry.enabled).collect();
+        assert_eq!(enabled, [true, false, true]);
+
+        // Nothing is listed while the tabs are locked
+        assert!(tray_menu_entries(&tabs, true).is_empty());
+    }
+
+    #[test]
+    fn tray_menu_ids_map_to_messages() {
+        assert!(matches!(tray_menu_message(TRAY_SHOW_ID), Some(Message::ShowWindow)));
+        assert!(matches!(tray_menu_message(TRAY_QUIT_ID), Some(Message::Quit)));
+        let copy_id = format!("{}{}", TRAY_COPY_PREFIX, 2);
+        assert!(matches!(tray_menu_message(&copy_id), Some(Message::CopyToClipboard(2))));
+        assert!(tray_menu_message("something-else").is_none());
+    }
+
+    #[test]
+    fn parses_hotkeys_with_modifiers() {
+        assert_eq!(
+            parse_hotkey(DEFAULT_HOTKEY),
+            Ok(HotKey::new(Some(Modifiers::CONTROL | Modifiers::ALT), Code::KeyT))
+        );
+        assert_eq!(
+            parse_hotkey("control + shift + 5"),
+            Ok(HotKey::new(Some(Modifiers::CONTROL | Modifiers::SHIFT), Code::Digit5))
+        );
+        assert_eq!(parse_hotkey("Cmd+f5"), Ok(HotKey::new(Some(Modifiers::SUPER), Code::F5)));
+        assert_eq!(parse_hotkey("Alt+Space"), Ok(HotKey::new(Some(Modifiers::ALT), Code::Space)));
+    }
+
+    #[test]
+    fn rejects_incomplete_or_unknown_hotkeys() {
+        assert!(parse_hotkey("T").is_err());
+        assert!(parse_hotkey("Hyper+T").is_err());
+        assert!(parse_hotkey("Ctrl+Nope").is_err());
+        assert!(parse_hotkey("Ctrl+").is_err());
+        assert!(parse_hotkey("").is_err());
+    }
+
+    #[test]
+    fn tab_color_survives_a_save_and_defaults_to_none() {
+        for color in [None, Some(TabColor::Purple)] {
+            let tab = Tab {
+                color,
+                ..tab_with("JBSWY3DPEHPK3PXP", Algorithm::SHA1, 6)
+            };
+            let loaded: Tab = serde_json::from_str(&serde_json::to_string(&tab).unwrap()).unwrap();
+            assert_eq!(loaded.color, color);
+        }
+
+        // Tabs saved before colors existed keep the theme's styling
+        let old: Tab = serde_json::from_str(r#"{"secret_key":"JBSWY3DPEHPK3PXP"}"#).unwrap();
+        assert_eq!(old.color, None);
+    }
+
+    #[test]
+    fn removing_a_tab_waits_for_confirmation() {
+        let mut app = TotpGenerator {
+            tabs: vec![Tab::default(), Tab::default(), Tab::default()],
+            ..Default::default()
+        };
+
+        let _ = app.handle_message(Message::RequestRemoveTab(1));
+        assert_eq!(app.pending_removal, Some(1));
+        assert_eq!(app.tabs.len(), 3);
+
+        let _ = app.handle_message(Message::CancelRemoveTab);
+        assert_eq!(app.pending_removal, None);
+        assert_eq!(app.tabs.len(), 3);
+
+        // Switching tabs abandons the prompt
+        let _ = app.handle_message(Message::RequestRemoveTab(1));
+        let _ = app.handle_message(Message::SelectTab(2));
+        assert_eq!(app.pending_removal, None);
+        let _ = app.handle_message(Message::ConfirmRemoveTab);
+        assert_eq!(app.tabs.len(), 3);
+
+        let _ = app.handle_message(Message::RequestRemoveTab(1));
+        let _ = app.handle_message(Message::ConfirmRemoveTab);
+        assert_eq!(app.pending_removal, None);
+        assert_eq!(app.tabs.len(), 2);
+
+        // Out of range, or the last tab left, there is nothing to confirm
+        let _ = app.handle_message(Message::RequestRemoveTab(5));
+        assert_eq!(app.pending_removal, None);
+        app.tabs.truncate(1);
+        app.active_tab = 0;
+        let _ = app.handle_message(Message::RequestRemoveTab(0));
+        assert_eq!(app.pending_removal, None);
+    }
+
+    #[test]
+    fn undo_restores_a_removed_tab_exactly() {
+        let removed = Tab {
+            issuer: "ACME".to_string(),
+            account: "alice".to_string(),
+            period: 60,
+            mode: OtpMode::Totp,
+            color: Some(TabColor::Red),
+            pinned: true,
+            notify_before_expiry: true,
+            auto_copy: true,
+            ..tab_with("JBSWY3DPEHPK3PXP", Algorithm::SHA512, 8)
+        };
+        let mut app = TotpGenerator {
+            tabs: vec![Tab::default(), removed.clone(), Tab::default()],
+            active_tab: 1,
+            ..Default::default()
+        };
+
+        let _ = app.handle_message(Message::RemoveTab(1));
+        assert_eq!(app.tabs.len(), 2);
+        let _ = app.handle_message(Message::UndoRemoveTab);
+        assert_eq!(app.tabs.len(), 3);
+        assert_eq!(app.active_tab, 1);
+
+        // Every saved field comes back as it was
+        let restored = &app.tabs[1];
+        assert_eq!(serde_json::to_value(restored).unwrap(), serde_json::to_value(&removed).unwrap());
+        assert_eq!(restored.token.len(), 8);
+        assert_eq!(app.last_removed.as_ref().map(|(idx, _)| *idx), None);
+    }
+
+    #[test]
+    fn debounced_generation_follows_the_edited_tab() {
+        let mut app = TotpGenerator {
+            tabs: vec![Tab::default(), Tab::default()],
+            ..Default::default()
+        };
+        let _ = app.handle_message(Message::SecretKeyChanged("JBSWY3DPEHPK3PXP".to_string(), 1));
+        let edit = app.tabs[1].secret_edit;
+        assert!(app.tabs[1].token.is_empty());
+
+        // The edited tab moves before the debounce fires; the code still lands on it
+        let _ = app.handle_message(Message::MoveTab { from: 1, to: 0 });
+        let _ = app.handle_message(Message::GenerateDebounced(edit));
+        assert_eq!(app.tabs[0].secret_key, "JBSWY3DPEHPK3PXP");
+        assert_eq!(app.tabs[0].token.len(), 6);
+        assert!(app.tabs[1].token.is_empty());
+    }
+
+    #[test]
+    fn stale_debounced_generation_is_skipped() {
+        let mut app = TotpGenerator::default();
+        let _ = app.handle_message(Message::SecretKeyChanged("JBSWY3DP".to_string(), 0));
+        let stale = app.tabs[0].secret_edit;
+        let _ = app.handle_message(Message::SecretKeyChanged("JBSWY3DPEHPK3PXP".to_string(), 0));
+
+        let _ = app.handle_message(Message::GenerateDebounced(stale));
+        assert!(app.tabs[0].token.is_empty());
+
+        // Clearing the field also cancels the generation that was pending
+        let pending = app.tabs[0].secret_edit;
+        let _ = app.handle_message(Message::SecretKeyChanged(String::new(), 0));
+        let _ = app.handle_message(Message::GenerateDebounced(pending));
+        assert!(app.tabs[0].token.is_empty());
+        assert_eq!(app.tabs[0].error, None);
+    }
+
+    #[test]
+    fn remaining_time_switches_to_minutes_after_sixty_seconds() {
+        assert_eq!(format_remaining(0), "0 seconds");
+        assert_eq!(format_remaining(1), "1 second");
+        assert_eq!(format_remaining(59), "59 seconds");
+        assert_eq!(format_remaining(60), "60 seconds");
+        assert_eq!(format_remaining(61), "1:01");
+        assert_eq!(format_remaining(125), "2:05");
+    }
+
+    #[test]
+    fn copy_all_lists_each_tab_with_a_code() {
+        let with_code = |issuer: &str, token: &str| Tab {
+            issuer: issuer.to_string(),
+            token: token.to_string(),
+            ..Default::default()
+        };
+        let tabs = vec![
+            with_code("GitHub", "123456"),
+            with_code("Empty", ""),
+            with_code("", "654321"),
+            with_code("AWS", "12345678"),
+        ];
+
+        assert_eq!(
+            all_tokens_text(&tabs, false),
+            ("GitHub: 123456\nTab 3: 654321\nAWS: 12345678".to_string(), 3)
+        );
+        assert_eq!(all_tokens_text(&tabs, true).0, "GitHub: 123 456\nTab 3: 654 321\nAWS: 1234 5678");
+        assert_eq!(all_tokens_text(&[with_code("Empty", "")], false), (String::new(), 0));
+    }
+
+    #[test]
+    fn pinned_tabs_come_first_in_their_saved_order() {
+        let pinned = |pinned: bool| Tab { pinned, ..Default::default() };
+        let mut app = TotpGenerator {
+            tabs: vec![pinned(false), pinned(true), pinned(false), pinned(true)],
+            active_tab: 2,
+            ..Default::default()
+        };
+        assert_eq!(app.ordered_tab_indices(), [1, 3, 0, 2]);
+
+        // Pinning changes only the display order, so the active tab stays the same one
+        let _ = app.handle_message(Message::TogglePinned(2));
+        assert_eq!(app.ordered_tab_indices(), [1, 2, 3, 0]);
+        assert_eq!(app.active_tab, 2);
+
+        app.tabs.iter_mut().for_each(|tab| tab.pinned = false);
+        assert_eq!(app.ordered_tab_indices(), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn uri_pasted_as_a_name_fills_the_tab() {
+        let mut app = TotpGenerator {
+            tabs: vec![Tab::default()],
+            ..Default::default()
+        };
+
+        // Text that merely mentions a URI is an ordinary name
+        for name in ["GitHub", "see otpauth://totp/x", "otpauth:/totp/x"] {
+            let _ = app.handle_message(Message::IssuerChanged(name.to_string(), 0));
+            assert_eq!(app.tabs[0].issuer, name);
+            assert!(app.tabs[0].secret_key.is_empty());
+        }
+
+        let uri = " OTPAUTH://totp/GitHub:octocat?secret=JBSWY3DPEHPK3PXP&issuer=GitHub&period=60&digits=8";
+        let _ = app.handle_message(Message::AccountChanged(uri.to_string(), 0));
+        let tab = &app.tabs[0];
+        assert_eq!((tab.issuer.as_str(), tab.account.as_str()), ("GitHub", "octocat"));
+        assert_eq!(tab.secret_key, "JBSWY3DPEHPK3PXP");
+        assert_eq!((tab.period, tab.digits), (60, 8));
+        assert!(!tab.token.is_empty());
+
+        // A URI that doesn't parse is kept as typed, with the reason shown
+        let _ = app.handle_message(Message::IssuerChanged("otpauth://totp/Broken".to_string(), 0));
+        assert_eq!(app.tabs[0].issuer, "otpauth://totp/Broken");
+        assert!(app.tabs[0].error.is_some());
+    }
+
+    #[test]
+    fn copied_message_clears_on_its_own_tab_after_switching() {
+        let mut app = TotpGenerator {
+            tabs: vec![Tab::default(), Tab::default()],
+            ..Default::default()
+        };
+        app.tabs[0].show_timed_message("Code copied to clipboard!".to_string(), 2);
+        let _ = app.handle_message(Message::SelectTab(1));
+        app.tabs[1].error = Some("Secret key is too short".to_string());
+
+        let _ = app.handle_message(Message::Tick);
+        assert_eq!(app.tabs[0].error.as_deref(), Some("Code copied to clipboard!"));
+        let _ = app.handle_message(Message::Tick);
+        assert_eq!(app.tabs[0].error, None);
+        // The other tab's error isn't a timed message and stays up
+        assert_eq!(app.tabs[1].error.as_deref(), Some("Secret key is too short"));
+    }
+
+    // PNG file of a QR code holding `text`, like a screenshot of a setup page
+    fn qr_png(text: &str) -> Vec<u8> {
+        let luma = QrCode::new(text).unwrap().render::<::image::Luma<u8>>().build();
+        let mut png = Vec::new();
+        ::image::DynamicImage::ImageLuma8(luma)
+            .write_to(&mut png, ::image::ImageOutputFormat::Png)
+            .unwrap();
+        png
+    }
+
+    #[test]
+    fn decodes_otpauth_uri_from_a_qr_png() {
+        let uri = "otpauth://totp/ACME:alice?secret=JBSWY3DPEHPK3PXP&issuer=ACME";
+        assert_eq!(qr_uri_from_image(&qr_png(uri)), Ok(uri.to_string()));
+    }
+
+    #[test]
+    fn qr_decoding_reports_unusable_images() {
+        assert!(qr_uri_from_image(b"not an image").is_err());
+
+        let blank = ::image::GrayImage::from_pixel(64, 64, ::image::Luma([255]));
+        let mut png = Vec::new();
+        ::image::DynamicImage::ImageLuma8(blank)
+            .write_to(&mut png, ::image::ImageOutputFormat::Png)
+            .unwrap();
+        assert_eq!(qr_uri_from_image(&png), Err("no QR code found in the image".to_string()));
+    }
+
+    #[test]
+    fn validity_indicator_needs_a_usable_key() {
+        let tab = |secret: &str| tab_with(secret, Algorithm::SHA1, 6);
+        assert!(secret_is_valid(&tab("JBSWY3DPEHPK3PXP"), false));
+        assert!(secret_is_valid(&tab("jbsw y3dp ehpk 3pxp"), false));
+        assert!(!secret_is_valid(&tab(""), false));
+        assert!(!secret_is_valid(&tab("JBSWY3DP"), false));
+        assert!(!secret_is_valid(&tab("JBSWY3DPEHPK3PX!"), false));
+        // Strict mode accepts only the key exactly as written
+        assert!(!secret_is_valid(&tab("jbsw y3dp ehpk 3pxp"), true));
+
+        let hex = Tab {
+            secret_format: SecretFormat::Hex,
+            ..tab("3132333435363738393031323334353637383930")
+        };
+        assert!(secret_is_valid(&hex, false));
+    }
+
+    #[test]
+    fn counter_is_the_number_of_whole_periods() {
+        assert_eq!(current_counter(0, 30), 0);
+        assert_eq!(current_counter(29, 30), 0);
+        assert_eq!(current_counter(30, 30), 1);
+        assert_eq!(current_counter(59, 30), 1);
+        // RFC 6238 test vector: T = 0x23523EC at 1111111111
+        assert_eq!(current_counter(1_111_111_111, 30), 0x23523EC);
+        assert_eq!(current_counter(1_111_111_111, 60), 18_518_518);
+        // A zero period from a hand-edited file doesn't divide by zero
+        assert_eq!(current_counter(42, 0), 42);
+    }
+
+    #[test]
+    fn skipped_rollover_second_still_regenerates_once() {
+        // 1_700_000_010 starts a new 30-second step
+        let at = |secs: u64| UNIX_EPOCH + Duration::from_secs(secs);
+        let mut app = TotpGenerator {
+            tabs: vec![tab_with("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ", Algorithm::SHA1, 6)],
+            ..Default::default()
+        };
+        app.generate_token_at(0, at(1_700_000_008));
+        assert!(!app.tabs[0].needs_regeneration(1_700_000_009));
+
+        // The tick for the rollover second never came
+        assert!(app.tabs[0].needs_regeneration(1_700_000_011));
+        app.generate_token_at(0, at(1_700_000_011));
+        assert!(!app.tabs[0].needs_regeneration(1_700_000_012));
+        assert!(!app.tabs[0].needs_regeneration(1_700_000_039));
+        assert!(app.tabs[0].needs_regeneration(1_700_000_040));
+    }
+
+    #[test]
+    fn ticks_run_only_while_something_counts_down() {
+        let mut tabs = vec![Tab::default(), tab_with("JBSWY3DPEHPK3PXP", Algorithm::SHA1, 6)];
+        assert!(!needs_tick(&tabs));
+        assert!(!needs_tick(&[]));
+
+        tabs[1].token = "123456".to_string();
+        assert!(needs_tick(&tabs));
+
+        tabs[1].token.clear();
+        tabs[0].show_timed_message("Code copied to clipboard!".to_string(), 3);
+        assert!(needs_tick(&tabs));
+
+        tabs[0].message_secs_left = 0;
+        tabs[0].revealed = true;
+        assert!(needs_tick(&tabs));
+    }
+
+    #[test]
+    fn expiry_threshold_is_crossed_once_per_period() {
+        assert!(crossed_expiry_threshold(6, 5, 5));
+        // A skipped tick still counts
+        assert!(crossed_expiry_threshold(8, 3, 5));
+        // Already below: no repeat on every tick
+        assert!(!crossed_expiry_threshold(5, 4, 5));
+        assert!(!crossed_expiry_threshold(10, 9, 5));
+        // The rollover resets the countdown above the threshold
+        assert!(!crossed_expiry_threshold(1, 30, 5));
+        assert!(crossed_expiry_threshold(30, 5, 5));
+    }
+
+    #[test]
+    fn tab_numbers_pick_tabs_and_nine_picks_the_last() {
+        assert_eq!(tab_for_number(1, 3), Some(0));
+        assert_eq!(tab_for_number(3, 3), Some(2));
+        assert_eq!(tab_for_number(9, 3), Some(2));
+        assert_eq!(tab_for_number(9, 12), Some(11));
+        assert_eq!(tab_for_number(8, 12), Some(7));
+        // Numbers past the last tab, 0 and an empty tab list do nothing
+        assert_eq!(tab_for_number(4, 3), None);
+        assert_eq!(tab_for_number(0, 3), None);
+        assert_eq!(tab_for_number(9, 0), None);
+        assert_eq!(tab_for_number(1, 0), None);
+
+        let mut app = TotpGenerator {
+            tabs: vec![Tab::default(), Tab::default()],
+            ..Default::default()
+        };
+        let _ = app.handle_message(Message::SelectTabNumber(9));
+        assert_eq!(app.active_tab, 1);
+        let _ = app.handle_message(Message::SelectTabNumber(5));
+        assert_eq!(app.active_tab, 1);
+    }
+
+    #[test]
+    fn short_secrets_are_flagged_as_weak() {
+        let key_len = |secret: &str| tab_with(secret, Algorithm::SHA1, 6).decode_key(false).unwrap().len();
+        // 80 bits, the shortest key accepted at all
+        assert_eq!(key_len("JBSWY3DPEHPK3PXP"), 10);
+        assert_eq!(
+            weak_key_hint(key_len("JBSWY3DPEHPK3PXP")).as_deref(),
+            Some("weak: secret is only 10 bytes (padded to 16)")
+        );
+        assert!(weak_key_hint(key_len("JBSWY3DPEHPK3PXPJBSWY3DP")).is_some());
+        // 20 and 25 bytes need no padding
+        assert_eq!(weak_key_hint(key_len("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ")), None);
+        assert_eq!(weak_key_hint(key_len("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBV")), None);
+        assert!(weak_key_hint(PADDED_KEY_BYTES - 1).is_some());
+        assert_eq!(weak_key_hint(PADDED_KEY_BYTES), None);
+    }
+
+    #[test]
+    fn csv_import_reads_accounts_and_reports_bad_lines() {
+        let text = "name,secret\n\
+                    # work accounts\n\
+                    GitHub, JBSWY3DPEHPK3PXP\n\
+                    \n\
+                    \"ACME, Inc. \"\"prod\"\"\",GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ,8,60,SHA256\n\
+                    no secret here\n\
+                    Broken,not-base32!\n\
+                    Empty,\n";
+        let (tabs, errors) = parse_csv_accounts(text, false);
+
+        let names: Vec<&str> = tabs.iter().map(|tab| tab.issuer.as_str()).collect();
+        assert_eq!(names, ["GitHub", "ACME, Inc. \"prod\""]);
+        // The secret is always the second column, whatever follows it
+        assert_eq!(tabs[0].secret_key, "JBSWY3DPEHPK3PXP");
+        assert_eq!(tabs[1].secret_key, "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert!(tabs.iter().all(|tab| !tab.editing_name));
+
+        assert_eq!(errors.len(), 3);
+        assert!(errors[0].starts_with("line 6: expected name,secret"));
+        assert!(errors[1].starts_with("line 7: "));
+        assert!(errors[2].starts_with("line 8: expected name,secret"));
+    }
+
+    #[test]
+    fn csv_header_is_only_skipped_on_the_first_row() {
+        let (tabs, errors) = parse_csv_accounts("GitHub,JBSWY3DPEHPK3PXP\nname,secret\n", false);
+        assert_eq!(tabs.len(), 1);
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn csv_fields_follow_rfc4180_quoting() {
+        assert_eq!(csv_fields(" a , b ,c"), Ok(vec!["a".to_string(), "b".to_string(), "c".to_string()]));
+        assert_eq!(
+            csv_fields("\"x, \"\"y\"\"\" ,z"),
+            Ok(vec!["x, \"y\"".to_string(), "z".to_string()])
+        );
+        assert_eq!(csv_fields("a,,"), Ok(vec!["a".to_string(), String::new(), String::new()]));
+        assert!(csv_fields("\"open,secret").is_err());
+        assert!(csv_fields("a\"b,secret").is_err());
+        assert!(csv_fields("\"a\"b,secret").is_err());
+    }
+
+    #[test]
+    fn csv_quoted_field_can_span_lines() {
+        let records = csv_records("\"two\nlines\",JBSWY3DPEHPK3PXP\nnext,GEZDGNBVGY3TQOJQ\n");
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0], (1, Ok(vec!["two\nlines".to_string(), "JBSWY3DPEHPK3PXP".to_string()])));
+        assert_eq!(records[1].0, 3);
+    }
+
+    #[test]
+    fn csv_stray_quote_only_fails_its_own_line() {
+        let text = "Bob\"s,JBSWY3DPEHPK3PXP\nGitHub,JBSWY3DPEHPK3PXP\nACME,GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ\n";
+        let (tabs, errors) = parse_csv_accounts(text, false);
+        let names: Vec<&str> = tabs.iter().map(|tab| tab.issuer.as_str()).collect();
+        assert_eq!(names, ["GitHub", "ACME"]);
+        assert_eq!(errors, vec!["line 1: unexpected quote inside a field".to_string()]);
+
+        // A quoted field left open at the end of the file is still one error
+        let (tabs, errors) = parse_csv_accounts("GitHub,JBSWY3DPEHPK3PXP\n\"open,x\nmore\n", false);
+        assert_eq!(tabs.len(), 1);
+        assert_eq!(errors, vec!["line 2: quoted field is never closed".to_string()]);
     }
-    
-    // Helper function to pad a key to at least 16 bytes (128 bits)
-    fn pad_key(key: Vec<u8>) -> Vec<u8> {
-        // If the key is too short, extend it with zeros
-        // This matches how standard TOTP implementations handle short keys
-        if key.len() < 16 {
-            let mut padded = key.clone();
-            padded.resize(16, 0); // Zero-pad to 16 bytes
-            return padded;
-        }
-        key
+
+    #[test]
+    fn csv_row_quotes_names_with_commas_and_quotes() {
+        let tab = Tab {
+            issuer: "ACME, Inc. \"prod\"".to_string(),
+            account: String::new(),
+            ..tab_with("jbsw y3dp ehpk 3pxp", Algorithm::SHA256, 8)
+        };
+        assert_eq!(
+            csv_row(&tab, false).as_deref(),
+            Some("\"ACME, Inc. \"\"prod\"\"\",JBSWY3DPEHPK3PXP,8,30,SHA256")
+        );
+        assert_eq!(csv_row(&tab_with("", Algorithm::SHA1, 6), false), None);
     }
-    
-    fn generate_token(&mut self, tab_index: usize) {
-        if tab_index >= self.tabs.len() {
-            return;
-        }
-        
-        let tab = &mut self.tabs[tab_index];
-        
-        if tab.secret_key.is_empty() {
-            tab.error = Some("Please enter a secret key".to_string());
-            tab.token = String::new();
-            return;
-        }
 
-        // Get current time
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
-        
-        // Decode the key
-        let decoded_key = Self::decode_secret(&tab.secret_key);
-        
-        // Create the TOTP with the decoded key
-        match TOTP::new(
-            Algorithm::SHA1,
-            self.digits as usize,
-            1,
-            self.period,
-            decoded_key,
-        ) {
-            Ok(totp) => {
-                match totp.generate_current() {
-                    Ok(token) => {
-                        tab.token = token;
-                        tab.error = None;
-                        
-                        // Update time remaining
-                        tab.time_remaining = self.period - (now % self.period);
-                    }
-                    Err(e) => {
-                        tab.error = Some(format!("Failed to generate token: {}", e));
-                        tab.token = String::new();
-                    }
-                }
-            }
-            Err(e) => {
-                tab.error = Some(format!("Invalid secret key: {}", e));
-                tab.token = String::new();
-            }
+    #[test]
+    fn csv_export_imports_back_to_the_same_accounts() {
+        let tabs = vec![
+            Tab {
+                issuer: "ACME, Inc. \"prod\"".to_string(),
+                account: "ops".to_string(),
+                period: 60,
+                ..tab_with("JBSWY3DPEHPK3PXP", Algorithm::SHA256, 8)
+            },
+            Tab {
+                issuer: "Hex".to_string(),
+                secret_format: SecretFormat::Hex,
+                ..tab_with("3132333435363738393031323334353637383930", Algorithm::SHA512, 7)
+            },
+            // No usable secret, so left out of the export
+            tab_with("", Algorithm::SHA1, 6),
+        ];
+        let (csv, count) = accounts_csv(&tabs, false);
+        assert_eq!(count, 2);
+
+        let (imported, errors) = parse_csv_accounts(&csv, false);
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(imported.len(), 2);
+        for (original, imported) in tabs.iter().zip(&imported) {
+            assert_eq!(imported.display_name(), original.display_name());
+            assert_eq!(imported.decode_key(false), original.decode_key(false));
+            assert_eq!(
+                (imported.digits, imported.period, imported.algorithm),
+                (original.digits, original.period, original.algorithm)
+            );
+            assert_eq!(token_for_timestamp(imported, 59, false), token_for_timestamp(original, 59, false));
         }
     }
-}
 
-// Custom styles for message containers, buttons and tabs
-struct SuccessMessageStyle;
-struct ErrorMessageStyle;
-struct BlueButtonStyle;
-struct ActiveTabButtonStyle;
-struct InactiveTabButtonStyle;
+    #[test]
+    fn csv_import_rejects_bad_code_settings() {
+        let (tabs, errors) = parse_csv_accounts(
+            "a,JBSWY3DPEHPK3PXP,9\nb,JBSWY3DPEHPK3PXP,6,0\nc,JBSWY3DPEHPK3PXP,6,30,MD5\nd,JBSWY3DPEHPK3PXP,,,\n",
+            false,
+        );
+        assert_eq!(errors.len(), 3);
+        assert_eq!(tabs.len(), 1);
+        assert_eq!((tabs[0].digits, tabs[0].period, tabs[0].algorithm), (6, 30, Algorithm::SHA1));
+    }
 
-impl iced::widget::container::StyleSheet for SuccessMessageStyle {
-    type Style = iced::Theme;
+    #[test]
+    fn tab_menu_entries_act_on_the_tab_that_was_clicked() {
+        assert!(matches!(tab_menu_message(TabMenuAction::Rename, 2), Message::RenameTabStarted(2)));
+        assert!(matches!(tab_menu_message(TabMenuAction::Duplicate, 2), Message::DuplicateTab(2)));
+        assert!(matches!(tab_menu_message(TabMenuAction::Remove, 2), Message::RequestRemoveTab(2)));
+        assert!(matches!(tab_menu_message(TabMenuAction::TogglePin, 2), Message::TogglePinned(2)));
+        assert!(matches!(
+            tab_menu_message(TabMenuAction::SetColor(Some(TabColor::Green)), 2),
+            Message::TabColorChanged(Some(TabColor::Green), 2)
+        ));
 
-    fn appearance(&self, _style: &Self::Style) -> iced::widget::container::Appearance {
-        iced::widget::container::Appearance {
-            background: Some(iced::Background::Color(iced::Color::from_rgba(0.0, 0.5, 0.0, 0.1))),
-            border_radius: 4.0,
-            border_width: 1.0,
-            border_color: iced::Color::from_rgb(0.0, 0.5, 0.0),
+        let mut app = TotpGenerator {
+            tabs: vec![Tab::default(), Tab::default(), Tab::default()],
             ..Default::default()
-        }
+        };
+        let _ = app.handle_message(Message::ShowTabMenu(1));
+        let _ = app.handle_message(Message::TabMenuSelected(TabMenuAction::TogglePin));
+        assert!(app.tabs[1].pinned);
+        assert!(!app.tabs[0].pinned && !app.tabs[2].pinned);
+        assert_eq!(app.tab_menu, None);
+
+        // With the menu closed, a stray selection does nothing
+        let _ = app.handle_message(Message::TabMenuSelected(TabMenuAction::TogglePin));
+        assert!(app.tabs[1].pinned);
+        let _ = app.handle_message(Message::ShowTabMenu(7));
+        assert_eq!(app.tab_menu, None);
     }
-}
 
-impl iced::widget::container::StyleSheet for ErrorMessageStyle {
-    type Style = iced::Theme;
+    #[test]
+    fn duplicate_copies_the_settings_but_not_the_secret() {
+        let original = Tab {
+            issuer: "ACME".to_string(),
+            account: "alice".to_string(),
+            period: 60,
+            token: "12345678".to_string(),
+            ..tab_with("JBSWY3DPEHPK3PXP", Algorithm::SHA256, 8)
+        };
+        let copy = original.duplicate();
+        assert_eq!((copy.digits, copy.period, copy.algorithm), (8, 60, Algorithm::SHA256));
+        assert_eq!((copy.issuer.as_str(), copy.account.as_str()), ("ACME (copy)", "alice"));
+        assert!(copy.secret_key.is_empty());
+        assert!(copy.token.is_empty());
 
-    fn appearance(&self, _style: &Self::Style) -> iced::widget::container::Appearance {
-        iced::widget::container::Appearance {
-            background: Some(iced::Background::Color(iced::Color::from_rgba(0.8, 0.0, 0.0, 0.1))),
-            border_radius: 4.0,
-            border_width: 1.0,
-            border_color: iced::Color::from_rgb(0.8, 0.0, 0.0),
+        // Without an issuer the account carries the "(copy)" mark
+        let copy = Tab { issuer: String::new(), ..original }.duplicate();
+        assert_eq!((copy.issuer.as_str(), copy.account.as_str()), ("", "alice (copy)"));
+    }
+
+    #[test]
+    fn code_shown_after_a_step_boundary_is_the_new_steps() {
+        let at = |secs: u64| UNIX_EPOCH + Duration::from_secs(secs);
+        let mut app = TotpGenerator {
+            tabs: vec![tab_with("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ", Algorithm::SHA1, 6)],
             ..Default::default()
+        };
+        app.generate_token_at(0, at(1_700_000_010));
+        let new_step = app.tabs[0].token.clone();
+
+        app.generate_token_at(0, at(1_700_000_009));
+        let old_step = app.tabs[0].token.clone();
+        assert_ne!(old_step, new_step);
+        assert_eq!(app.tabs[0].next_token, new_step);
+
+        // Two seconds into the new step the recomputed counter has moved on
+        assert!(app.tabs[0].needs_regeneration(1_700_000_012));
+        app.generate_token_at(0, at(1_700_000_012));
+        assert_eq!(app.tabs[0].token, new_step);
+        assert!(step_boundary_missed(1_700_000_012, 30));
+        assert!(!step_boundary_missed(1_700_000_010, 30));
+    }
+
+    #[test]
+    fn zero_message_timeout_keeps_the_message_up() {
+        let mut tab = Tab::default();
+        tab.show_timed_message("Code copied to clipboard!".to_string(), 0);
+        for _ in 0..10 {
+            tab.tick_message();
         }
+        assert_eq!(tab.error.as_deref(), Some("Code copied to clipboard!"));
+
+        tab.show_timed_message("Code copied to clipboard!".to_string(), 1);
+        tab.tick_message();
+        assert_eq!(tab.error, None);
     }
-}
 
-impl iced::widget::button::StyleSheet for BlueButtonStyle {
-    type Style = iced::Theme;
+    #[test]
+    fn countdown_dots_at_the_extremes() {
+        // All lit at the start of the period, the last going out only at expiry
+        assert_eq!(countdown_lit_dots(30, 30), (10, 10));
+        assert_eq!(countdown_lit_dots(1, 30), (1, 10));
+        assert_eq!(countdown_lit_dots(0, 30), (0, 10));
+        // Long periods are capped, and more time left than the period is a full row
+        assert_eq!(countdown_lit_dots(90, 90), (20, 20));
+        assert_eq!(countdown_lit_dots(45, 30), (10, 10));
+        // Very short or zero periods still get one dot
+        assert_eq!(countdown_lit_dots(1, 1), (1, 1));
+        assert_eq!(countdown_lit_dots(0, 0), (0, 1));
+    }
 
-    fn active(&self, _style: &Self::Style) -> iced::widget::button::Appearance {
-        iced::widget::button::Appearance {
-            background: Some(iced::Background::Color(iced::Color::from_rgb(0.0, 0.5, 0.9))),
-            border_radius: 4.0,
-            text_color: iced::Color::WHITE,
+    #[test]
+    fn favicon_domain_reads_hosts_and_plain_names() {
+        assert_eq!(favicon_domain("GitHub").as_deref(), Some("github.com"));
+        assert_eq!(favicon_domain("  Google Cloud ").as_deref(), Some("google.com"));
+        assert_eq!(favicon_domain("gitlab.example.org").as_deref(), Some("gitlab.example.org"));
+        assert_eq!(favicon_domain("https://www.GitHub.com/login?x=1").as_deref(), Some("github.com"));
+        assert_eq!(favicon_domain("http://example.com:8080").as_deref(), Some("example.com"));
+        assert_eq!(favicon_domain(""), None);
+        assert_eq!(favicon_domain("AT&T"), None);
+        assert_eq!(favicon_domain("bad..domain"), None);
+    }
+
+    #[cfg(feature = "favicons")]
+    #[test]
+    fn favicon_url_points_at_the_site_root() {
+        assert_eq!(favicon_url("github.com"), "https://github.com/favicon.ico");
+    }
+
+    #[test]
+    fn favicons_are_looked_up_only_when_issuers_change() {
+        let mut app = TotpGenerator {
+            show_favicons: true,
+            tabs: vec![Tab { issuer: "GitHub".to_string(), ..Default::default() }],
             ..Default::default()
-        }
+        };
+        let _ = app.fetch_missing_favicons();
+        assert!(app.favicons.contains_key("github.com"));
+
+        // Nothing changed, so a later update doesn't go through the tabs again
+        app.favicons.clear();
+        let _ = app.fetch_missing_favicons();
+        assert!(app.favicons.is_empty());
+
+        app.tabs[0].issuer = "gitlab.com".to_string();
+        let _ = app.fetch_missing_favicons();
+        assert!(app.favicons.contains_key("gitlab.com"));
     }
-    
-    fn hovered(&self, style: &Self::Style) -> iced::widget::button::Appearance {
-        let active = self.active(style);
-        
-        iced::widget::button::Appearance {
-            background: Some(iced::Background::Color(iced::Color::from_rgb(0.1, 0.6, 1.0))),
-            ..active
-        }
+
+    #[test]
+    fn steam_export_round_trips_without_adjustments() {
+        let tab = Tab {
+            issuer: "Steam".to_string(),
+            account: "gaben".to_string(),
+            mode: OtpMode::Steam,
+            ..tab_with("JBSWY3DPEHPK3PXP", Algorithm::SHA1, 6)
+        };
+        let uri = build_otpauth_uri(&tab, false).unwrap();
+        assert!(uri.contains("digits=5"));
+
+        let parsed = parse_otpauth_uri(&uri).unwrap();
+        assert_eq!(parsed.mode, OtpMode::Steam);
+        assert!(parsed.adjusted.is_empty(), "{:?}", parsed.adjusted);
+        // Steam ignores digits, so the tab keeps a valid value for if it is switched to TOTP
+        assert_eq!(parsed.digits, 6);
     }
-}
 
-impl iced::widget::button::StyleSheet for ActiveTabButtonStyle {
-    type Style = iced::Theme;
+    #[test]
+    fn out_of_range_digits_are_clamped_with_a_warning() {
+        let parse = |digits: &str| {
+            parse_otpauth_uri(&format!("otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP&digits={}", digits)).unwrap()
+        };
+        let parsed = parse("10");
+        assert_eq!(parsed.digits, 8);
+        assert_eq!(parsed.adjusted, vec!["digits=10 is not supported, using 8".to_string()]);
 
-    fn active(&self, _style: &Self::Style) -> iced::widget::button::Appearance {
-        iced::widget::button::Appearance {
-            background: Some(iced::Background::Color(iced::Color::from_rgb(0.95, 0.95, 0.95))),
-            border_radius: 6.0,
-            border_width: 1.0,
-            border_color: iced::Color::from_rgb(0.7, 0.7, 0.7),
-            shadow_offset: iced::Vector::new(0.0, 0.0),
-            text_color: iced::Color::from_rgb(0.1, 0.1, 0.1),
+        let parsed = parse("4");
+        assert_eq!(parsed.digits, 6);
+        assert_eq!(parsed.adjusted, vec!["digits=4 is not supported, using 6".to_string()]);
+
+        let parsed = parse("7");
+        assert_eq!(parsed.digits, 7);
+        assert!(parsed.adjusted.is_empty());
+
+        let mut tab = Tab::default();
+        assert_eq!(
+            tab.apply_parsed_otp(parse("9")).as_deref(),
+            Some("Adjusted the otpauth URI: digits=9 is not supported, using 8")
+        );
+        assert_eq!(tab.digits, 8);
+    }
+
+    #[test]
+    fn codes_are_masked_when_hidden_or_obscured() {
+        assert!(!code_masked(false, false, false));
+        assert!(code_masked(true, false, false));
+        // A reveal shows the code, but not while the window is obscured
+        assert!(!code_masked(true, true, false));
+        assert!(code_masked(true, true, true));
+        assert!(code_masked(false, false, true));
+    }
+
+    #[test]
+    fn preview_time_reads_timestamps_offsets_and_dates() {
+        // 2009-02-13 23:31:30 UTC is one of the RFC 6238 test times
+        assert_eq!(parse_preview_time("2009-02-13 23:31:30", 0), Some(1_234_567_890));
+        assert_eq!(parse_preview_time(" 1234567890 ", 0), Some(1_234_567_890));
+        assert_eq!(parse_preview_time("+90", 1_000), Some(1_090));
+        assert_eq!(parse_preview_time("- 30", 1_000), Some(970));
+        assert_eq!(parse_preview_time("-2000", 1_000), None);
+        assert_eq!(parse_preview_time("yesterday", 1_000), None);
+
+        let tab = tab_with("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ", Algorithm::SHA1, 8);
+        let time = parse_preview_time("2009-02-13 23:31:30", 0).unwrap();
+        assert_eq!(token_for_timestamp(&tab, time, false).unwrap(), "89005924");
+    }
+
+    #[test]
+    fn grace_window_covers_three_consecutive_steps() {
+        let now = 1_111_111_111;
+        let times = grace_window_timestamps(now, 30);
+        assert_eq!(times, [1_111_111_081, now, 1_111_111_141]);
+        let steps: Vec<u64> = times.iter().map(|&time| current_counter(time, 30)).collect();
+        assert_eq!(steps, [37_037_036, 37_037_037, 37_037_038]);
+
+        // The previous and current codes are the RFC 6238 vectors for those steps
+        let tab = tab_with("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ", Algorithm::SHA1, 8);
+        let codes: Vec<String> = times
+            .iter()
+            .map(|&time| token_for_timestamp(&tab, time, false).unwrap())
+            .collect();
+        assert_eq!(codes[..2], ["07081804", "14050471"]);
+        assert_ne!(codes[2], codes[1]);
+
+        // Near the start of time the window can't reach back a step
+        assert_eq!(grace_window_timestamps(10, 30), [0, 10, 40]);
+    }
+
+    #[test]
+    fn recency_order_puts_pinned_then_latest_first() {
+        let used = |at: Option<u64>, pinned: bool| Tab { shown_last_used: at, pinned, ..Default::default() };
+        assert_eq!(compare_by_recency(&used(Some(20), false), &used(Some(10), false)), Ordering::Less);
+        assert_eq!(compare_by_recency(&used(Some(10), false), &used(Some(20), false)), Ordering::Greater);
+        // Never used sorts last; pinned beats any timestamp
+        assert_eq!(compare_by_recency(&used(None, false), &used(Some(1), false)), Ordering::Greater);
+        assert_eq!(compare_by_recency(&used(None, true), &used(Some(99), false)), Ordering::Less);
+        assert_eq!(compare_by_recency(&used(Some(5), true), &used(Some(5), true)), Ordering::Equal);
+    }
+
+    #[test]
+    fn recency_order_holds_still_while_tabs_are_selected() {
+        let used = |at: u64| Tab { last_used: Some(at), editing_name: false, ..Default::default() };
+        let mut app = TotpGenerator {
+            tabs: vec![used(30), used(20), used(10)],
+            ..Default::default()
+        };
+        let _ = app.handle_message(Message::TabOrderChanged(TabOrder::RecentlyUsed));
+        assert_eq!(app.ordered_tab_indices(), [0, 1, 2]);
+
+        let _ = app.handle_message(Message::SelectTab(2));
+        assert_eq!(app.ordered_tab_indices(), [0, 1, 2]);
+
+        let _ = app.handle_message(Message::WindowFocusChanged(false));
+        assert_eq!(app.ordered_tab_indices(), [2, 0, 1]);
+    }
+
+    #[test]
+    fn selectable_code_only_replaces_a_missing_clipboard() {
+        assert!(!selectable_token_fallback(false, false));
+        assert!(selectable_token_fallback(true, false));
+        // A masked code must not become selectable plain text
+        assert!(!selectable_token_fallback(true, true));
+        assert!(!selectable_token_fallback(false, true));
+    }
+
+    #[test]
+    fn secret_is_masked_again_after_switching_tabs() {
+        let mut app = TotpGenerator {
+            tabs: vec![Tab::default(), Tab::default()],
+            ..Default::default()
+        };
+        assert!(!app.tabs[0].secret_visible);
+
+        let _ = app.handle_message(Message::ToggleSecretVisibility(0));
+        assert!(app.tabs[0].secret_visible);
+        assert!(!app.tabs[1].secret_visible);
+        let _ = app.handle_message(Message::ToggleSecretVisibility(0));
+        assert!(!app.tabs[0].secret_visible);
+
+        let _ = app.handle_message(Message::ToggleSecretVisibility(0));
+        let _ = app.handle_message(Message::SelectTab(1));
+        let _ = app.handle_message(Message::SelectTab(0));
+        assert!(!app.tabs[0].secret_visible);
+
+        // Pasting while masked still sets the secret
+        let _ = app.handle_message(Message::SecretKeyChanged("JBSWY3DPEHPK3PXP".to_string(), 0));
+        assert_eq!(app.tabs[0].secret_key, "JBSWY3DPEHPK3PXP");
+        assert!(!app.tabs[0].secret_visible);
+    }
+
+    #[test]
+    fn counts_stray_unicode_whitespace_and_hidden_characters() {
+        assert_eq!(ignored_secret_chars("JBSWY3DPEHPK3PXP"), 0);
+        // Plain grouping spaces and dashes inside the key are intended
+        assert_eq!(ignored_secret_chars("JBSW Y3DP-EHPK 3PXP"), 0);
+        // Whitespace at the edges is counted, of any kind
+        assert_eq!(ignored_secret_chars("  JBSWY3DPEHPK3PXP\n"), 3);
+        assert_eq!(ignored_secret_chars("\u{00A0}JBSWY3DPEHPK3PXP\u{00A0}"), 2);
+        // Inside the key only non-ASCII whitespace and hidden characters are
+        assert_eq!(ignored_secret_chars("JBSW\u{00A0}Y3DP\u{200B}EHPK3PXP"), 2);
+        assert_eq!(ignored_secret_chars("\u{200B}JBSWY3DP EHPK3PXP\u{FEFF}"), 2);
+        assert_eq!(ignored_secret_chars(""), 0);
+    }
+
+    #[test]
+    fn auto_copy_fires_only_for_the_intended_tabs() {
+        let on = Tab { auto_copy: true, ..Tab::default() };
+        let off = Tab::default();
+        assert!(should_auto_copy(&on, true, false));
+        assert!(!should_auto_copy(&on, false, false));
+        assert!(should_auto_copy(&on, false, true));
+        assert!(!should_auto_copy(&off, true, false));
+        assert!(!should_auto_copy(&off, true, true));
+    }
+
+    #[test]
+    fn status_line_shows_code_time_and_offset() {
+        assert_eq!(status_line(1_700_000_000, 0), "Code time 2023-11-14 22:13:20 UTC · no clock offset");
+        assert_eq!(status_line(1_700_000_003, 3), "Code time 2023-11-14 22:13:23 UTC · clock offset +3s");
+        assert_eq!(status_line(0, -90), "Code time 1970-01-01 00:00:00 UTC · clock offset -90s");
+        assert_eq!(format_utc_time(u64::MAX), "out of range");
+    }
+
+    #[test]
+    fn only_a_gap_longer_than_two_periods_counts_as_waking_up() {
+        assert!(!woke_from_sleep(1_000, 1_001, 30));
+        // A tick that is late by a few seconds is not a sleep
+        assert!(!woke_from_sleep(1_000, 1_015, 30));
+        assert!(!woke_from_sleep(1_000, 1_060, 30));
+        assert!(woke_from_sleep(1_000, 1_061, 30));
+        assert!(woke_from_sleep(1_000, 4_600, 30));
+        // Longer periods need a longer gap
+        assert!(!woke_from_sleep(1_000, 1_100, 60));
+        // A clock that went backwards isn't a wake-up
+        assert!(!woke_from_sleep(5_000, 1_000, 30));
+        assert!(!woke_from_sleep(1_000, 1_002, 0));
+        assert!(woke_from_sleep(1_000, 1_003, 0));
+    }
+
+    #[test]
+    fn ctrl_t_adds_and_ctrl_w_removes_tabs() {
+        let ctrl = |key_code| {
+            Event::Keyboard(keyboard::Event::KeyPressed {
+                key_code,
+                modifiers: keyboard::Modifiers::COMMAND,
+            })
+        };
+        let ignored = event::Status::Ignored;
+        assert!(matches!(handle_event(ctrl(keyboard::KeyCode::T), ignored), Some(Message::AddTab)));
+        assert!(matches!(
+            handle_event(ctrl(keyboard::KeyCode::W), ignored),
+            Some(Message::RequestRemoveActiveTab)
+        ));
+        let plain_t = Event::Keyboard(keyboard::Event::KeyPressed {
+            key_code: keyboard::KeyCode::T,
+            modifiers: keyboard::Modifiers::empty(),
+        });
+        assert!(handle_event(plain_t, ignored).is_none());
+
+        let mut app = TotpGenerator {
+            tabs: vec![Tab::default()],
             ..Default::default()
+        };
+        let _ = app.handle_message(Message::AddTab);
+        assert_eq!((app.tabs.len(), app.active_tab), (2, 1));
+        let _ = app.handle_message(Message::RequestRemoveActiveTab);
+        assert_eq!(app.pending_removal, Some(1));
+        let _ = app.handle_message(Message::ConfirmRemoveTab);
+        assert_eq!(app.tabs.len(), 1);
+
+        // The last tab stays, and there is nothing to confirm
+        let _ = app.handle_message(Message::RequestRemoveActiveTab);
+        assert_eq!(app.pending_removal, None);
+        assert_eq!(app.tabs.len(), 1);
+    }
+
+    #[test]
+    fn history_keeps_only_the_latest_codes() {
+        let mut tab = Tab::default();
+        for step in 0..12u64 {
+            tab.record_history(step * 30, format!("{:06}", step), HISTORY_LEN);
         }
+        assert_eq!(tab.history.len(), HISTORY_LEN);
+        assert_eq!(tab.history.front(), Some(&(60, "000002".to_string())));
+        assert_eq!(tab.history.back(), Some(&(330, "000011".to_string())));
+
+        // Off by default, so nothing is recorded
+        let mut app = TotpGenerator {
+            tabs: vec![tab_with("JBSWY3DPEHPK3PXP", Algorithm::SHA1, 6)],
+            ..Default::default()
+        };
+        app.generate_token(0);
+        assert!(app.tabs[0].history.is_empty());
+        app.keep_history = true;
+        app.generate_token_at(0, UNIX_EPOCH + Duration::from_secs(1_700_000_010));
+        assert_eq!(app.tabs[0].history.len(), 1);
+
+        // The history is never written to disk
+        let json = serde_json::to_string(&app.tabs[0]).unwrap();
+        assert!(!json.contains("history"));
     }
-    
-    fn hovered(&self, style: &Self::Style) -> iced::widget::button::Appearance {
-        let active = self.active(style);
-        
-        iced::widget::button::Appearance {
-            background: Some(iced::Background::Color(iced::Color::from_rgb(1.0, 1.0, 1.0))),
-            ..active
+
+    #[test]
+    fn escape_cancels_renames_only_when_no_field_handled_it() {
+        let escape = || {
+            Event::Keyboard(keyboard::Event::KeyPressed {
+                key_code: keyboard::KeyCode::Escape,
+                modifiers: keyboard::Modifiers::empty(),
+            })
+        };
+        assert!(matches!(
+            handle_event(escape(), event::Status::Ignored),
+            Some(Message::CancelRenames)
+        ));
+        assert!(handle_event(escape(), event::Status::Captured).is_none());
+    }
+
+    #[test]
+    fn collapsed_issuer_groups_ignore_case_and_spaces() {
+        let issuer = |name: &str| Tab { issuer: name.to_string(), ..Default::default() };
+        let mut app = TotpGenerator {
+            tabs: vec![issuer("GitHub"), issuer("AWS"), issuer(" github ")],
+            ..Default::default()
+        };
+        let groups = app.grouped_tabs();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0], ("GitHub".to_string(), vec![0, 2]));
+
+        let _ = app.handle_message(Message::ToggleIssuerGroup("GitHub".to_string()));
+        assert!(app.collapsed_groups.contains("github"));
+
+        // Once the tab naming the group is gone, the group keeps its collapsed state
+        app.tabs.remove(0);
+        let (name, _) = &app.grouped_tabs()[1];
+        assert_eq!(name, "github");
+        assert!(app.collapsed_groups.contains(&issuer_group_key(name)));
+
+        let _ = app.handle_message(Message::ToggleIssuerGroup(" GITHUB".to_string()));
+        assert!(app.collapsed_groups.is_empty());
+    }
+
+    #[test]
+    fn refresh_regenerates_only_the_targeted_tab() {
+        let mut app = TotpGenerator {
+            tabs: vec![
+                tab_with("JBSWY3DPEHPK3PXP", Algorithm::SHA1, 6),
+                tab_with("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ", Algorithm::SHA1, 6),
+                Tab::default(),
+            ],
+            ..Default::default()
+        };
+        let _ = app.handle_message(Message::GenerateToken(1));
+        assert!(app.tabs[0].token.is_empty());
+        assert_eq!(app.tabs[1].token.len(), 6);
+
+        // A tab without a secret is left alone rather than shown an error
+        let _ = app.handle_message(Message::GenerateToken(2));
+        assert_eq!(app.tabs[2].error, None);
+        let _ = app.handle_message(Message::GenerateToken(7));
+    }
+
+    #[test]
+    fn format_token_groups_each_supported_length() {
+        assert_eq!(format_token("123456"), "123 456");
+        assert_eq!(format_token("1234567"), "1234 567");
+        assert_eq!(format_token("12345678"), "1234 5678");
+        // Steam codes and anything unexpected are shown as they are
+        assert_eq!(format_token("PV9M4"), "PV9M4");
+        assert_eq!(format_token("123456789"), "123456789");
+    }
+
+    #[test]
+    fn copy_and_mask_forms_share_the_grouping() {
+        assert_eq!(copied_token("1234 567", true), "1234 567");
+        assert_eq!(copied_token("1234 567", false), "1234567");
+        assert_eq!(copied_token("12345678", true), "1234 5678");
+        assert_eq!(mask_token("123456"), "••• •••");
+        assert_eq!(mask_token("1234567"), "•••• •••");
+        assert_eq!(mask_token("12345678"), "•••• ••••");
+    }
+
+    #[test]
+    fn mini_mode_toggles_and_follows_the_active_tab() {
+        let mut app = TotpGenerator {
+            tabs: vec![
+                Tab { token: "123456".to_string(), ..Tab::default() },
+                Tab { token: "654321".to_string(), ..Tab::default() },
+            ],
+            active_tab: 1,
+            focused: true,
+            ..Default::default()
+        };
+        let full_size = app.window;
+
+        let _ = app.handle_message(Message::ToggleMiniMode);
+        assert!(app.mini);
+        assert_eq!(app.mini_token_text(), "654 321");
+
+        let _ = app.handle_message(Message::SelectTab(0));
+        assert_eq!(app.mini_token_text(), "123 456");
+
+        let _ = app.handle_message(Message::ToggleMiniMode);
+        assert!(!app.mini);
+        // The full-size geometry is kept for when the mini window is left
+        assert_eq!(app.window, full_size);
+    }
+
+    #[test]
+    fn otpauth_link_on_the_command_line_opens_a_tab() {
+        let uri = "otpauth://totp/ACME:alice?secret=JBSWY3DPEHPK3PXP&issuer=ACME&digits=8&period=60";
+        let launch_uri = match parse_cli_args(&[uri.to_string()]) {
+            Ok(LaunchMode::Gui { secret_file: None, launch_uri }) => launch_uri,
+            other => panic!("unexpected launch mode {:?}", other),
+        };
+        assert_eq!(launch_uri.as_deref(), Some(uri));
+
+        // The empty starting tab is set up from the link
+        let mut app = TotpGenerator { launch_uri: launch_uri.clone(), ..Default::default() };
+        app.apply_launch_uri();
+        assert_eq!(app.tabs.len(), 1);
+        let tab = &app.tabs[0];
+        assert_eq!((tab.issuer.as_str(), tab.account.as_str()), ("ACME", "alice"));
+        assert_eq!((tab.digits, tab.period), (8, 60));
+        assert_eq!(tab.token.len(), 8);
+        assert_eq!(app.launch_uri, None);
+
+        // A tab that already has the secret is selected instead of adding a new one
+        let mut app = TotpGenerator {
+            tabs: vec![
+                tab_with("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ", Algorithm::SHA1, 6),
+                tab_with("jbsw y3dp ehpk 3pxp", Algorithm::SHA1, 6),
+            ],
+            launch_uri,
+            ..Default::default()
+        };
+        app.apply_launch_uri();
+        assert_eq!(app.tabs.len(), 2);
+        assert_eq!(app.active_tab, 1);
+
+        assert!(parse_cli_args(&[uri.to_string(), "--secret".to_string(), "JBSWY3DPEHPK3PXP".to_string()]).is_err());
+    }
+
+    #[test]
+    fn starts_hidden_only_with_a_tray_to_come_back_from() {
+        assert!(start_hidden(true, true));
+        assert!(!start_hidden(true, false));
+        assert!(!start_hidden(false, true));
+        assert!(!start_hidden(false, false));
+    }
+
+    #[test]
+    fn clipboard_readback_reports_truncated_and_different_contents() {
+        assert_eq!(clipboard_readback_warning("123456", Some("123456")), None);
+        assert_eq!(
+            clipboard_readback_warning("GitHub: 123456\nACME: 654321", Some("GitHub: 123456")).as_deref(),
+            Some("The clipboard only kept 14 of 27 characters")
+        );
+        assert_eq!(
+            clipboard_readback_warning("123456", Some("something else")).as_deref(),
+            Some("The clipboard doesn't hold what was copied")
+        );
+        // An empty clipboard is a prefix of anything, but nothing was kept
+        assert_eq!(
+            clipboard_readback_warning("123456", Some("")).as_deref(),
+            Some("The clipboard doesn't hold what was copied")
+        );
+        assert!(clipboard_readback_warning("123456", None).is_some());
+    }
+
+    #[test]
+    fn period_presets_switch_the_countdown() {
+        let mut app = TotpGenerator {
+            tabs: vec![tab_with("JBSWY3DPEHPK3PXP", Algorithm::SHA1, 6)],
+            ..Default::default()
+        };
+        let clock = UNIX_EPOCH + Duration::from_secs(1_700_000_050);
+        for (preset, remaining) in PERIOD_PRESETS.into_iter().zip([5, 20, 50]) {
+            let _ = app.handle_message(Message::PeriodChanged(preset, 0));
+            assert_eq!(app.tabs[0].period, preset);
+            assert!((1..=preset).contains(&app.tabs[0].time_remaining));
+
+            app.generate_token_at(0, clock);
+            assert_eq!(app.tabs[0].time_remaining, remaining);
         }
     }
-}
 
-impl iced::widget::button::StyleSheet for InactiveTabButtonStyle {
-    type Style = iced::Theme;
+    #[test]
+    fn unix_time_rejects_implausibly_early_clocks() {
+        let plausible = UNIX_EPOCH + Duration::from_secs(MIN_PLAUSIBLE_UNIX_TIME);
+        assert_eq!(unix_time_at(plausible), Ok(MIN_PLAUSIBLE_UNIX_TIME));
+        assert!(unix_time_at(plausible - Duration::from_secs(1)).is_err());
+        assert!(unix_time_at(UNIX_EPOCH).is_err());
+        assert!(unix_time_at(UNIX_EPOCH - Duration::from_secs(86_400)).is_err());
+    }
 
-    fn active(&self, _style: &Self::Style) -> iced::widget::button::Appearance {
-        iced::widget::button::Appearance {
-            background: Some(iced::Background::Color(iced::Color::from_rgb(0.8, 0.8, 0.8))),
-            border_radius: 6.0,
-            border_width: 1.0,
-            border_color: iced::Color::from_rgb(0.6, 0.6, 0.6),
-            shadow_offset: iced::Vector::new(0.0, 0.0),
-            text_color: iced::Color::from_rgb(0.4, 0.4, 0.4),
+    #[test]
+    fn wrong_clock_shows_an_error_instead_of_a_code() {
+        let mut app = TotpGenerator {
+            tabs: vec![tab_with("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ", Algorithm::SHA1, 6)],
             ..Default::default()
+        };
+        app.generate_token_at(0, UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+        assert!(!app.tabs[0].token.is_empty());
+        assert_eq!(app.tabs[0].error, None);
+
+        app.generate_token_at(0, UNIX_EPOCH - Duration::from_secs(60));
+        assert!(app.tabs[0].token.is_empty());
+        assert!(app.tabs[0].next_token.is_empty());
+        assert_eq!(app.tabs[0].error.as_deref(), unix_time_at(UNIX_EPOCH).err().as_deref());
+
+        app.generate_token_at(0, UNIX_EPOCH + Duration::from_secs(59));
+        assert!(app.tabs[0].token.is_empty());
+        assert!(app.tabs[0].error.is_some());
+    }
+
+    #[test]
+    fn tab_width_grows_with_label_text_size_and_buttons() {
+        let base = estimated_tab_width("GitHub", 14.0, 0);
+        assert!(base > 16.0);
+        assert!(estimated_tab_width("GitHub (work)", 14.0, 0) > base);
+        assert!(estimated_tab_width("GitHub", 20.0, 0) > base);
+        assert_eq!(estimated_tab_width("GitHub", 14.0, 2), base + 2.0 * (14.0 + 12.0));
+        // Characters, not bytes, set the width
+        assert_eq!(estimated_tab_width("Ünïcödé", 14.0, 0), estimated_tab_width("Unicode", 14.0, 0));
+        assert_eq!(estimated_tab_width("", 14.0, 0), 16.0);
+    }
+
+    #[test]
+    fn tabs_fit_until_the_first_one_that_overflows() {
+        let widths = [100.0, 80.0, 120.0];
+        assert_eq!(tabs_that_fit(&widths, 1000.0), 3);
+        assert_eq!(tabs_that_fit(&widths, 300.0), 3);
+        assert_eq!(tabs_that_fit(&widths, 299.0), 2);
+        assert_eq!(tabs_that_fit(&widths, 180.0), 2);
+        assert_eq!(tabs_that_fit(&widths, 179.0), 1);
+        assert_eq!(tabs_that_fit(&widths, 50.0), 0);
+        assert_eq!(tabs_that_fit(&[], 50.0), 0);
+        // A narrow tab after an overflowing one still overflows, keeping the order
+        assert_eq!(tabs_that_fit(&[100.0, 500.0, 10.0], 200.0), 1);
+    }
+
+    // WCAG 2 contrast ratio between two opaque colors
+    fn contrast_ratio(a: iced::Color, b: iced::Color) -> f32 {
+        let luminance = |color: iced::Color| {
+            let linear = |c: f32| {
+                if c <= 0.03928 {
+                    c / 12.92
+                } else {
+                    ((c + 0.055) / 1.055).powf(2.4)
+                }
+            };
+            0.2126 * linear(color.r) + 0.7152 * linear(color.g) + 0.0722 * linear(color.b)
+        };
+        let (la, lb) = (luminance(a), luminance(b));
+        (la.max(lb) + 0.05) / (la.min(lb) + 0.05)
+    }
+
+    #[test]
+    fn high_contrast_palettes_meet_wcag_aaa() {
+        for dark in [false, true] {
+            let palette = app_palette(dark, true);
+            assert!(contrast_ratio(palette.text, palette.background) > 20.9, "dark: {}", dark);
+            for accent in [palette.primary, palette.success, palette.danger] {
+                let ratio = contrast_ratio(accent, palette.background);
+                assert!(ratio >= 7.0, "dark: {}, {:?} has ratio {}", dark, accent, ratio);
+            }
         }
     }
-    
-    fn hovered(&self, style: &Self::Style) -> iced::widget::button::Appearance {
-        let active = self.active(style);
-        
-        iced::widget::button::Appearance {
-            background: Some(iced::Background::Color(iced::Color::from_rgb(0.85, 0.85, 0.85))),
-            text_color: iced::Color::from_rgb(0.2, 0.2, 0.2),
-            ..active
+
+    #[test]
+    fn no_color_forces_high_contrast_without_changing_the_setting() {
+        let mut app = TotpGenerator { no_color: true, ..Default::default() };
+        assert!(!app.high_contrast);
+        assert!(is_high_contrast(&app.theme()));
+
+        app.handle_message(Message::HighContrastToggled(true));
+        app.handle_message(Message::HighContrastToggled(false));
+        assert!(!app.high_contrast);
+        assert!(is_high_contrast(&app.theme()));
+
+        app.no_color = false;
+        assert!(!is_high_contrast(&app.theme()));
+        app.high_contrast = true;
+        assert!(is_high_contrast(&app.theme()));
+    }
+
+    #[test]
+    fn progress_shows_time_left_or_time_elapsed() {
+        assert_eq!(progress_value(30, 30, ProgressFill::Remaining), 1.0);
+        assert_eq!(progress_value(15, 30, ProgressFill::Remaining), 0.5);
+        assert_eq!(progress_value(0, 30, ProgressFill::Remaining), 0.0);
+
+        assert_eq!(progress_value(30, 30, ProgressFill::Elapsed), 0.0);
+        assert_eq!(progress_value(15, 30, ProgressFill::Elapsed), 0.5);
+        assert_eq!(progress_value(0, 30, ProgressFill::Elapsed), 1.0);
+
+        for remaining in 0..=60 {
+            let left = progress_value(remaining, 60, ProgressFill::Remaining);
+            let elapsed = progress_value(remaining, 60, ProgressFill::Elapsed);
+            assert!((left + elapsed - 1.0).abs() < 1e-6);
         }
     }
+
+    #[test]
+    fn progress_stays_in_range_for_odd_inputs() {
+        // More time left than the period (e.g. right after a period change) is a full bar
+        assert_eq!(progress_value(90, 30, ProgressFill::Remaining), 1.0);
+        assert_eq!(progress_value(90, 30, ProgressFill::Elapsed), 0.0);
+        for fill in [ProgressFill::Remaining, ProgressFill::Elapsed] {
+            let value = progress_value(5, 0, fill);
+            assert!((0.0..=1.0).contains(&value));
+        }
+    }
+
+    #[test]
+    fn hotp_uri_seeds_the_counter() {
+        let parsed = parse_otpauth_uri("otpauth://hotp/Example:alice?secret=JBSWY3DPEHPK3PXP&counter=42").unwrap();
+        assert_eq!(parsed.mode, OtpMode::Hotp);
+        assert_eq!(parsed.counter, 42);
+
+        let parsed = parse_otpauth_uri("otpauth://hotp/Example:alice?secret=JBSWY3DPEHPK3PXP").unwrap();
+        assert_eq!(parsed.counter, 0);
+
+        assert!(parse_otpauth_uri("otpauth://hotp/alice?secret=JBSWY3DPEHPK3PXP&counter=-1").is_err());
+    }
+
+    #[test]
+    fn totp_uri_ignores_a_stray_counter() {
+        let parsed = parse_otpauth_uri("otpauth://totp/Example:alice?secret=JBSWY3DPEHPK3PXP&counter=42").unwrap();
+        assert_eq!(parsed.mode, OtpMode::Totp);
+        assert_eq!(parsed.counter, 0);
+        // Not even an invalid one is an error
+        assert!(parse_otpauth_uri("otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP&counter=abc").is_ok());
+    }
+
+    #[test]
+    fn decoded_key_is_cached_until_the_secret_settings_change() {
+        const MARKER: [u8; 16] = [0xAA; 16];
+        let mut app = TotpGenerator {
+            tabs: vec![tab_with("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ", Algorithm::SHA1, 6)],
+            ..Default::default()
+        };
+        app.generate_token(0);
+        // Swap the cached key for a marker, so a cache hit can be told apart from a fresh decode
+        let mark = |app: &mut TotpGenerator| {
+            app.tabs[0].decoded.as_mut().expect("key was decoded").key = MARKER.to_vec();
+        };
+        let cached = |app: &mut TotpGenerator, strict: bool| app.tabs[0].padded_key(strict) == Ok(MARKER.to_vec());
+
+        mark(&mut app);
+        assert!(cached(&mut app, false));
+        app.handle_message(Message::Tick);
+        assert!(cached(&mut app, false));
+
+        app.tabs[0].secret_key.push_str("GEZDGNBV");
+        assert!(!cached(&mut app, false));
+
+        mark(&mut app);
+        assert!(!cached(&mut app, true));
+
+        mark(&mut app);
+        app.tabs[0].base32_alphabet = Base32Alphabet::Rfc4648Hex;
+        assert!(!cached(&mut app, true));
+
+        app.tabs[0].base32_alphabet = Base32Alphabet::Rfc4648;
+        assert!(app.tabs[0].padded_key(true).is_ok());
+        mark(&mut app);
+        app.tabs[0].secret_format = SecretFormat::Hex;
+        assert!(!cached(&mut app, true));
+    }
+
+    #[test]
+    fn only_a_changed_code_counts_as_a_rollover() {
+        assert!(code_rolled_over("123456", "654321"));
+        assert!(!code_rolled_over("123456", "123456"));
+        // A code appearing for the first time or disappearing after an error
+        assert!(!code_rolled_over("", "123456"));
+        assert!(!code_rolled_over("123456", ""));
+        assert!(!code_rolled_over("", ""));
+    }
+
+    #[test]
+    fn an_earlier_flash_timer_does_not_end_a_newer_flash() {
+        let mut app = TotpGenerator::default();
+        let _ = app.start_rollover_flash();
+        let first = app.flash_generation;
+        let _ = app.start_rollover_flash();
+        let second = app.flash_generation;
+        assert_ne!(first, second);
+
+        app.handle_message(Message::RolloverFlashEnded(first));
+        assert!(app.flashing);
+        app.handle_message(Message::RolloverFlashEnded(second));
+        assert!(!app.flashing);
+    }
+
+    #[test]
+    fn active_tab_follows_removals() {
+        // Tabs 0..=4 with tab 2 active; four remain after each removal
+        assert_eq!(active_after_removal(2, 0, 4), 1, "removed before the active tab");
+        assert_eq!(active_after_removal(2, 2, 4), 2, "removed the active tab");
+        assert_eq!(active_after_removal(2, 4, 4), 2, "removed after the active tab");
+        // Removing the active last tab selects the new last tab
+        assert_eq!(active_after_removal(4, 4, 4), 3);
+        assert_eq!(active_after_removal(0, 0, 0), 0);
+    }
+
+    #[test]
+    fn removing_a_tab_keeps_the_same_tab_active() {
+        let mut app = TotpGenerator {
+            tabs: ["A", "B", "C", "D"]
+                .iter()
+                .map(|issuer| Tab { issuer: issuer.to_string(), ..Default::default() })
+                .collect(),
+            active_tab: 2,
+            ..Default::default()
+        };
+        let _ = app.handle_message(Message::RemoveTab(0));
+        assert_eq!(app.tabs[app.active_tab].issuer, "C");
+        let _ = app.handle_message(Message::RemoveTab(2));
+        assert_eq!(app.tabs[app.active_tab].issuer, "C");
+        let _ = app.handle_message(Message::RemoveTab(app.active_tab));
+        assert_eq!(app.tabs[app.active_tab].issuer, "B");
+    }
+
+    #[test]
+    fn copy_and_hide_hides_only_after_a_successful_copy() {
+        assert_eq!(hide_after_copy(true, true), Some(HideAction::ToTray));
+        assert_eq!(hide_after_copy(true, false), Some(HideAction::Minimize));
+        assert_eq!(hide_after_copy(false, true), None);
+        assert_eq!(hide_after_copy(false, false), None);
+    }
 }